@@ -0,0 +1,19 @@
+CREATE TABLE orders_progress
+(
+    order_id       INTEGER PRIMARY KEY AUTOINCREMENT,
+    name           TEXT NOT NULL CHECK (length(name) <= 255),
+    filepath       TEXT NOT NULL,
+    status         TEXT NOT NULL CHECK (status in ('QUEUED', 'IN_PROGRESS', 'DONE', 'FAILED', 'CANCELLED')),
+    result_id      INTEGER,
+    progress_stage TEXT,
+    progress       REAL,
+    FOREIGN KEY (result_id) REFERENCES results (result_id)
+);
+
+INSERT INTO orders_progress (order_id, name, filepath, status, result_id)
+SELECT order_id, name, filepath, status, result_id
+FROM orders;
+
+DROP TABLE orders;
+
+ALTER TABLE orders_progress RENAME TO orders;
@@ -23,6 +23,12 @@ pub struct Order {
 
     /// Unique identifier for the result (optional).
     pub result_id: Option<i64>,
+
+    /// Stage of the analysis in progress (optional)
+    pub progress_stage: Option<String>,
+
+    /// Fraction of the stage done, from 0 to 1 (optional)
+    pub progress: Option<f64>,
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -53,6 +59,9 @@ pub enum OrderStatus {
 
     /// Order executed with error
     Failed,
+
+    /// Order cancelled by user before or during execution
+    Cancelled,
 }
 
 impl OrderStatus {
@@ -62,6 +71,7 @@ impl OrderStatus {
             OrderStatus::InProgress => "IN_PROGRESS",
             OrderStatus::Done => "DONE",
             OrderStatus::Failed => "FAILED",
+            OrderStatus::Cancelled => "CANCELLED",
         }
     }
 }
@@ -75,6 +85,7 @@ impl TryFrom<String> for OrderStatus {
             "IN_PROGRESS" => Ok(OrderStatus::InProgress),
             "DONE" => Ok(OrderStatus::Done),
             "FAILED" => Ok(OrderStatus::Failed),
+            "CANCELLED" => Ok(OrderStatus::Cancelled),
             _ => Err(format!("Invalid status value: {}", value)),
         }
     }
@@ -222,6 +233,29 @@ pub async fn update_order_status(
     .await
 }
 
+/// Update the status of the order, only if it still has the expected status
+///
+/// Returns whether the order was updated, so a concurrent change such as cancellation is kept.
+pub async fn transition_order_status(
+    pool: &SqlitePool,
+    order_id: i64,
+    from: OrderStatus,
+    to: OrderStatus,
+) -> Result<bool, Error> {
+    let from = String::from(from);
+    let to = String::from(to);
+    let result = sqlx::query!(
+        "UPDATE orders SET status = $1 WHERE order_id = $2 AND status = $3",
+        to,
+        order_id,
+        from
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
 /// Update the result id for the order
 pub async fn update_order_result(
     pool: &SqlitePool,
@@ -237,4 +271,21 @@ pub async fn update_order_result(
     .await
 }
 
+/// Update the progress of the order
+pub async fn update_order_progress(
+    pool: &SqlitePool,
+    order_id: i64,
+    stage: &str,
+    progress: f64,
+) -> Result<Vec<SqliteRow>, Error> {
+    sqlx::query!(
+        "UPDATE orders SET progress_stage = $1, progress = $2 WHERE order_id = $3",
+        stage,
+        progress,
+        order_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
 // endregion
@@ -1,23 +1,32 @@
 //! Orders' executor
 
 use crate::db::{
-    create_result, query_orders_by_status, update_order_result, update_order_status, OrderStatus,
+    create_result, get_order_by_id, query_orders_by_status, transition_order_status,
+    update_order_progress, update_order_result, update_order_status, OrderStatus,
 };
 use crate::routes::order::create_file;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use std::env;
-use std::path::PathBuf;
-use std::process::Output;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::process::Command;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Default maximal number of clusters swept by k-medoids, unless set by `MAX_K` variable
+const DEFAULT_MAX_K: usize = 20;
+
+/// Interval of saving progress of the running analysis and checking for its cancellation
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Executes the orders and saves results
 ///
 /// On startup, moves all in-progress orders back to the queue.
 /// Periodically checks for orders in the queue and processes them.
 /// Saves results to the database.
+/// Persists progress of the running analysis and stops it once the order is cancelled.
 pub async fn executor_task(pool: Arc<SqlitePool>) {
     let orders = query_orders_by_status(&pool, OrderStatus::InProgress, None)
         .await
@@ -41,9 +50,20 @@ pub async fn executor_task(pool: Arc<SqlitePool>) {
 
         let order = &order[0];
 
-        update_order_status(&pool, order.order_id, OrderStatus::InProgress)
-            .await
-            .expect("Failed to update order status");
+        // Order cancelled after it was queried is not started
+        let started = transition_order_status(
+            &pool,
+            order.order_id,
+            OrderStatus::Queued,
+            OrderStatus::InProgress,
+        )
+        .await
+        .expect("Failed to update order status");
+
+        if !started {
+            info!("Order #{} cancelled", order.order_id);
+            continue;
+        }
 
         let filename = create_file("output-", ".txt", "exquisitor-fs")
             .await
@@ -52,7 +72,9 @@ pub async fn executor_task(pool: Arc<SqlitePool>) {
             .to_string_lossy()
             .to_string();
 
-        let result = run_exquisitor_analysis(order.filepath.as_str(), &filename).await;
+        let result =
+            run_exquisitor_analysis(&pool, order.order_id, order.filepath.as_str(), &filename)
+                .await;
 
         let status = if result.is_ok() && result.unwrap() {
             OrderStatus::Done
@@ -61,9 +83,20 @@ pub async fn executor_task(pool: Arc<SqlitePool>) {
         };
         let success = status == OrderStatus::Done;
 
-        update_order_status(&pool, order.order_id, status)
-            .await
-            .expect("Failed to update order status");
+        // Cancelled order keeps its status and has no result
+        let finished =
+            transition_order_status(&pool, order.order_id, OrderStatus::InProgress, status)
+                .await
+                .expect("Failed to update order status");
+
+        if !finished || !success {
+            remove_file(Path::new(&filename)).await;
+        }
+
+        if !finished {
+            info!("Order #{} cancelled", order.order_id);
+            continue;
+        }
 
         let result_id = create_result(&pool, success, if success { Some(filename) } else { None })
             .await
@@ -77,8 +110,70 @@ pub async fn executor_task(pool: Arc<SqlitePool>) {
     }
 }
 
+/// Configuration of the analysis passed to the CLI
+#[derive(Serialize)]
+struct AnalysisConfig<'a> {
+    input: InputConfig<'a>,
+    measure: MeasureConfig<'a>,
+    clustering: ClusteringConfig<'a>,
+    search: SearchConfig<'a>,
+    output: OutputConfig<'a>,
+}
+
+#[derive(Serialize)]
+struct InputConfig<'a> {
+    path: &'a str,
+}
+
+#[derive(Serialize)]
+struct MeasureConfig<'a> {
+    pipeline: &'a str,
+    model: &'a str,
+}
+
+#[derive(Serialize)]
+struct ClusteringConfig<'a> {
+    method: &'a str,
+    max_k: usize,
+}
+
+#[derive(Serialize)]
+struct SearchConfig<'a> {
+    backend: &'a str,
+    blast: &'a str,
+    database: &'a str,
+}
+
+#[derive(Serialize)]
+struct OutputConfig<'a> {
+    path: &'a str,
+}
+
+/// Progress of the analysis saved by the CLI
+#[derive(Deserialize)]
+struct AnalysisProgress {
+    stage: String,
+    done: usize,
+    total: usize,
+}
+
+impl AnalysisProgress {
+    fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 1f64;
+        }
+
+        self.done as f64 / self.total as f64
+    }
+}
+
 /// Runs the ordered analysis
-async fn run_exquisitor_analysis(input_filename: &str, output_filename: &str) -> Result<bool, ()> {
+async fn run_exquisitor_analysis(
+    pool: &SqlitePool,
+    order_id: i64,
+    input_filename: &str,
+    output_filename: &str,
+) -> Result<bool, ()> {
     let mut program = env::current_exe().map_err(|_| ())?;
     if let Some(extension) = program.extension() {
         let filename = format!("exquisitor-cli.{}", extension.to_string_lossy());
@@ -87,44 +182,197 @@ async fn run_exquisitor_analysis(input_filename: &str, output_filename: &str) ->
         program.set_file_name("exquisitor-cli");
     }
 
+    // Paths in the configuration are resolved against its directory, so they are made absolute
     let blast = get_env("BLAST").map_err(|_| ())?;
-    let blast_db = get_env("BLASTDB").map_err(|_| ())?;
-    let model = get_env("MODEL").map_err(|_| ())?;
+    let blast = if is_program_name(&blast) {
+        blast
+    } else {
+        absolute_path(&blast).await?
+    };
+    let blast_db = absolute_path(&get_env("BLASTDB").map_err(|_| ())?).await?;
+    let model = absolute_path(&get_env("MODEL").map_err(|_| ())?).await?;
+    let input_path = absolute_path(input_filename).await?;
+    let output_path = absolute_path(output_filename).await?;
+    let max_k = max_k();
 
+    let config = AnalysisConfig {
+        input: InputConfig {
+            path: input_path.as_str(),
+        },
+        measure: MeasureConfig {
+            pipeline: "neural",
+            model: model.as_str(),
+        },
+        clustering: ClusteringConfig {
+            method: "auto-k-medoid",
+            max_k,
+        },
+        search: SearchConfig {
+            backend: "blast",
+            blast: blast.as_str(),
+            database: blast_db.as_str(),
+        },
+        output: OutputConfig {
+            path: output_path.as_str(),
+        },
+    };
+
+    let mut config_filename = PathBuf::from(output_filename);
+    config_filename.set_extension("toml");
+    let content = toml::to_string(&config).map_err(|_| ())?;
+    tokio::fs::write(&config_filename, content)
+        .await
+        .map_err(|_| ())?;
+
+    let mut progress_filename = PathBuf::from(output_filename);
+    progress_filename.set_extension("progress");
+    let mut cancel_filename = PathBuf::from(output_filename);
+    cancel_filename.set_extension("cancel");
+
+    let config_filename = config_filename.to_string_lossy().to_string();
+    let progress_argument = progress_filename.to_string_lossy().to_string();
+    let cancel_argument = cancel_filename.to_string_lossy().to_string();
     let args = vec![
         "run",
-        "--input",
-        input_filename,
-        "--output",
-        output_filename,
-        "--blast",
-        blast.as_str(),
-        "--blast-db",
-        blast_db.as_str(),
-        "--pipeline",
-        "neural",
-        "--clustering",
-        "k-medoid",
-        "--k",
-        "100",
-        "--model",
-        model.as_str(),
+        "--config",
+        config_filename.as_str(),
+        "--progress-file",
+        progress_argument.as_str(),
+        "--cancel-file",
+        cancel_argument.as_str(),
     ];
     info!("Running CLI! {}", program.to_string_lossy().to_string());
     debug!("Args: {:?}", args);
-    run_exquisitor_cli(program, args).await
+
+    let watch = OrderWatch {
+        pool,
+        order_id,
+        progress_file: &progress_filename,
+        cancel_file: &cancel_filename,
+    };
+    let result = run_exquisitor_cli(program, args, &watch).await;
+
+    for path in [
+        Path::new(&config_filename),
+        progress_filename.as_path(),
+        cancel_filename.as_path(),
+    ] {
+        remove_file(path).await;
+    }
+
+    result
+}
+
+/// Returns maximal number of clusters set by `MAX_K` variable, or the default one if it is invalid
+fn max_k() -> usize {
+    let Ok(value) = get_env("MAX_K") else {
+        return DEFAULT_MAX_K;
+    };
+
+    match value.parse::<usize>() {
+        Ok(max_k) if max_k >= 2 => max_k,
+        _ => {
+            warn!(
+                "Invalid MAX_K {:?}, expected integer of at least 2, using {}",
+                value, DEFAULT_MAX_K
+            );
+            DEFAULT_MAX_K
+        }
+    }
 }
 
 fn get_env(key: &str) -> Result<String, Box<dyn std::error::Error>> {
     env::var(key).map_err(|e| e.into())
 }
 
-async fn run_exquisitor_cli(program: PathBuf, arguments: Vec<&str>) -> Result<bool, ()> {
-    let output: Output = Command::new(program)
+/// Removes auxiliary file of the analysis, which may have never been created
+async fn remove_file(path: &Path) {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to remove {}: {}", path.to_string_lossy(), e),
+    }
+}
+
+/// Checks if the program is given by name only, to be looked up in `PATH`
+fn is_program_name(program: &str) -> bool {
+    Path::new(program).components().count() == 1 && !Path::new(program).is_absolute()
+}
+
+/// Returns canonical absolute path, or absolute one if the path does not exist
+///
+/// Database of BLAST is a common prefix of its files, so it does not exist itself.
+async fn absolute_path(path: &str) -> Result<String, ()> {
+    let path = match tokio::fs::canonicalize(path).await {
+        Ok(path) => path,
+        Err(_) => std::path::absolute(path).map_err(|_| ())?,
+    };
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Order watched while its analysis is running
+struct OrderWatch<'a> {
+    pool: &'a SqlitePool,
+    order_id: i64,
+    progress_file: &'a Path,
+    cancel_file: &'a Path,
+}
+
+impl OrderWatch<'_> {
+    /// Persists the latest progress and requests cancellation of the cancelled order
+    async fn update(&self) {
+        if let Ok(content) = tokio::fs::read_to_string(self.progress_file).await {
+            match serde_json::from_str::<AnalysisProgress>(&content) {
+                Ok(progress) => {
+                    let updated = update_order_progress(
+                        self.pool,
+                        self.order_id,
+                        &progress.stage,
+                        progress.fraction(),
+                    )
+                    .await;
+
+                    if let Err(e) = updated {
+                        warn!("Failed to update order progress: {}", e);
+                    }
+                }
+                Err(e) => warn!("Invalid progress of order #{}: {}", self.order_id, e),
+            }
+        }
+
+        if is_cancelled(self.pool, self.order_id).await && !self.cancel_file.exists() {
+            if let Err(e) = tokio::fs::write(self.cancel_file, "").await {
+                warn!("Failed to cancel order #{}: {}", self.order_id, e);
+            }
+        }
+    }
+}
+
+/// Checks if the order was cancelled
+async fn is_cancelled(pool: &SqlitePool, order_id: i64) -> bool {
+    match get_order_by_id(pool, order_id).await {
+        Ok(Some(order)) => order.status == OrderStatus::Cancelled.as_str(),
+        _ => false,
+    }
+}
+
+async fn run_exquisitor_cli(
+    program: PathBuf,
+    arguments: Vec<&str>,
+    watch: &OrderWatch<'_>,
+) -> Result<bool, ()> {
+    let mut child = Command::new(program)
         .args(&arguments)
-        .output()
-        .await
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
         .map_err(|_| ())?;
 
-    Ok(output.status.success())
+    loop {
+        match tokio::time::timeout(WATCH_INTERVAL, child.wait()).await {
+            Ok(status) => return Ok(status.map_err(|_| ())?.success()),
+            Err(_) => watch.update().await,
+        }
+    }
 }
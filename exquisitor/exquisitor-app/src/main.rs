@@ -6,7 +6,7 @@ use crate::executor::executor_task;
 use crate::routes::errors;
 use axum::middleware::Next;
 use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::routing::{get, post};
 use axum::{extract, middleware, Extension, Router};
 use dotenv::dotenv;
 use sqlx::SqlitePool;
@@ -57,6 +57,7 @@ async fn main() {
         )
         .route("/order/download/:id/:kind", get(routes::order::download))
         .route("/order/:id", get(routes::order::render))
+        .route("/order/:id/cancel", post(routes::order::cancel))
         .nest_service("/assets", serve_dir_from_assets)
         .fallback(errors::handle_not_found)
         .layer(Extension(pool))
@@ -8,7 +8,7 @@ use crate::Arc;
 use askama::Template;
 use axum::extract::{Multipart, Path};
 use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
-use axum::response::{IntoResponse, Response};
+use axum::response::{IntoResponse, Redirect, Response};
 use axum::Extension;
 use sqlx::SqlitePool;
 use std::io;
@@ -56,6 +56,31 @@ pub(crate) async fn render(
     template.into_response()
 }
 
+/// Cancels the queued or in-progress order
+///
+/// Running analysis is stopped by the executor, which watches for cancelled orders.
+pub(crate) async fn cancel(
+    Path(id): Path<i64>,
+    Extension(pool): Extension<Arc<SqlitePool>>,
+) -> Response {
+    let order = match get_order(id, &pool).await {
+        Ok(value) => value,
+        Err(value) => return value,
+    };
+
+    if order.status != OrderStatus::Queued.as_str()
+        && order.status != OrderStatus::InProgress.as_str()
+    {
+        return create_code_response(StatusCode::BAD_REQUEST, "Bad Request").into_response();
+    }
+
+    if let Err(e) = db::update_order_status(&pool, id, OrderStatus::Cancelled).await {
+        return InternalServerError::DatabaseError(e).into_response();
+    }
+
+    Redirect::to(&format!("/order/{}", id)).into_response()
+}
+
 #[derive(Template)]
 #[template(path = "order_add.html")]
 struct OrderAddTemplate;
@@ -0,0 +1,247 @@
+//! Module contains command for benchmarking results of many pipeline runs against each other
+
+use crate::commands::clusters::{load_clusters, Elements};
+use crate::commands::compare::load_organisms;
+use clap::{Parser, ValueEnum};
+use csv::Writer;
+use exquisitor_core::clustering::cluster::{compare_clusterings, Cluster};
+use exquisitor_core::searching::organism::OrganismFound;
+use exquisitor_core::searching::quality::{
+    calculate_search_quality, calculate_taxonomy_distance, load_taxonomy, Taxonomy,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct BenchmarkCommand {
+    /// Path to the manifest (JSON list of named result files)
+    #[arg(long)]
+    manifest: PathBuf,
+
+    /// Path to the taxonomy (JSON map of organism names to lineages) for taxonomy-weighted distance
+    #[arg(long)]
+    taxonomy: Option<PathBuf>,
+
+    /// Handling of elements present in only one of the clusterings
+    #[arg(long, value_enum, default_value_t = Elements::Intersection)]
+    elements: Elements,
+
+    /// Path to the output file
+    #[arg(long)]
+    output: PathBuf,
+
+    /// Format of the output file
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum OutputFormat {
+    Csv,
+    Json,
+}
+
+/// Entry of the manifest
+///
+/// Relative paths are resolved against the directory of the manifest.
+#[derive(Deserialize, Debug)]
+struct ManifestEntry {
+    /// Unique name of the entry, e.g. mock community or pipeline configuration
+    name: String,
+
+    /// Path to the found organisms
+    #[serde(default)]
+    organisms: Option<PathBuf>,
+
+    /// Path to the clusters
+    #[serde(default)]
+    clusters: Option<PathBuf>,
+
+    /// Whether the entry is a reference, e.g. the composition of mock community
+    #[serde(default)]
+    reference: bool,
+}
+
+/// Results loaded for a single entry of the manifest
+struct Sample {
+    name: String,
+    organisms: Option<Vec<OrganismFound>>,
+    clusters: Option<Vec<Cluster>>,
+    reference: bool,
+}
+
+/// Metrics of a single pair of entries
+///
+/// Metrics are empty when either of the entries has no results of the given kind.
+#[derive(Serialize, Default, Debug)]
+struct BenchmarkRow {
+    reference: String,
+    second: String,
+    positive: Option<f64>,
+    negative: Option<f64>,
+    precision: Option<f64>,
+    recall: Option<f64>,
+    f1: Option<f64>,
+    l1: Option<f64>,
+    bray_curtis: Option<f64>,
+    taxonomy_weighted: Option<f64>,
+    fmi: Option<f64>,
+    ari: Option<f64>,
+    nmi: Option<f64>,
+    ami: Option<f64>,
+    homogeneity: Option<f64>,
+    completeness: Option<f64>,
+    v_measure: Option<f64>,
+    representatives_overlap: Option<f64>,
+}
+
+/// Benchmarks results listed in the manifest
+///
+/// Compares every reference entry with every other entry. When no entry is marked as reference,
+/// all ordered pairs of entries are compared. Writes one row of search and cluster metrics per
+/// pair.
+pub(crate) fn benchmark(args: BenchmarkCommand) -> IoResult<()> {
+    let samples = load_manifest(&args.manifest)?;
+    let taxonomy = match args.taxonomy {
+        Some(path) => Some(load_taxonomy(&mut File::open(path)?)?),
+        None => None,
+    };
+
+    let all_references = !samples.iter().any(|sample| sample.reference);
+    let mut rows = vec![];
+
+    for reference in samples
+        .iter()
+        .filter(|sample| all_references || sample.reference)
+    {
+        for second in samples
+            .iter()
+            .filter(|sample| sample.name != reference.name)
+        {
+            debug!("Comparing {} with {}", reference.name, second.name);
+            rows.push(compare_samples(
+                reference,
+                second,
+                taxonomy.as_ref(),
+                args.elements.clone(),
+            )?);
+        }
+    }
+
+    info!("Compared {} pairs of results", rows.len());
+
+    let mut file = File::create(args.output)?;
+    match args.format {
+        OutputFormat::Csv => save_csv(&mut file, &rows),
+        OutputFormat::Json => {
+            let json = serde_json::to_string(&rows)?;
+            file.write_all(json.as_bytes())
+        }
+    }
+}
+
+/// Loads manifest and all of the result files it lists
+fn load_manifest(path: &Path) -> IoResult<Vec<Sample>> {
+    let mut data = String::new();
+    File::open(path)?.read_to_string(&mut data)?;
+    let entries: Vec<ManifestEntry> = serde_json::from_str(&data)?;
+
+    if entries.len() < 2 {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Manifest must contain at least two entries",
+        ));
+    }
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut names = HashSet::new();
+    let mut samples = vec![];
+
+    for entry in entries {
+        if !names.insert(entry.name.clone()) {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("Duplicated entry {} in manifest", entry.name),
+            ));
+        }
+        if entry.organisms.is_none() && entry.clusters.is_none() {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("Entry {} has neither organisms nor clusters", entry.name),
+            ));
+        }
+
+        samples.push(Sample {
+            organisms: entry
+                .organisms
+                .map(|organisms| load_organisms(base.join(organisms)))
+                .transpose()?,
+            clusters: entry
+                .clusters
+                .map(|clusters| load_clusters(&base.join(clusters)))
+                .transpose()?,
+            name: entry.name,
+            reference: entry.reference,
+        });
+    }
+
+    debug!("Loaded {} entries of manifest", samples.len());
+
+    Ok(samples)
+}
+
+/// Calculates search and cluster metrics of the pair of entries
+fn compare_samples(
+    reference: &Sample,
+    second: &Sample,
+    taxonomy: Option<&Taxonomy>,
+    elements: Elements,
+) -> IoResult<BenchmarkRow> {
+    let mut row = BenchmarkRow {
+        reference: reference.name.clone(),
+        second: second.name.clone(),
+        ..Default::default()
+    };
+
+    if let (Some(first), Some(other)) = (&reference.organisms, &second.organisms) {
+        let quality = calculate_search_quality(first.clone(), other.clone());
+
+        row.positive = Some(quality.positive);
+        row.negative = Some(quality.negative);
+        row.precision = Some(quality.precision);
+        row.recall = Some(quality.recall);
+        row.f1 = Some(quality.f1);
+        row.l1 = Some(quality.l1);
+        row.bray_curtis = Some(quality.bray_curtis);
+        row.taxonomy_weighted =
+            taxonomy.map(|taxonomy| calculate_taxonomy_distance(first, other, taxonomy));
+    }
+
+    if let (Some(first), Some(other)) = (&reference.clusters, &second.clusters) {
+        let comparison = compare_clusterings(first, other, elements.into())?;
+
+        row.fmi = Some(comparison.fmi);
+        row.ari = Some(comparison.ari);
+        row.nmi = Some(comparison.nmi);
+        row.ami = Some(comparison.ami);
+        row.homogeneity = Some(comparison.homogeneity);
+        row.completeness = Some(comparison.completeness);
+        row.v_measure = Some(comparison.v_measure);
+        row.representatives_overlap = Some(comparison.representatives_overlap);
+    }
+
+    Ok(row)
+}
+
+/// Saves rows in CSV format with header
+fn save_csv(buffer: &mut dyn Write, rows: &[BenchmarkRow]) -> IoResult<()> {
+    let mut writer = Writer::from_writer(buffer);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()
+}
@@ -1,14 +1,20 @@
 //! Compares the clusters created by the taxonomic classification pipeline
 
+use crate::commands::run::{
+    calculate_distance_matrix, detect_file_format, load_sequences, validate_measure, FileFormat,
+    MeasureConfiguration, Pipeline,
+};
 use burn::serde::Serialize;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use exquisitor_core::clustering::cluster::{
-    clusters_fmi_score, clusters_nmi_score, load_clustering_data, Cluster,
+    compare_clusterings, load_clustering_data, Cluster, ClusteringComparison, ElementsPolicy,
 };
+use exquisitor_core::clustering::file::{checksum, ClusteringFile};
+use exquisitor_core::clustering::quality::{evaluate_clustering, ClusteringQuality};
 use std::fs::File;
 use std::io::{Result as IoResult, Write};
 use std::path::{Path, PathBuf};
-use tracing::debug;
+use tracing::{debug, warn};
 
 #[derive(Parser, Debug, Clone)]
 pub(crate) struct CompareClustersCommand {
@@ -20,49 +26,159 @@ pub(crate) struct CompareClustersCommand {
     #[arg(long)]
     second: PathBuf,
 
+    /// Handling of elements present in only one of the clusterings
+    #[arg(long, value_enum, default_value_t = Elements::Intersection)]
+    elements: Elements,
+
     /// Path to the output file
     #[arg(long)]
     output: Option<PathBuf>,
 }
 
+#[derive(ValueEnum, Clone, Debug)]
+pub(crate) enum Elements {
+    Intersection,
+    Singletons,
+    Strict,
+}
+
+impl From<Elements> for ElementsPolicy {
+    fn from(value: Elements) -> Self {
+        match value {
+            Elements::Intersection => ElementsPolicy::Intersection,
+            Elements::Singletons => ElementsPolicy::Singletons,
+            Elements::Strict => ElementsPolicy::Strict,
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct CompareClustersResult {
     reference: PathBuf,
     second: PathBuf,
-    fmi: f64,
-    nmi: f64,
+    #[serde(flatten)]
+    comparison: ClusteringComparison,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct EvaluateClustersCommand {
+    /// Path to the clusters
+    #[arg(long)]
+    clusters: PathBuf,
+
+    /// Path to the clustered sequence file
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// File format of the input file
+    #[arg(long, value_enum, default_value_t = FileFormat::Auto)]
+    file_format: FileFormat,
+
+    /// Pipeline used to calculate dissimilarities between sequences
+    #[arg(long, value_enum)]
+    pipeline: Pipeline,
+
+    /// Dissimilarity measure configuration
+    #[command(flatten)]
+    measure_configuration: MeasureConfiguration,
+
+    /// Path to the output file
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct EvaluateClustersResult {
+    clustering: PathBuf,
+    input: PathBuf,
+    #[serde(flatten)]
+    quality: ClusteringQuality,
 }
 
-fn load_clusters(path: &Path) -> IoResult<Vec<Cluster>> {
+pub(crate) fn load_clusters(path: &Path) -> IoResult<Vec<Cluster>> {
     let mut file = File::open(path)?;
     load_clustering_data(&mut file)
 }
 
 /// Executes the commands
 ///
-/// Prints NMI and FMI score between clusters
+/// Prints FMI, ARI, NMI, AMI, V-measure and representatives overlap between clusters
 pub(crate) fn compare_clusters(args: CompareClustersCommand) -> IoResult<()> {
     let first = load_clusters(args.reference.as_path())?;
     let second = load_clusters(args.second.as_path())?;
 
     debug!("Loaded both clusterings");
 
-    let fmi = clusters_fmi_score(&first, &second);
-    let nmi = clusters_nmi_score(&first, &second);
+    let comparison = compare_clusterings(&first, &second, args.elements.into())?;
 
-    debug!("Calculated FMI & NMI score");
+    debug!("Calculated comparison metrics");
 
     if let Some(path) = args.output {
         let mut file = File::create(path)?;
         let json = serde_json::to_string(&CompareClustersResult {
             reference: args.reference,
             second: args.second,
-            fmi,
-            nmi,
+            comparison,
         })?;
         file.write_all(json.as_bytes())?;
     } else {
-        println!("FMI: {}; NMI: {};", fmi, nmi);
+        println!(
+            "FMI: {}; ARI: {}; NMI: {}; AMI: {}; Homogeneity: {}; Completeness: {}; V-measure: {}; \
+            Representatives overlap: {};",
+            comparison.fmi,
+            comparison.ari,
+            comparison.nmi,
+            comparison.ami,
+            comparison.homogeneity,
+            comparison.completeness,
+            comparison.v_measure,
+            comparison.representatives_overlap
+        );
+    }
+
+    Ok(())
+}
+
+/// Executes the commands
+///
+/// Prints internal quality metrics of clusters as JSON
+pub(crate) fn evaluate_clusters(args: EvaluateClustersCommand) -> IoResult<()> {
+    let clustering = ClusteringFile::load(&mut File::open(&args.clusters)?)?;
+    let clusters = clustering.clusters;
+
+    if let Some(expected) = clustering.metadata.input_checksum {
+        if checksum(&mut File::open(&args.input)?)? != expected {
+            warn!("Input file differs from the one used to create the clusters");
+        }
+    }
+
+    validate_measure(&args.pipeline, &args.measure_configuration)?;
+
+    let format = match args.file_format {
+        FileFormat::Auto => detect_file_format(&args.input)?,
+        other => other,
+    };
+    let sequences = load_sequences(&args.input, format)?;
+
+    debug!("Loaded {} clusters", clusters.len());
+
+    let distance_matrix =
+        calculate_distance_matrix(&sequences, args.pipeline, &args.measure_configuration)?;
+    let quality = evaluate_clustering(&distance_matrix, &clusters)?;
+
+    debug!("Calculated quality of clusters");
+
+    let json = serde_json::to_string(&EvaluateClustersResult {
+        clustering: args.clusters,
+        input: args.input,
+        quality,
+    })?;
+
+    if let Some(path) = args.output {
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+    } else {
+        println!("{}", json);
     }
 
     Ok(())
@@ -1,6 +1,8 @@
 use clap::Parser;
 use exquisitor_core::searching::organism::{load_found_organisms, OrganismFound};
-use exquisitor_core::searching::quality::calculate_search_quality;
+use exquisitor_core::searching::quality::{
+    calculate_search_quality, calculate_taxonomy_distance, load_taxonomy, save_search_quality,
+};
 use std::fs::File;
 use std::io::Result as IoResult;
 use std::path::PathBuf;
@@ -14,20 +16,54 @@ pub(crate) struct CompareCommand {
     /// Path to the second file
     #[arg(long)]
     second: PathBuf,
+
+    /// Path to the taxonomy (JSON map of organism names to lineages) for taxonomy-weighted distance
+    #[arg(long)]
+    taxonomy: Option<PathBuf>,
+
+    /// Path to the output file with metrics in JSON format
+    #[arg(long)]
+    output: Option<PathBuf>,
 }
 
 /// Compare the results of taxonomic classification
 pub(crate) fn compare(args: CompareCommand) -> IoResult<()> {
-    let reference = load(args.reference)?;
-    let second = load(args.second)?;
-    let quality = calculate_search_quality(reference, second);
-    println!("Pos: {} Neg: {}", quality.0, quality.1);
+    let reference = load_organisms(args.reference)?;
+    let second = load_organisms(args.second)?;
+
+    let taxonomy_weighted = match args.taxonomy {
+        Some(path) => {
+            let taxonomy = load_taxonomy(&mut File::open(path)?)?;
+            Some(calculate_taxonomy_distance(&reference, &second, &taxonomy))
+        }
+        None => None,
+    };
+
+    let mut quality = calculate_search_quality(reference, second);
+    if let Some(distance) = taxonomy_weighted {
+        quality = quality.with_taxonomy_weighted(distance);
+    }
+
+    match args.output {
+        Some(path) => save_search_quality(&mut File::create(path)?, &quality)?,
+        None => {
+            println!("Pos: {} Neg: {}", quality.positive, quality.negative);
+            println!(
+                "Precision: {} Recall: {} F1: {}",
+                quality.precision, quality.recall, quality.f1
+            );
+            println!("L1: {} Bray-Curtis: {}", quality.l1, quality.bray_curtis);
+            if let Some(distance) = quality.taxonomy_weighted {
+                println!("Taxonomy-weighted: {}", distance);
+            }
+        }
+    }
 
     Ok(())
 }
 
 /// Loads the results of taxonomic classification from file
-fn load(path: PathBuf) -> IoResult<Vec<OrganismFound>> {
+pub(crate) fn load_organisms(path: PathBuf) -> IoResult<Vec<OrganismFound>> {
     let mut file = File::open(path)?;
     load_found_organisms(&mut file)
 }
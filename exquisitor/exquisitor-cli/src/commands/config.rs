@@ -0,0 +1,533 @@
+//! Module with configuration file of the run command
+//!
+//! Configuration is written in TOML (or JSON, for files with `.json` extension) and describes the
+//! whole pipeline:
+//!
+//! ```toml
+//! [input]
+//! path = "reads.fastq"
+//!
+//! [preprocessing]
+//! dereplicate = true
+//!
+//! [measure]
+//! pipeline = "k-mer"
+//! kmer = 4
+//!
+//! [clustering]
+//! method = "auto-k-medoid"
+//! max_k = 100
+//!
+//! [search]
+//! backend = "blast"
+//! blast = "/usr/bin/blastn"
+//! database = "/data/nt"
+//!
+//! [output]
+//! path = "result.json"
+//! save_clusters = true
+//! ```
+//!
+//! Values of the configuration are expanded into command line options placed before the options
+//! given explicitly, so the latter override the former. Flags take an optional value (e.g.
+//! `--dereplicate=false`), so the command line can switch them off as well as on. Relative paths
+//! are resolved against the directory of the configuration file, except programs given by name
+//! only (e.g. `blastn`), which are looked up in `PATH`.
+
+use crate::commands::run::{
+    ClusteringMethod, Device, FileFormat, Initialization, KCriterion, Pipeline, Pooling,
+    ResamplingUnit, Voting,
+};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+
+/// Name of the run subcommand
+const RUN_COMMAND: &str = "run";
+
+/// Option of the run subcommand with path to the configuration file
+const CONFIG_OPTION: &str = "--config";
+
+/// Global options of the application taking a value
+const GLOBAL_OPTIONS: [&str; 1] = ["--log-level"];
+
+/// Configuration of the run command
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RunConfig {
+    input: InputConfig,
+    preprocessing: PreprocessingConfig,
+    measure: MeasureConfig,
+    clustering: ClusteringConfig,
+    search: SearchConfig,
+    output: OutputConfig,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct InputConfig {
+    path: Option<PathBuf>,
+    format: Option<FileFormat>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct PreprocessingConfig {
+    dereplicate: Option<bool>,
+    prefix: Option<bool>,
+    reverse_complement: Option<bool>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct MeasureConfig {
+    pipeline: Option<Pipeline>,
+    gap_penalty: Option<f64>,
+    similarity_matrix_file: Option<PathBuf>,
+    kmer: Option<usize>,
+    model: Option<PathBuf>,
+    batch_size: Option<usize>,
+    device: Option<Device>,
+    window_stride: Option<usize>,
+    window_pooling: Option<Pooling>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct ClusteringConfig {
+    method: Option<ClusteringMethod>,
+    k: Option<usize>,
+    min_k: Option<usize>,
+    max_k: Option<usize>,
+    k_step: Option<usize>,
+    k_criterion: Option<KCriterion>,
+    seed: Option<u64>,
+    initialization: Option<Initialization>,
+    sort_by_abundance: Option<bool>,
+    identity: Option<f64>,
+    word_length: Option<usize>,
+    max_distance: Option<f64>,
+}
+
+/// Backend used for searching the database
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum SearchBackend {
+    Blast,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct SearchConfig {
+    backend: Option<SearchBackend>,
+    blast: Option<PathBuf>,
+    database: Option<PathBuf>,
+    queries_per_cluster: Option<usize>,
+    consensus: Option<bool>,
+    matching: MatchingConfig,
+    bootstrap: BootstrapConfig,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct MatchingConfig {
+    voting_rule: Option<Voting>,
+    all_hits: Option<bool>,
+    max_evalue: Option<f64>,
+    min_identity: Option<f64>,
+    min_coverage: Option<f64>,
+    absolute_abundance: Option<bool>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct BootstrapConfig {
+    iterations: Option<usize>,
+    confidence: Option<f64>,
+    unit: Option<ResamplingUnit>,
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct OutputConfig {
+    path: Option<PathBuf>,
+    only_cluster: Option<bool>,
+    save_clusters: Option<bool>,
+    work_dir: Option<PathBuf>,
+    resume: Option<bool>,
+}
+
+impl RunConfig {
+    /// Loads configuration from TOML or JSON file
+    pub(crate) fn load(path: &Path) -> IoResult<Self> {
+        let data = fs::read_to_string(path)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        let config = if is_json {
+            serde_json::from_str(&data).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&data).map_err(|e| e.to_string())
+        };
+
+        config.map_err(|e| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid configuration {}: {}", path.to_string_lossy(), e),
+            )
+        })
+    }
+
+    /// Converts configuration into command line options of the run command
+    pub(crate) fn to_arguments(&self, base: &Path) -> Vec<OsString> {
+        let mut arguments = Arguments {
+            values: vec![],
+            base,
+        };
+
+        arguments.path("input", &self.input.path);
+        arguments.choice("file-format", &self.input.format);
+
+        arguments.flag("dereplicate", self.preprocessing.dereplicate);
+        arguments.flag("dereplicate-prefix", self.preprocessing.prefix);
+        arguments.flag(
+            "dereplicate-reverse-complement",
+            self.preprocessing.reverse_complement,
+        );
+
+        let measure = &self.measure;
+        arguments.choice("pipeline", &measure.pipeline);
+        arguments.value("gap-penalty", &measure.gap_penalty);
+        arguments.path("similarity-matrix-file", &measure.similarity_matrix_file);
+        arguments.value("kmer", &measure.kmer);
+        arguments.path("model", &measure.model);
+        arguments.value("batch-size", &measure.batch_size);
+        arguments.choice("device", &measure.device);
+        arguments.value("window-stride", &measure.window_stride);
+        arguments.choice("window-pooling", &measure.window_pooling);
+
+        let clustering = &self.clustering;
+        arguments.choice("clustering", &clustering.method);
+        arguments.value("k", &clustering.k);
+        arguments.value("min-k", &clustering.min_k);
+        arguments.value("max-k", &clustering.max_k);
+        arguments.value("k-step", &clustering.k_step);
+        arguments.choice("k-criterion", &clustering.k_criterion);
+        arguments.value("seed", &clustering.seed);
+        arguments.choice("initialization", &clustering.initialization);
+        arguments.flag("sort-by-abundance", clustering.sort_by_abundance);
+        arguments.value("identity", &clustering.identity);
+        arguments.value("word-length", &clustering.word_length);
+        arguments.value("max-distance", &clustering.max_distance);
+
+        let search = &self.search;
+        match search.backend {
+            // BLAST is the only backend, used when none is given
+            Some(SearchBackend::Blast) | None => {}
+        }
+        arguments.program("blast", &search.blast);
+        arguments.path("blast-db", &search.database);
+        arguments.value("queries-per-cluster", &search.queries_per_cluster);
+        arguments.flag("consensus", search.consensus);
+
+        let matching = &search.matching;
+        arguments.choice("voting-rule", &matching.voting_rule);
+        arguments.flag("all-hits", matching.all_hits);
+        arguments.value("max-evalue", &matching.max_evalue);
+        arguments.value("min-identity", &matching.min_identity);
+        arguments.value("min-coverage", &matching.min_coverage);
+        arguments.flag("absolute-abundance", matching.absolute_abundance);
+
+        let bootstrap = &search.bootstrap;
+        arguments.value("bootstrap", &bootstrap.iterations);
+        arguments.value("bootstrap-confidence", &bootstrap.confidence);
+        arguments.choice("bootstrap-unit", &bootstrap.unit);
+        arguments.value("bootstrap-seed", &bootstrap.seed);
+
+        arguments.path("output", &self.output.path);
+        arguments.flag("only-cluster", self.output.only_cluster);
+        arguments.flag("save-clusters", self.output.save_clusters);
+        arguments.path("work-dir", &self.output.work_dir);
+        arguments.flag("resume", self.output.resume);
+
+        arguments.values
+    }
+}
+
+/// Command line options built from the configuration
+struct Arguments<'a> {
+    values: Vec<OsString>,
+    base: &'a Path,
+}
+
+impl Arguments<'_> {
+    fn flag(&mut self, name: &str, value: Option<bool>) {
+        if let Some(value) = value {
+            self.values.push(format!("--{}={}", name, value).into());
+        }
+    }
+
+    fn value<T: Display>(&mut self, name: &str, value: &Option<T>) {
+        if let Some(value) = value {
+            self.values.push(format!("--{}={}", name, value).into());
+        }
+    }
+
+    fn choice<T: ValueEnum>(&mut self, name: &str, value: &Option<T>) {
+        if let Some(value) = value.as_ref().and_then(|value| value.to_possible_value()) {
+            self.values
+                .push(format!("--{}={}", name, value.get_name()).into());
+        }
+    }
+
+    fn path(&mut self, name: &str, value: &Option<PathBuf>) {
+        if let Some(value) = value {
+            let mut argument = OsString::from(format!("--{}=", name));
+            argument.push(self.base.join(value));
+            self.values.push(argument);
+        }
+    }
+
+    /// Adds path of the program, keeping the name only program unchanged
+    fn program(&mut self, name: &str, value: &Option<PathBuf>) {
+        match value {
+            Some(value) if value.components().count() == 1 && !value.is_absolute() => {
+                let mut argument = OsString::from(format!("--{}=", name));
+                argument.push(value);
+                self.values.push(argument);
+            }
+            _ => self.path(name, value),
+        }
+    }
+}
+
+/// Expands configuration file of the run command into command line options
+///
+/// Options from the configuration are inserted right after the subcommand, so options given
+/// explicitly on the command line override them. Arguments of other commands are left unchanged.
+pub(crate) fn expand_config(mut arguments: Vec<OsString>) -> IoResult<Vec<OsString>> {
+    let Some(command) = find_subcommand(&arguments) else {
+        return Ok(arguments);
+    };
+    if arguments[command] != RUN_COMMAND {
+        return Ok(arguments);
+    }
+
+    let mut config = None;
+    let mut rest = arguments[command + 1..].iter();
+    while let Some(argument) = rest.next() {
+        let argument = argument.to_string_lossy();
+        if argument == CONFIG_OPTION {
+            config = rest.next().map(PathBuf::from);
+        } else if let Some(path) = argument.strip_prefix(&format!("{}=", CONFIG_OPTION)) {
+            config = Some(PathBuf::from(path));
+        }
+    }
+
+    let Some(path) = config else {
+        return Ok(arguments);
+    };
+
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let expanded = RunConfig::load(&path)?.to_arguments(&base);
+    arguments.splice(command + 1..command + 1, expanded);
+
+    Ok(arguments)
+}
+
+/// Finds position of the subcommand, skipping the program name and global options
+fn find_subcommand(arguments: &[OsString]) -> Option<usize> {
+    let mut idx = 1;
+
+    while idx < arguments.len() {
+        let argument = arguments[idx].to_string_lossy();
+        if GLOBAL_OPTIONS.contains(&argument.as_ref()) {
+            idx += 2;
+        } else if argument.starts_with('-') {
+            idx += 1;
+        } else {
+            return Some(idx);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::run::RunCommand;
+    use clap::CommandFactory;
+    use std::env;
+
+    fn to_arguments(config: &str, base: &str) -> Vec<String> {
+        let config: RunConfig = toml::from_str(config).unwrap();
+        config
+            .to_arguments(Path::new(base))
+            .into_iter()
+            .map(|argument| argument.to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn create_arguments(arguments: &[&str]) -> Vec<OsString> {
+        arguments.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_to_arguments() {
+        let arguments = to_arguments(
+            r#"
+            [input]
+            path = "reads.fastq"
+
+            [preprocessing]
+            dereplicate = true
+            prefix = false
+
+            [clustering]
+            method = "auto-k-medoid"
+            max_k = 20
+            "#,
+            "configs",
+        );
+
+        assert_eq!(
+            arguments,
+            vec![
+                format!(
+                    "--input={}",
+                    Path::new("configs").join("reads.fastq").display()
+                ),
+                "--dereplicate=true".to_string(),
+                "--dereplicate-prefix=false".to_string(),
+                "--clustering=auto-k-medoid".to_string(),
+                "--max-k=20".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_arguments_paths() {
+        let absolute = env::temp_dir().join("nt");
+        let config = format!(
+            "[search]\nblast = \"blastn\"\ndatabase = {:?}\n\n[output]\npath = \"out/result.json\"",
+            absolute.to_string_lossy()
+        );
+
+        let arguments = to_arguments(&config, "configs");
+
+        // Program given by name is looked up in PATH and absolute paths are kept
+        assert_eq!(
+            arguments,
+            vec![
+                "--blast=blastn".to_string(),
+                format!("--blast-db={}", absolute.display()),
+                format!(
+                    "--output={}",
+                    Path::new("configs").join("out/result.json").display()
+                ),
+            ]
+        );
+
+        let arguments = to_arguments("[search]\nblast = \"bin/blastn\"", "configs");
+        assert_eq!(
+            arguments,
+            vec![format!(
+                "--blast={}",
+                Path::new("configs").join("bin/blastn").display()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_expand_config() {
+        let directory = env::temp_dir().join(format!("exquisitor-config-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("run.toml");
+        fs::write(
+            &path,
+            "[clustering]\nk = 5\n[output]\npath = \"result.json\"",
+        )
+        .unwrap();
+
+        let mut config_option = OsString::from("--config=");
+        config_option.push(&path);
+        let arguments = vec![
+            OsString::from("exquisitor-cli"),
+            OsString::from("--log-level"),
+            OsString::from("debug"),
+            OsString::from("run"),
+            config_option.clone(),
+            OsString::from("--k=7"),
+        ];
+
+        let expanded = expand_config(arguments).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        // Options from the configuration precede explicit ones, so the latter take precedence
+        let mut output = OsString::from("--output=");
+        output.push(directory.join("result.json"));
+        assert_eq!(
+            expanded,
+            vec![
+                OsString::from("exquisitor-cli"),
+                OsString::from("--log-level"),
+                OsString::from("debug"),
+                OsString::from("run"),
+                OsString::from("--k=5"),
+                output,
+                config_option,
+                OsString::from("--k=7"),
+            ]
+        );
+    }
+
+    fn parse_flag(preprocessing: &str, explicit: &str) -> bool {
+        let config = format!(
+            "[input]\npath = \"reads.fasta\"\n[preprocessing]\n{}\n[clustering]\n\
+            method = \"naive\"\n[search]\nblast = \"blastn\"\ndatabase = \"nt\"",
+            preprocessing
+        );
+        let config: RunConfig = toml::from_str(&config).unwrap();
+        let mut arguments = vec![OsString::from("run")];
+        arguments.extend(config.to_arguments(Path::new("")));
+        arguments.push(OsString::from(explicit));
+
+        *RunCommand::command()
+            .try_get_matches_from(arguments)
+            .unwrap()
+            .get_one::<bool>("dereplicate")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_flag_override() {
+        let enabled = "dereplicate = true";
+        let disabled = "dereplicate = false";
+
+        // Explicit flags switch off as well as on the flags of the configuration
+        assert!(!parse_flag(enabled, "--dereplicate=false"));
+        assert!(parse_flag(disabled, "--dereplicate"));
+        assert!(parse_flag(disabled, "--dereplicate=true"));
+    }
+
+    #[test]
+    fn test_expand_config_other_command() {
+        let arguments = create_arguments(&["exquisitor-cli", "embed", "--config", "run.toml"]);
+
+        assert_eq!(expand_config(arguments.clone()).unwrap(), arguments);
+    }
+
+    #[test]
+    fn test_expand_config_missing_file() {
+        let arguments = create_arguments(&["exquisitor-cli", "run", "--config", "missing.toml"]);
+
+        assert!(expand_config(arguments).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Module contains command exporting neural embeddings of sequences
+
+use crate::commands::progress::ProgressObserver;
+use crate::commands::run::{
+    detect_file_format, load_embedder, load_records, Device, FileFormat, Pooling,
+};
+use burn::prelude::Backend;
+use clap::{Parser, ValueEnum};
+use exquisitor_core::clustering::neural::DEFAULT_BATCH_SIZE;
+use exquisitor_core::io::binary::save_embeddings;
+use exquisitor_core::io::export::{save_csv, save_npy};
+use exquisitor_core::io::sequence::Sequence;
+#[cfg(feature = "ndarray")]
+use exquisitor_core::neural::backend::CpuBackend;
+#[cfg(feature = "wgpu")]
+use exquisitor_core::neural::backend::GpuBackend;
+use exquisitor_core::progress::Observer;
+use std::fs::File;
+use std::io::{BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct EmbedCommand {
+    /// Path to the input sequence file
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// File format of the input file
+    #[arg(long, value_enum, default_value_t = FileFormat::Auto)]
+    file_format: FileFormat,
+
+    /// Path to neural model
+    #[arg(long)]
+    model: String,
+
+    /// Number of sequences embedded at once
+    #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+
+    /// Device running neural model
+    #[arg(long, value_enum, default_value_t = Device::default())]
+    device: Device,
+
+    /// Stride of windows embedding sequences longer than neural model input; truncated if unset
+    #[arg(long)]
+    window_stride: Option<usize>,
+
+    /// Pooling of embeddings of windows
+    #[arg(long, value_enum, default_value_t = Pooling::Mean)]
+    window_pooling: Pooling,
+
+    /// Path to the output embeddings
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Format of the output file
+    #[arg(long, value_enum, default_value_t = EmbeddingFormat::Binary)]
+    format: EmbeddingFormat,
+
+    /// Render progress bar of the embedding
+    #[arg(long, action)]
+    progress: bool,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum EmbeddingFormat {
+    /// Binary array format of the project, with record identifiers
+    Binary,
+
+    /// CSV with record identifier followed by the embedding in each row
+    Csv,
+
+    /// NumPy array of float32, with record identifiers saved to `<output>.ids`
+    Npy,
+}
+
+/// Embeds sequences with neural model and saves embeddings with record identifiers
+pub(crate) fn embed(args: EmbedCommand) -> IoResult<()> {
+    if args.batch_size < 1 {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Batch size should be positive",
+        ));
+    }
+
+    if args.window_stride == Some(0) {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Window stride should be positive",
+        ));
+    }
+
+    let format = match args.file_format.clone() {
+        FileFormat::Auto => detect_file_format(&args.input)?,
+        other => other,
+    };
+    let (ids, sequences) = load_records(&args.input, format)?;
+
+    debug!("Loaded {} sequences", sequences.len());
+
+    let observer = ProgressObserver::new(args.progress);
+    let embeddings = match args.device {
+        #[cfg(feature = "wgpu")]
+        Device::Gpu => embed_sequences::<GpuBackend>(&args, &sequences, &observer)?,
+        #[cfg(feature = "ndarray")]
+        Device::Cpu => embed_sequences::<CpuBackend>(&args, &sequences, &observer)?,
+    };
+
+    let mut writer = BufWriter::new(File::create(&args.output)?);
+    match args.format {
+        EmbeddingFormat::Binary => save_embeddings(&mut writer, &embeddings, &ids)?,
+        EmbeddingFormat::Csv => save_csv(&mut writer, &embeddings, &ids)?,
+        EmbeddingFormat::Npy => {
+            save_npy(&mut writer, &embeddings)?;
+
+            let mut ids_path = args.output.clone();
+            ids_path.set_extension("ids");
+            let mut ids_writer = BufWriter::new(File::create(&ids_path)?);
+            for id in &ids {
+                writeln!(ids_writer, "{}", id)?;
+            }
+            ids_writer.flush()?;
+
+            debug!("Saved record identifiers to {}", ids_path.to_string_lossy());
+        }
+    }
+    writer.flush()?;
+
+    info!(
+        "Saved {} embeddings to {}",
+        embeddings.len(),
+        args.output.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Embeds sequences with neural model run by given backend
+fn embed_sequences<B: Backend>(
+    args: &EmbedCommand,
+    sequences: &[Sequence],
+    observer: &dyn Observer,
+) -> IoResult<Vec<Vec<f32>>> {
+    let (embedder, device) = load_embedder::<B>(&args.model)?;
+
+    Ok(embedder
+        .with_batch_size(args.batch_size)
+        .with_window_stride(args.window_stride)
+        .with_window_pooling(args.window_pooling.into())
+        .embed_vectors_observed(device, sequences, observer)?)
+}
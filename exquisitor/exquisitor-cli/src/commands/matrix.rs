@@ -0,0 +1,152 @@
+//! Module contains commands computing dissimilarity matrix once and clustering it repeatedly
+
+use crate::commands::progress::ProgressObserver;
+use crate::commands::run::{
+    create_clustering, create_measure, detect_file_format, load_records, require,
+    validate_clustering, validate_measure, ClusteringConfiguration, FileFormat,
+    MeasureConfiguration, Pipeline,
+};
+use clap::Parser;
+use exquisitor_core::clustering::dissimilarity::{
+    dissimilarity_matrix_observed, CosineDissimilarity,
+};
+use exquisitor_core::clustering::file::{
+    checksum, unix_timestamp, ClusteringFile, ClusteringMetadata,
+};
+use exquisitor_core::io::binary::{load_embeddings, load_matrix, save_matrix};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error as IoError, ErrorKind, Result as IoResult, Write};
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct MatrixCommand {
+    /// Path to the input sequence file
+    #[arg(short, long, conflicts_with = "embeddings")]
+    input: Option<PathBuf>,
+
+    /// Path to the embeddings in binary format, compared by cosine dissimilarity
+    #[arg(long)]
+    embeddings: Option<PathBuf>,
+
+    /// File format of the input file
+    #[arg(long, value_enum, default_value_t = FileFormat::Auto)]
+    file_format: FileFormat,
+
+    /// Pipeline
+    #[arg(long, value_enum)]
+    pipeline: Option<Pipeline>,
+
+    /// Dissimilarity measure configuration
+    #[command(flatten)]
+    measure_configuration: MeasureConfiguration,
+
+    /// Path to the output dissimilarity matrix in binary format
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Render progress bar of the calculation
+    #[arg(long, action)]
+    progress: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub(crate) struct ClusterMatrixCommand {
+    /// Path to the dissimilarity matrix in binary format
+    #[arg(long)]
+    matrix: PathBuf,
+
+    /// Clustering configuration
+    #[command(flatten)]
+    clustering_configuration: ClusteringConfiguration,
+
+    /// Path to the output clusters file
+    #[arg(short, long)]
+    output: PathBuf,
+}
+
+/// Calculates dissimilarity matrix of sequences or embeddings and saves it in binary format
+pub(crate) fn matrix(args: MatrixCommand) -> IoResult<()> {
+    let observer = ProgressObserver::new(args.progress);
+
+    let (ids, matrix) = match (args.input, args.embeddings) {
+        (Some(input), None) => {
+            let pipeline = require(
+                &args.pipeline,
+                "measure.pipeline",
+                "pipeline",
+                "matrix of sequences",
+            )?;
+            validate_measure(pipeline, &args.measure_configuration)?;
+
+            let format = match args.file_format {
+                FileFormat::Auto => detect_file_format(&input)?,
+                other => other,
+            };
+            let (ids, sequences) = load_records(&input, format)?;
+
+            debug!("Loaded {} sequences", sequences.len());
+
+            let measure = create_measure(pipeline.clone(), &args.measure_configuration)?;
+            (ids, measure.matrix(&sequences, &observer)?)
+        }
+        (None, Some(path)) => {
+            let (header, embeddings) = load_embeddings(&mut BufReader::new(File::open(path)?))?;
+
+            debug!("Loaded {} embeddings", embeddings.len());
+
+            let matrix =
+                dissimilarity_matrix_observed(&embeddings, &CosineDissimilarity, &observer)?;
+            (header.ids, matrix)
+        }
+        _ => {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Either --input or --embeddings is required",
+            ))
+        }
+    };
+
+    let mut writer = BufWriter::new(File::create(&args.output)?);
+    save_matrix(&mut writer, &matrix, &ids)?;
+    writer.flush()?;
+
+    info!(
+        "Saved {}x{} matrix to {}",
+        matrix.len(),
+        matrix.len(),
+        args.output.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Clusters dissimilarity matrix saved in binary format
+pub(crate) fn cluster_matrix(args: ClusterMatrixCommand) -> IoResult<()> {
+    validate_clustering(&args.clustering_configuration)?;
+    let clustering = create_clustering(&args.clustering_configuration, Some(args.output.clone()))?;
+
+    let (header, matrix) = load_matrix(&mut BufReader::new(File::open(&args.matrix)?))?;
+
+    debug!("Loaded {}x{} matrix", matrix.len(), matrix.len());
+
+    let clusters = clustering.cluster(matrix)?;
+
+    info!("Clustered into {}", clusters.len());
+
+    let metadata = ClusteringMetadata {
+        input: Some(args.matrix.to_string_lossy().to_string()),
+        input_checksum: Some(checksum(&mut File::open(&args.matrix)?)?),
+        record_ids: header.ids,
+        pipeline: None,
+        parameters: serde_json::json!({
+            "clustering": args.clustering_configuration,
+        }),
+        started_at: None,
+        created_at: Some(unix_timestamp()),
+    };
+
+    ClusteringFile::new(clusters)
+        .with_metadata(metadata)
+        .save(&mut File::create(&args.output)?)
+}
@@ -1,7 +1,12 @@
 //! Commands available in console application
 
+pub(crate) mod benchmark;
 pub(crate) mod clusters;
 pub(crate) mod compare;
+pub(crate) mod config;
+pub(crate) mod embed;
 pub(crate) mod experiment;
+pub(crate) mod matrix;
+pub(crate) mod progress;
 pub(crate) mod run;
 pub(crate) mod search;
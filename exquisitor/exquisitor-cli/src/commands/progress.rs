@@ -0,0 +1,138 @@
+//! Module with observer of the pipeline rendering progress and watching for cancellation
+//!
+//! Progress is rendered as a bar on the standard error and saved as JSON to the progress file,
+//! so other processes (e.g. the web application) can follow the run. The run is cancelled once
+//! the cancel file is created.
+
+use exquisitor_core::progress::{Observer, Progress};
+use std::cell::Cell;
+use std::fs;
+use std::io::{stderr, Result as IoResult, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Width of the progress bar in characters
+const BAR_WIDTH: usize = 30;
+
+/// Interval of checking whether the cancel file exists
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Observer rendering progress of the pipeline and cancelling it on request
+pub(crate) struct ProgressObserver {
+    bar: bool,
+    progress_file: Option<PathBuf>,
+    cancel_file: Option<PathBuf>,
+    last: Cell<Option<(Progress, usize)>>,
+    last_check: Cell<Option<Instant>>,
+    cancelled: Cell<bool>,
+}
+
+impl ProgressObserver {
+    pub(crate) fn new(bar: bool) -> Self {
+        Self {
+            bar,
+            progress_file: None,
+            cancel_file: None,
+            last: Cell::new(None),
+            last_check: Cell::new(None),
+            cancelled: Cell::new(false),
+        }
+    }
+
+    pub(crate) fn with_progress_file(mut self, progress_file: Option<PathBuf>) -> Self {
+        self.progress_file = progress_file;
+        self
+    }
+
+    pub(crate) fn with_cancel_file(mut self, cancel_file: Option<PathBuf>) -> Self {
+        self.cancel_file = cancel_file;
+        self
+    }
+
+    /// Renders the progress bar of the stage, ending the line once the stage is done
+    fn render(&self, progress: &Progress, percent: usize) -> IoResult<()> {
+        let filled = percent * BAR_WIDTH / 100;
+        let mut stderr = stderr().lock();
+
+        write!(
+            stderr,
+            "\r{:<12} [{}{}] {:>3}% ({}/{})",
+            progress.stage,
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            percent,
+            progress.done,
+            progress.total
+        )?;
+        if progress.done >= progress.total {
+            writeln!(stderr)?;
+        }
+
+        stderr.flush()
+    }
+
+    /// Saves the progress to file, replacing it at once so readers never see partial content
+    fn save(&self, path: &PathBuf, progress: &Progress) -> IoResult<()> {
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        fs::write(&temporary, serde_json::to_string(progress)?)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+impl Observer for ProgressObserver {
+    fn progress(&self, progress: Progress) {
+        let percent = (progress.fraction() * 100f64).floor() as usize;
+
+        // Render only changes of whole percents, the work may report thousands of units
+        if let Some((last, last_percent)) = self.last.get() {
+            if last.stage == progress.stage
+                && last_percent == percent
+                && (progress.done < progress.total || last.done == progress.done)
+            {
+                return;
+            }
+        }
+        self.last.set(Some((progress, percent)));
+
+        if self.bar {
+            if let Err(e) = self.render(&progress, percent) {
+                warn!("Cannot render progress: {}", e);
+            }
+        }
+
+        if let Some(ref path) = self.progress_file {
+            if let Err(e) = self.save(path, &progress) {
+                warn!("Cannot save progress to {}: {}", path.to_string_lossy(), e);
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        if self.cancelled.get() {
+            return true;
+        }
+
+        let Some(ref path) = self.cancel_file else {
+            return false;
+        };
+
+        let now = Instant::now();
+        if self
+            .last_check
+            .get()
+            .is_some_and(|last| now.duration_since(last) < CANCEL_CHECK_INTERVAL)
+        {
+            return false;
+        }
+        self.last_check.set(Some(now));
+
+        if path.exists() {
+            self.cancelled.set(true);
+        }
+
+        self.cancelled.get()
+    }
+}
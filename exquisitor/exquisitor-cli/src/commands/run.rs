@@ -1,32 +1,59 @@
-use burn::backend::wgpu::WgpuDevice;
-use burn::backend::Wgpu;
-use clap::{Parser, ValueEnum};
+use crate::commands::progress::ProgressObserver;
+use burn::prelude::Backend;
+use clap::{ArgAction, Parser, ValueEnum};
+use exquisitor_core::checkpoint::{Artifact, Checkpoints};
 use exquisitor_core::clustering::cluster::{
-    save_clustering_data, KMedoidClustering, NaiveClustering,
+    save_k_selection, AutoKMedoidClustering, Cluster, KMedoidClustering, KMedoidInitialization,
+    KSelectionCriterion, NaiveClustering,
 };
-use exquisitor_core::clustering::dissimilarity::{
-    dissimilarity_matrix, CosineDissimilarity, DissimilarityMatrix, KMer, NeedlemanWunsch,
+use exquisitor_core::clustering::consensus::{save_consensus, Consensus, ConsensusBuilder};
+use exquisitor_core::clustering::dereplication::Dereplicator;
+use exquisitor_core::clustering::dissimilarity::{DissimilarityMatrix, KMer, NeedlemanWunsch};
+use exquisitor_core::clustering::file::{
+    checksum, unix_timestamp, ClusteringFile, ClusteringMetadata,
 };
-use exquisitor_core::clustering::neural::NeuralEmbedder;
-use exquisitor_core::clustering::traits::Clustering;
+use exquisitor_core::clustering::greedy::GreedyClustering;
+use exquisitor_core::clustering::neural::{
+    NeuralDissimilarity, NeuralEmbedder, WindowPooling, DEFAULT_BATCH_SIZE,
+};
+use exquisitor_core::clustering::traits::{Clustering, Dissimilarities};
 use exquisitor_core::io::fasta::reader::FastaReader;
 use exquisitor_core::io::fastq::reader::FastqReader;
 use exquisitor_core::io::sequence::Sequence;
 use exquisitor_core::io::traits::{Reader, Record};
+#[cfg(feature = "ndarray")]
+use exquisitor_core::neural::backend::CpuBackend;
+use exquisitor_core::neural::backend::Device as NeuralDevice;
+#[cfg(feature = "wgpu")]
+use exquisitor_core::neural::backend::GpuBackend;
+use exquisitor_core::pipeline::{
+    Pipeline as ClassificationPipeline, PipelineInput, QuerySelection,
+};
+use exquisitor_core::progress::{NoObserver, Observer};
+use exquisitor_core::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
 use exquisitor_core::searching::blast::Blast;
-use exquisitor_core::searching::organism::{filter_matches, save_found_organisms, save_matches};
-use exquisitor_core::searching::traits::DatabaseSearch;
+use exquisitor_core::searching::bootstrap::{Bootstrap, BootstrapAggregation, BootstrapUnit};
+use exquisitor_core::searching::organism::{
+    save_agreements, save_found_organisms, save_matches, MatchFilter, VotingRule,
+};
+use exquisitor_core::searching::traits::Aggregation;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::Formatter;
 use std::fs::File;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
 use std::io::Result as IoResult;
-use std::path::PathBuf;
-use tracing::{debug, info};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
 
 #[derive(Parser, Debug, Clone)]
+#[command(args_override_self = true)]
 pub(crate) struct RunCommand {
+    /// Path to the pipeline configuration file (TOML or JSON), overridden by command line options
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// Path to the input sequence file
     #[arg(short, long)]
     input: PathBuf,
@@ -41,54 +68,307 @@ pub(crate) struct RunCommand {
 
     /// Pipeline
     #[arg(long, value_enum)]
-    pipeline: Pipeline,
+    pipeline: Option<Pipeline>,
+
+    /// Dissimilarity measure configuration
+    #[command(flatten)]
+    measure_configuration: MeasureConfiguration,
 
     /// Clustering configuration
     #[command(flatten)]
     clustering_configuration: ClusteringConfiguration,
 
     /// Print only clustering results
-    #[arg(long, action)]
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
     only_cluster: bool,
 
     /// Save clustering information
-    #[arg(long, action)]
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
     save_clusters: bool,
 
+    /// Use consensus sequences of clusters instead of representatives as queries
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    consensus: bool,
+
+    /// Number of the most central members of each cluster used as queries
+    #[arg(long, default_value_t = 1, conflicts_with = "consensus")]
+    queries_per_cluster: usize,
+
+    /// Filter and aggregation of matches configuration
+    #[command(flatten)]
+    match_configuration: MatchConfiguration,
+
+    /// Bootstrap estimation of abundance configuration
+    #[command(flatten)]
+    bootstrap_configuration: BootstrapConfiguration,
+
     /// Database configuration
     #[command(flatten)]
     database_configuration: DatabaseConfiguration,
+
+    /// Render progress bar of the pipeline stages
+    #[arg(long, action)]
+    progress: bool,
+
+    /// Path to the file with the latest progress in JSON format
+    #[arg(long)]
+    progress_file: Option<PathBuf>,
+
+    /// Path to the file which cancels the run once created
+    #[arg(long)]
+    cancel_file: Option<PathBuf>,
+
+    /// Path to the work directory with checkpoints of the pipeline stages
+    #[arg(long)]
+    work_dir: Option<PathBuf>,
+
+    /// Resume the run, skipping stages with checkpoints matching the input and parameters
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    resume: bool,
 }
 
-#[derive(Parser, Debug, Clone)]
-struct ClusteringConfiguration {
+#[derive(Parser, Serialize, Debug, Clone)]
+pub(crate) struct ClusteringConfiguration {
     /// Method used for clustering
     #[arg(long)]
     clustering: ClusteringMethod,
 
+    /// Number of clusters
+    #[arg(long)]
+    k: Option<usize>,
+
+    /// Minimal number of clusters checked by automatic k selection
+    #[arg(long, default_value_t = 2)]
+    min_k: usize,
+
+    /// Maximal number of clusters checked by automatic k selection
+    #[arg(long)]
+    max_k: Option<usize>,
+
+    /// Step between numbers of clusters checked by automatic k selection
+    #[arg(long, default_value_t = 1)]
+    k_step: usize,
+
+    /// Criterion used by automatic k selection
+    #[arg(long, value_enum, default_value_t = KCriterion::Silhouette)]
+    k_criterion: KCriterion,
+
+    /// Seed used for initialization of clustering
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Initialization strategy of medoids used in KMedoids clustering
+    #[arg(long, value_enum, default_value_t = Initialization::Random)]
+    initialization: Initialization,
+
+    /// Sort candidates by abundance in naive clustering
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    sort_by_abundance: bool,
+
+    /// Dereplicate identical sequences before clustering by distance matrix
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    dereplicate: bool,
+
+    /// Collapse sequences identical to prefix of longer sequence during dereplication
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    dereplicate_prefix: bool,
+
+    /// Collapse sequences identical to reverse complement of other sequence during dereplication
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    dereplicate_reverse_complement: bool,
+
+    /// Minimal identity between centroid and members in greedy clustering
+    #[arg(long)]
+    identity: Option<f64>,
+
+    /// Length of words used for prefiltering in greedy clustering
+    #[arg(long, default_value_t = 8)]
+    word_length: usize,
+
+    /// Max distance between clusters
+    #[arg(long)]
+    max_distance: Option<f64>,
+}
+
+#[derive(Parser, Serialize, Debug, Clone)]
+pub(crate) struct MeasureConfiguration {
     /// Gap penalty modifier used in Needleman-Wunsch algorithm
-    #[arg(long, required_if_eq("pipeline", "basic"), allow_hyphen_values = true)]
+    #[arg(long, allow_hyphen_values = true)]
     gap_penalty: Option<f64>,
 
     /// Similarity matrix used in Needleman-Wunsch algorithm
     #[arg(long)]
     similarity_matrix_file: Option<PathBuf>,
 
-    /// Number of clusters
-    #[arg(long, required_if_eq_any([("clustering", "kmedoid")]))]
-    k: Option<usize>,
-
     /// K parameter used in KMer algorithm
-    #[arg(long, required_if_eq_any([("pipeline", "kmer")]))]
+    #[arg(long)]
     kmer: Option<usize>,
 
     /// Path to neural model
-    #[arg(long, required_if_eq("pipeline", "neural"))]
+    #[arg(long)]
     model: Option<String>,
 
-    /// Max distance between clusters
-    #[arg(long, required_if_eq("clustering", "naive"))]
-    max_distance: Option<f64>,
+    /// Number of sequences embedded at once by neural model
+    #[arg(long)]
+    #[serde(skip)]
+    batch_size: Option<usize>,
+
+    /// Device running neural model
+    #[arg(long, value_enum)]
+    #[serde(skip)]
+    device: Option<Device>,
+
+    /// Stride of windows embedding sequences longer than neural model input; truncated if unset
+    #[arg(long)]
+    window_stride: Option<usize>,
+
+    /// Pooling of embeddings of windows
+    #[arg(long, value_enum)]
+    window_pooling: Option<Pooling>,
+}
+
+#[derive(Parser, Debug, Clone)]
+struct MatchConfiguration {
+    /// Rule combining hits of multiple queries of the same cluster
+    #[arg(long, value_enum, default_value_t = Voting::Any)]
+    voting_rule: Voting,
+
+    /// Aggregate all hits of queries instead of only the best one
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    all_hits: bool,
+
+    /// Maximal e-value of matches
+    #[arg(long)]
+    max_evalue: Option<f64>,
+
+    /// Minimal identity (in percent) of matches
+    #[arg(long)]
+    min_identity: Option<f64>,
+
+    /// Minimal query coverage (in percent) of matches
+    #[arg(long)]
+    min_coverage: Option<f64>,
+
+    /// Report absolute scores instead of relative abundances summing to 1
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true"
+    )]
+    absolute_abundance: bool,
+}
+
+impl From<MatchConfiguration> for MatchFilter {
+    fn from(value: MatchConfiguration) -> Self {
+        MatchFilter::new()
+            .with_voting_rule(value.voting_rule.into())
+            .with_best_hit_only(!value.all_hits)
+            .with_max_evalue(value.max_evalue)
+            .with_min_identity(value.min_identity)
+            .with_min_coverage(value.min_coverage)
+            .with_normalize(!value.absolute_abundance)
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+struct BootstrapConfiguration {
+    /// Number of bootstrap iterations used to estimate confidence intervals of abundance
+    #[arg(long)]
+    bootstrap: Option<usize>,
+
+    /// Confidence level of bootstrap intervals
+    #[arg(long, default_value_t = 0.95)]
+    bootstrap_confidence: f64,
+
+    /// Unit resampled in bootstrap iterations
+    #[arg(long, value_enum, default_value_t = ResamplingUnit::Reads)]
+    bootstrap_unit: ResamplingUnit,
+
+    /// Seed used for bootstrap resampling
+    #[arg(long, default_value_t = 0)]
+    bootstrap_seed: u64,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ResamplingUnit {
+    Reads,
+    Clusters,
+}
+
+impl From<ResamplingUnit> for BootstrapUnit {
+    fn from(value: ResamplingUnit) -> Self {
+        match value {
+            ResamplingUnit::Reads => BootstrapUnit::Reads,
+            ResamplingUnit::Clusters => BootstrapUnit::Clusters,
+        }
+    }
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -102,8 +382,9 @@ pub(crate) struct DatabaseConfiguration {
     pub(crate) blast_db: PathBuf,
 }
 
-#[derive(ValueEnum, Eq, PartialEq, Clone, Debug)]
-enum FileFormat {
+#[derive(ValueEnum, Deserialize, Eq, PartialEq, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum FileFormat {
     Fasta,
     Fastq,
     Auto,
@@ -123,112 +404,179 @@ impl fmt::Display for FileFormat {
     }
 }
 
-#[derive(ValueEnum, Clone, Debug)]
-enum Pipeline {
+#[derive(ValueEnum, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Pipeline {
     Basic,
     KMer,
     Neural,
 }
 
-#[derive(ValueEnum, Clone, Debug)]
-enum ClusteringMethod {
-    Naive,
-    KMedoid,
+#[derive(ValueEnum, Deserialize, Copy, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Device {
+    /// GPU, through wgpu backend
+    #[cfg(feature = "wgpu")]
+    Gpu,
+
+    /// CPU, through ndarray backend
+    #[cfg(feature = "ndarray")]
+    Cpu,
 }
 
-/// Run full pipeline of taxonomic classification with clustering and preprocessing
-pub(crate) fn run(args: RunCommand) -> IoResult<()> {
-    // Detect file format
-    let format = match args.file_format {
-        FileFormat::Auto => detect_file_format(&args.input)?,
-        other => other,
-    };
+impl Default for Device {
+    fn default() -> Self {
+        NeuralDevice::default().into()
+    }
+}
 
-    debug!("File format: {}", format.to_string());
+impl From<Device> for NeuralDevice {
+    fn from(value: Device) -> Self {
+        match value {
+            #[cfg(feature = "wgpu")]
+            Device::Gpu => NeuralDevice::Gpu,
+            #[cfg(feature = "ndarray")]
+            Device::Cpu => NeuralDevice::Cpu,
+        }
+    }
+}
 
-    // Load sequences
-    let sequences = load_sequences(&args.input, format)?;
+impl From<NeuralDevice> for Device {
+    fn from(value: NeuralDevice) -> Self {
+        match value {
+            #[cfg(feature = "wgpu")]
+            NeuralDevice::Gpu => Device::Gpu,
+            #[cfg(feature = "ndarray")]
+            NeuralDevice::Cpu => Device::Cpu,
+        }
+    }
+}
 
-    debug!("Loaded {} sequences", sequences.len());
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Pooling {
+    Mean,
+    Max,
+}
 
-    // Distance matrix
-    let distance_matrix = match args.pipeline {
-        Pipeline::Basic => {
-            let gap_penalty = args
-                .clustering_configuration
-                .gap_penalty
-                .ok_or(IoError::new(
-                    ErrorKind::Other,
-                    "Missing gap penalty modifier",
-                ))?;
+impl From<Pooling> for WindowPooling {
+    fn from(value: Pooling) -> Self {
+        match value {
+            Pooling::Mean => WindowPooling::Mean,
+            Pooling::Max => WindowPooling::Max,
+        }
+    }
+}
 
-            let similarity_matrix = NeedlemanWunsch::create_default_similarity_matrix();
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ClusteringMethod {
+    Naive,
+    KMedoid,
+    AutoKMedoid,
+    Greedy,
+}
 
-            dissimilarity_matrix(
-                &sequences,
-                &NeedlemanWunsch::new(gap_penalty, similarity_matrix),
-            )?
-        }
-        Pipeline::KMer => {
-            let distance_metric = KMer::new(args.clustering_configuration.kmer.ok_or(
-                IoError::new(ErrorKind::Other, "Missing k parameter for KMer algorithm"),
-            )?);
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Initialization {
+    Random,
+    Build,
+    KMeansPlusPlus,
+}
 
-            dissimilarity_matrix(&sequences, &distance_metric)?
+impl From<Initialization> for KMedoidInitialization {
+    fn from(value: Initialization) -> Self {
+        match value {
+            Initialization::Random => KMedoidInitialization::Random,
+            Initialization::Build => KMedoidInitialization::Build,
+            Initialization::KMeansPlusPlus => KMedoidInitialization::KMeansPlusPlus,
         }
-        Pipeline::Neural => {
-            let device: WgpuDevice = Default::default();
-            let embedder = NeuralEmbedder::<Wgpu<f32, i32>>::new(
-                &args.clustering_configuration.model.ok_or(IoError::new(
-                    ErrorKind::Other,
-                    "Missing path to neural model",
-                ))?,
-                device.clone(),
-            )?;
-            debug!("Neural model loaded!");
+    }
+}
 
-            let embeddings = embedder.embed(device.clone(), &sequences);
-            debug!("Embeddings ready!");
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum KCriterion {
+    Silhouette,
+    Elbow,
+}
 
-            let embeddings = embeddings
-                .iter_dim(0)
-                .map(|t| t.to_data().to_vec::<f32>().unwrap())
-                .collect::<Vec<_>>();
+impl From<KCriterion> for KSelectionCriterion {
+    fn from(value: KCriterion) -> Self {
+        match value {
+            KCriterion::Silhouette => KSelectionCriterion::Silhouette,
+            KCriterion::Elbow => KSelectionCriterion::Elbow,
+        }
+    }
+}
 
-            dissimilarity_matrix(&embeddings, &CosineDissimilarity)?
+#[derive(ValueEnum, Deserialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Voting {
+    Any,
+    Majority,
+    Unanimous,
+}
+
+impl From<Voting> for VotingRule {
+    fn from(value: Voting) -> Self {
+        match value {
+            Voting::Any => VotingRule::Any,
+            Voting::Majority => VotingRule::Majority,
+            Voting::Unanimous => VotingRule::Unanimous,
         }
+    }
+}
+
+/// Run full pipeline of taxonomic classification with clustering and preprocessing
+pub(crate) fn run(args: RunCommand) -> IoResult<()> {
+    let started_at = unix_timestamp();
+
+    validate(&args)?;
+
+    // Detect file format
+    let format = match args.file_format.clone() {
+        FileFormat::Auto => detect_file_format(&args.input)?,
+        other => other,
     };
 
-    debug!("Calculated distance matrix: {}", distance_matrix.len());
-
-    let clustering_method: Box<dyn Clustering<DissimilarityMatrix>> =
-        match args.clustering_configuration.clustering {
-            ClusteringMethod::Naive => Box::new(NaiveClustering::new(
-                args.clustering_configuration
-                    .max_distance
-                    .ok_or(IoError::new(
-                        ErrorKind::Other,
-                        "Missing max distance parameter",
-                    ))?,
-            )),
-            ClusteringMethod::KMedoid => Box::new(KMedoidClustering::new(
-                args.clustering_configuration.k.ok_or(IoError::new(
-                    ErrorKind::Other,
-                    "Missing k parameter for KMedoids clustering",
-                ))?,
-            )),
-        };
+    debug!("File format: {}", format.to_string());
 
-    let clusters = clustering_method.cluster(distance_matrix)?;
+    let checkpoints = match args.work_dir {
+        Some(ref directory) => Some(create_checkpoints(&args, directory, &format)?),
+        None => None,
+    };
 
-    debug!("Clustered into {}", clusters.len());
+    // Load sequences
+    let input = load_input(&args, format, checkpoints.as_ref())?;
+
+    debug!("Loaded {} sequences", input.sequences().len());
+
+    let observer = ProgressObserver::new(args.progress)
+        .with_progress_file(args.progress_file.clone())
+        .with_cancel_file(args.cancel_file.clone());
+    let mut pipeline = create_pipeline(&args)?.with_observer(&observer);
+    if let Some(checkpoints) = checkpoints {
+        pipeline = pipeline.with_checkpoints(checkpoints);
+    }
+    if args.queries_per_cluster > 1 && !args.consensus && !pipeline.uses_central_members() {
+        warn!("Greedy clustering does not calculate distance matrix, using representatives only");
+    }
+
+    let clusters = pipeline.cluster(&input)?;
+
+    debug!("Clustered into {}", clusters.clusters.len());
 
     if args.only_cluster || args.save_clusters {
         if let Some(ref path) = args.output {
             let mut clusters_path = path.clone();
             clusters_path.set_extension("clusters".to_string());
+            let metadata = clustering_metadata(&args, input.ids().clone(), started_at)?;
             let mut file = File::create(&clusters_path)?;
-            save_clustering_data(&mut file, &clusters)?;
+            ClusteringFile::new(clusters.clusters.clone())
+                .with_metadata(metadata)
+                .save(&mut file)?;
 
             debug!("Saved clusters to {}", clusters_path.to_string_lossy());
         }
@@ -238,43 +586,529 @@ pub(crate) fn run(args: RunCommand) -> IoResult<()> {
         return Ok(());
     }
 
-    let representatives = clusters
-        .iter()
-        .filter_map(|c| sequences.get(c.representative()))
-        .cloned()
-        .collect();
+    let result = pipeline.search(&input, clusters)?;
 
-    let database = Blast::new(
-        args.database_configuration.blast.to_str().unwrap(),
-        args.database_configuration.blast_db.to_str().unwrap(),
-    );
-    let matches = database.search(representatives)?;
+    debug!("Searched {} queries", result.queries.len());
+
+    if let Some(ref consensus) = result.consensus {
+        save_consensus_report(consensus, args.output.as_ref())?;
+    }
 
     if let Some(ref path) = args.output {
         let mut matches_path = path.clone();
         matches_path.set_extension("matches".to_string());
         let mut file = File::create(&matches_path)?;
-        save_matches(&mut file, &matches)?;
+        save_matches(&mut file, &result.matches)?;
+    }
+
+    for agreement in result.agreements.iter().filter(|a| a.is_mixed()) {
+        warn!(
+            "Cluster {} may be chimeric or mixed: agreement {:.2} of {} queries",
+            agreement.cluster, agreement.agreement, agreement.queries
+        );
+    }
+
+    if let Some(ref path) = args.output {
+        let mut agreement_path = path.clone();
+        agreement_path.set_extension("agreement");
+        let mut file = File::create(&agreement_path)?;
+        save_agreements(&mut file, &result.agreements)?;
     }
-    let found = filter_matches(&matches, &clusters, sequences.len());
 
     if let Some(path) = args.output {
         let mut file = File::create(path.clone())?;
-        save_found_organisms(&mut file, &found)?;
+        save_found_organisms(&mut file, &result.found)?;
 
         debug!("Saved result to {}", path.to_string_lossy());
     } else {
-        info!("Found {}", found.len());
-        for found in found {
-            info!("- {}", found.name());
+        info!("Found {}", result.found.len());
+        for found in result.found {
+            match found.bootstrap() {
+                Some(estimate) => info!(
+                    "- {}: {:.4} ({:.0}% CI {:.4}-{:.4})",
+                    found.name(),
+                    found.quality(),
+                    estimate.confidence * 100f64,
+                    estimate.lower,
+                    estimate.upper
+                ),
+                None => info!("- {}", found.name()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates classification pipeline from the options
+fn create_pipeline(args: &RunCommand) -> IoResult<ClassificationPipeline<'static>> {
+    let configuration = &args.clustering_configuration;
+
+    let pipeline = match configuration.clustering {
+        ClusteringMethod::Greedy => {
+            ClassificationPipeline::new_sequence_clustering(Box::new(create_greedy(configuration)?))
+        }
+        _ => {
+            let pipeline = args.pipeline.clone().ok_or(IoError::new(
+                ErrorKind::InvalidInput,
+                "Missing pipeline for clustering by distance matrix",
+            ))?;
+
+            let pipeline = ClassificationPipeline::new(
+                create_measure(pipeline, &args.measure_configuration)?,
+                create_clustering(configuration, args.output.clone())?,
+            );
+
+            match should_dereplicate(configuration) {
+                true => pipeline.with_dereplicator(create_dereplicator(configuration)),
+                false => pipeline,
+            }
+        }
+    };
+
+    let queries = if args.consensus {
+        QuerySelection::Consensus(ConsensusBuilder::new())
+    } else if args.queries_per_cluster > 1 {
+        QuerySelection::Central(args.queries_per_cluster)
+    } else {
+        QuerySelection::Representatives
+    };
+
+    let filter: MatchFilter = args.match_configuration.clone().into();
+    let aggregation: Box<dyn Aggregation> = match args.bootstrap_configuration.bootstrap {
+        Some(iterations) => {
+            let configuration = &args.bootstrap_configuration;
+            let bootstrap = Bootstrap::new(iterations)
+                .with_confidence(configuration.bootstrap_confidence)
+                .with_unit(configuration.bootstrap_unit.clone().into())
+                .with_seed(configuration.bootstrap_seed);
+
+            Box::new(BootstrapAggregation::new(bootstrap, filter))
+        }
+        None => Box::new(filter),
+    };
+
+    let database = Blast::new(
+        args.database_configuration.blast.to_str().unwrap(),
+        args.database_configuration.blast_db.to_str().unwrap(),
+    );
+
+    Ok(pipeline
+        .with_queries(queries)
+        .with_search(Box::new(database))
+        .with_aggregation(aggregation))
+}
+
+/// Checks options required by the selected clustering method and pipeline
+///
+/// Errors name both the key of the configuration file and the command line option.
+fn validate(args: &RunCommand) -> IoResult<()> {
+    if args.resume {
+        require(
+            &args.work_dir,
+            "output.work_dir",
+            "work-dir",
+            "resuming the run",
+        )?;
+    }
+
+    validate_clustering(&args.clustering_configuration)?;
+    if let ClusteringMethod::Greedy = args.clustering_configuration.clustering {
+        return Ok(());
+    }
+
+    let pipeline = require(
+        &args.pipeline,
+        "measure.pipeline",
+        "pipeline",
+        "clustering by distance matrix",
+    )?;
+
+    validate_measure(pipeline, &args.measure_configuration)
+}
+
+/// Checks if parameters required by the clustering method are given
+pub(crate) fn validate_clustering(configuration: &ClusteringConfiguration) -> IoResult<()> {
+    match configuration.clustering {
+        ClusteringMethod::Naive => require(
+            &configuration.max_distance,
+            "clustering.max_distance",
+            "max-distance",
+            "naive clustering",
+        )
+        .map(|_| ())?,
+        ClusteringMethod::KMedoid => {
+            require(&configuration.k, "clustering.k", "k", "k-medoid clustering").map(|_| ())?
         }
+        ClusteringMethod::AutoKMedoid => require(
+            &configuration.max_k,
+            "clustering.max_k",
+            "max-k",
+            "auto-k-medoid clustering",
+        )
+        .map(|_| ())?,
+        ClusteringMethod::Greedy => require(
+            &configuration.identity,
+            "clustering.identity",
+            "identity",
+            "greedy clustering",
+        )
+        .map(|_| ())?,
     }
 
     Ok(())
 }
 
+/// Checks options required by the measure of given pipeline
+pub(crate) fn validate_measure(
+    pipeline: &Pipeline,
+    configuration: &MeasureConfiguration,
+) -> IoResult<()> {
+    match pipeline {
+        Pipeline::Basic => require(
+            &configuration.gap_penalty,
+            "measure.gap_penalty",
+            "gap-penalty",
+            "basic pipeline",
+        )
+        .map(|_| ()),
+        Pipeline::KMer => require(
+            &configuration.kmer,
+            "measure.kmer",
+            "kmer",
+            "k-mer pipeline",
+        )
+        .map(|_| ()),
+        Pipeline::Neural => {
+            require(
+                &configuration.model,
+                "measure.model",
+                "model",
+                "neural pipeline",
+            )?;
+
+            if configuration.batch_size == Some(0) {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "Batch size should be positive",
+                ));
+            }
+
+            if configuration.window_stride == Some(0) {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "Window stride should be positive",
+                ));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Creates checkpoints in the work directory keyed by the input and parameters of each stage
+fn create_checkpoints(
+    args: &RunCommand,
+    directory: &Path,
+    format: &FileFormat,
+) -> IoResult<Checkpoints> {
+    let input_checksum = checksum(&mut File::open(&args.input)?)?;
+    let clustering = &args.clustering_configuration;
+    let pipeline = args
+        .pipeline
+        .as_ref()
+        .and_then(|pipeline| pipeline.to_possible_value())
+        .map(|value| value.get_name().to_string());
+
+    let input = serde_json::json!({
+        "format": format.to_string(),
+        "qualities": args.consensus,
+    });
+    let matrix = serde_json::json!({
+        "pipeline": pipeline,
+        "measure": args.measure_configuration,
+        "dereplicate": clustering.dereplicate,
+        "dereplicate_prefix": clustering.dereplicate_prefix,
+        "dereplicate_reverse_complement": clustering.dereplicate_reverse_complement,
+    });
+    let clusters = serde_json::json!({
+        "clustering": clustering,
+        "queries_per_cluster": args.queries_per_cluster,
+        "consensus": args.consensus,
+    });
+    let matches = serde_json::json!({
+        "blast": args.database_configuration.blast,
+        "blast_db": args.database_configuration.blast_db,
+    });
+
+    let checkpoints = Checkpoints::new(directory, &input_checksum)
+        .with_parameters(Artifact::Input, &input.to_string())
+        .with_parameters(Artifact::Matrix, &matrix.to_string())
+        .with_parameters(Artifact::Clusters, &clusters.to_string())
+        .with_parameters(Artifact::Matches, &matches.to_string())
+        .with_resume(args.resume);
+
+    for artifact in [
+        Artifact::Input,
+        Artifact::Matrix,
+        Artifact::Clusters,
+        Artifact::Matches,
+    ] {
+        if checkpoints.is_saved(artifact) {
+            info!(
+                "Resuming {} from {}",
+                artifact.name(),
+                checkpoints.path(artifact).to_string_lossy()
+            );
+        }
+    }
+
+    Ok(checkpoints)
+}
+
+/// Loads sequences of the input, or restores them from the checkpoint
+fn load_input(
+    args: &RunCommand,
+    format: FileFormat,
+    checkpoints: Option<&Checkpoints>,
+) -> IoResult<PipelineInput> {
+    if let Some(checkpoints) = checkpoints {
+        if let Some(input) = checkpoints.load(Artifact::Input)? {
+            return Ok(input);
+        }
+    }
+
+    let (record_ids, sequences) = load_records(&args.input, format.clone())?;
+    let qualities = match args.consensus {
+        true => load_qualities(&args.input, format)?,
+        false => None,
+    };
+    let input = PipelineInput::new(sequences)
+        .with_ids(record_ids)
+        .with_qualities(qualities);
+
+    if let Some(checkpoints) = checkpoints {
+        checkpoints.save(Artifact::Input, &input)?;
+    }
+
+    Ok(input)
+}
+
+/// Returns the value of required option or error naming its configuration key and option
+pub(crate) fn require<'a, T>(
+    value: &'a Option<T>,
+    key: &str,
+    option: &str,
+    reason: &str,
+) -> IoResult<&'a T> {
+    value.as_ref().ok_or_else(|| {
+        IoError::new(
+            ErrorKind::InvalidInput,
+            format!("Missing `{}` (--{}) required by {}", key, option, reason),
+        )
+    })
+}
+
+/// Calculates distance matrix between sequences using measure of given pipeline
+pub(crate) fn calculate_distance_matrix(
+    sequences: &[Sequence],
+    pipeline: Pipeline,
+    configuration: &MeasureConfiguration,
+) -> IoResult<DissimilarityMatrix> {
+    Ok(create_measure(pipeline, configuration)?.matrix(sequences, &NoObserver)?)
+}
+
+/// Creates dissimilarity measure of given pipeline
+pub(crate) fn create_measure(
+    pipeline: Pipeline,
+    configuration: &MeasureConfiguration,
+) -> IoResult<Box<dyn Dissimilarities>> {
+    Ok(match pipeline {
+        Pipeline::Basic => {
+            let gap_penalty = configuration.gap_penalty.ok_or(IoError::new(
+                ErrorKind::Other,
+                "Missing gap penalty modifier",
+            ))?;
+
+            let similarity_matrix = NeedlemanWunsch::create_default_similarity_matrix();
+
+            Box::new(NeedlemanWunsch::new(gap_penalty, similarity_matrix))
+        }
+        Pipeline::KMer => Box::new(KMer::new(configuration.kmer.ok_or(IoError::new(
+            ErrorKind::Other,
+            "Missing k parameter for KMer algorithm",
+        ))?)),
+        Pipeline::Neural => {
+            let model = configuration.model.clone().ok_or(IoError::new(
+                ErrorKind::Other,
+                "Missing path to neural model",
+            ))?;
+
+            match configuration.device.unwrap_or_default() {
+                #[cfg(feature = "wgpu")]
+                Device::Gpu => neural_dissimilarity::<GpuBackend>(&model, configuration)?,
+                #[cfg(feature = "ndarray")]
+                Device::Cpu => neural_dissimilarity::<CpuBackend>(&model, configuration)?,
+            }
+        }
+    })
+}
+
+/// Creates dissimilarity measure comparing embeddings of neural model run by given backend
+fn neural_dissimilarity<B: Backend>(
+    model: &str,
+    configuration: &MeasureConfiguration,
+) -> IoResult<Box<dyn Dissimilarities>> {
+    let (embedder, device) = load_embedder::<B>(model)?;
+    let embedder = embedder
+        .with_batch_size(configuration.batch_size.unwrap_or(DEFAULT_BATCH_SIZE))
+        .with_window_stride(configuration.window_stride)
+        .with_window_pooling(
+            configuration
+                .window_pooling
+                .map(Into::into)
+                .unwrap_or_default(),
+        );
+
+    Ok(Box::new(NeuralDissimilarity::new(embedder, device)))
+}
+
+/// Loads neural model and returns the embedder with default device of the backend running it
+pub(crate) fn load_embedder<B: Backend>(model: &str) -> IoResult<(NeuralEmbedder<B>, B::Device)> {
+    let device: B::Device = Default::default();
+    let embedder = NeuralEmbedder::<B>::new(model, device.clone())?;
+    debug!("Neural model loaded!");
+
+    Ok((embedder, device))
+}
+
+/// Checks if any dereplication option is enabled
+fn should_dereplicate(configuration: &ClusteringConfiguration) -> bool {
+    configuration.dereplicate
+        || configuration.dereplicate_prefix
+        || configuration.dereplicate_reverse_complement
+}
+
+/// Creates dereplicator using configured options
+fn create_dereplicator(configuration: &ClusteringConfiguration) -> Dereplicator {
+    Dereplicator::new()
+        .with_prefix(configuration.dereplicate_prefix)
+        .with_reverse_complement(configuration.dereplicate_reverse_complement)
+}
+
+/// Creates greedy centroid clustering
+fn create_greedy(configuration: &ClusteringConfiguration) -> IoResult<GreedyClustering> {
+    let identity = configuration.identity.ok_or(IoError::new(
+        ErrorKind::InvalidInput,
+        "Missing identity threshold for greedy clustering",
+    ))?;
+
+    Ok(GreedyClustering::new(identity)
+        .with_word_length(configuration.word_length)
+        .with_dereplicator(create_dereplicator(configuration)))
+}
+
+/// Creates configured method clustering the elements represented by distance matrix
+pub(crate) fn create_clustering(
+    configuration: &ClusteringConfiguration,
+    output: Option<PathBuf>,
+) -> IoResult<Box<dyn Clustering<DissimilarityMatrix>>> {
+    Ok(match configuration.clustering {
+        ClusteringMethod::Naive => Box::new(
+            NaiveClustering::new(configuration.max_distance.ok_or(IoError::new(
+                ErrorKind::Other,
+                "Missing max distance parameter",
+            ))?)
+            .with_sort_by_abundance(configuration.sort_by_abundance),
+        ),
+        ClusteringMethod::KMedoid => Box::new(
+            KMedoidClustering::new(configuration.k.ok_or(IoError::new(
+                ErrorKind::Other,
+                "Missing k parameter for KMedoids clustering",
+            ))?)
+            .with_initialization(configuration.initialization.clone().into())
+            .with_seed(configuration.seed),
+        ),
+        ClusteringMethod::AutoKMedoid => {
+            let clustering = AutoKMedoidClustering::new(
+                configuration.min_k,
+                configuration.max_k.ok_or(IoError::new(
+                    ErrorKind::InvalidInput,
+                    "Missing max k parameter for automatic KMedoids clustering",
+                ))?,
+                configuration.k_criterion.clone().into(),
+                configuration.seed,
+            )
+            .with_initialization(configuration.initialization.clone().into())
+            .with_step(configuration.k_step);
+
+            Box::new(ReportedAutoKMedoid { clustering, output })
+        }
+        ClusteringMethod::Greedy => {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Greedy clustering does not use distance matrix",
+            ))
+        }
+    })
+}
+
+/// KMedoids clustering with automatic selection of k reporting the selection
+struct ReportedAutoKMedoid {
+    clustering: AutoKMedoidClustering,
+    output: Option<PathBuf>,
+}
+
+impl Clustering<DissimilarityMatrix> for ReportedAutoKMedoid {
+    fn cluster(&self, distance_matrix: DissimilarityMatrix) -> ExquisitorResult<Vec<Cluster>> {
+        self.cluster_observed(distance_matrix, &NoObserver)
+    }
+
+    fn cluster_observed(
+        &self,
+        distance_matrix: DissimilarityMatrix,
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Cluster>> {
+        let (selection, clusters) = self
+            .clustering
+            .select_observed(&distance_matrix, observer)?;
+
+        info!(
+            "Selected k = {} by {:?}",
+            selection.k(),
+            selection.criterion()
+        );
+        for point in selection.curve() {
+            debug!(
+                "k = {}: loss = {}, silhouette = {}",
+                point.k, point.loss, point.silhouette
+            );
+        }
+
+        if let Some(ref path) = self.output {
+            let mut selection_path = path.clone();
+            selection_path.set_extension("kselection");
+            File::create(&selection_path)
+                .and_then(|mut file| save_k_selection(&mut file, &selection))
+                .map_err(|e| {
+                    ExquisitorError::new(
+                        ExquisitorErrorKind::Io(e.kind()),
+                        format!(
+                            "Cannot save k selection to {}: {}",
+                            selection_path.to_string_lossy(),
+                            e
+                        ),
+                    )
+                })?;
+            debug!("Saved k selection to {}", selection_path.to_string_lossy());
+        }
+
+        Ok(clusters)
+    }
+}
+
 /// Detect file format
-fn detect_file_format(path: &PathBuf) -> IoResult<FileFormat> {
+pub(crate) fn detect_file_format(path: &PathBuf) -> IoResult<FileFormat> {
     match path.extension() {
         Some(extension) if extension.eq_ignore_ascii_case("fasta") => Ok(FileFormat::Fasta),
         Some(extension) if extension.eq_ignore_ascii_case("fastq") => Ok(FileFormat::Fastq),
@@ -286,19 +1120,95 @@ fn detect_file_format(path: &PathBuf) -> IoResult<FileFormat> {
 }
 
 /// Load records
-fn load_sequences(path: &PathBuf, format: FileFormat) -> IoResult<Vec<Sequence>> {
+pub(crate) fn load_sequences(path: &PathBuf, format: FileFormat) -> IoResult<Vec<Sequence>> {
+    Ok(load_records(path, format)?.1)
+}
+
+/// Loads identifiers and sequences of records from file
+pub(crate) fn load_records(
+    path: &PathBuf,
+    format: FileFormat,
+) -> IoResult<(Vec<String>, Vec<Sequence>)> {
     let file = File::open(&path)?;
 
-    let sequences: IoResult<Vec<Sequence>> = match format {
+    let records: IoResult<Vec<(String, Sequence)>> = match format {
         FileFormat::Fasta => FastaReader::new(file)
             .iter()
-            .map(|record| record.map(|value| value.sequence().clone()))
+            .map(|record| record.map(|value| (value.id().to_string(), value.sequence().clone())))
             .collect(),
         _ => FastqReader::new(file)
             .iter()
-            .map(|record| record.map(|value| value.sequence().clone()))
+            .map(|record| record.map(|value| (value.id().to_string(), value.sequence().clone())))
             .collect(),
     };
 
-    sequences
+    Ok(records?.into_iter().unzip())
+}
+
+/// Loads quality values of records from file, if the format contains them
+pub(crate) fn load_qualities(
+    path: &PathBuf,
+    format: FileFormat,
+) -> IoResult<Option<Vec<Sequence>>> {
+    if format != FileFormat::Fastq {
+        return Ok(None);
+    }
+
+    let file = File::open(path)?;
+    let qualities: IoResult<Vec<Sequence>> = FastqReader::new(file)
+        .iter()
+        .map(|record| record.map(|value| value.quality().clone()))
+        .collect();
+
+    Ok(Some(qualities?))
+}
+
+/// Reports consensus sequences of clusters, saving their depth and support next to the output
+fn save_consensus_report(consensus: &[Consensus], output: Option<&PathBuf>) -> IoResult<()> {
+    for (idx, c) in consensus.iter().enumerate() {
+        debug!(
+            "Consensus {}: size = {}, depth = {:.2}, quality = {:.4}",
+            idx,
+            c.size,
+            c.mean_depth(),
+            c.quality()
+        );
+    }
+
+    if let Some(path) = output {
+        let mut consensus_path = path.clone();
+        consensus_path.set_extension("consensus");
+        let mut file = File::create(&consensus_path)?;
+        save_consensus(&mut file, consensus)?;
+
+        debug!("Saved consensus to {}", consensus_path.to_string_lossy());
+    }
+
+    Ok(())
+}
+
+/// Describes the run producing clusters for the clustering file
+fn clustering_metadata(
+    args: &RunCommand,
+    record_ids: Vec<String>,
+    started_at: u64,
+) -> IoResult<ClusteringMetadata> {
+    let mut input = File::open(&args.input)?;
+
+    Ok(ClusteringMetadata {
+        input: Some(args.input.to_string_lossy().to_string()),
+        input_checksum: Some(checksum(&mut input)?),
+        record_ids,
+        pipeline: args
+            .pipeline
+            .as_ref()
+            .and_then(|pipeline| pipeline.to_possible_value())
+            .map(|value| value.get_name().to_string()),
+        parameters: serde_json::json!({
+            "measure": args.measure_configuration,
+            "clustering": args.clustering_configuration,
+        }),
+        started_at: Some(started_at),
+        created_at: Some(unix_timestamp()),
+    })
 }
@@ -6,12 +6,19 @@
 
 mod commands;
 
-use crate::commands::clusters::{compare_clusters, CompareClustersCommand};
+use crate::commands::benchmark::{benchmark, BenchmarkCommand};
+use crate::commands::clusters::{
+    compare_clusters, evaluate_clusters, CompareClustersCommand, EvaluateClustersCommand,
+};
 use crate::commands::compare::{compare, CompareCommand};
+use crate::commands::config::expand_config;
+use crate::commands::embed::{embed, EmbedCommand};
 use crate::commands::experiment::{experiment, ExperimentCommand};
+use crate::commands::matrix::{cluster_matrix, matrix, ClusterMatrixCommand, MatrixCommand};
 use crate::commands::run::{run, RunCommand};
 use crate::commands::search::{search, SearchCommand};
 use clap::{Parser, Subcommand};
+use std::io::ErrorKind;
 use std::process;
 use tracing::info;
 
@@ -33,20 +40,34 @@ struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
     /// Run the pipeline
-    Run(RunCommand),
+    Run(Box<RunCommand>),
     /// Run the experiment
     Experiment(ExperimentCommand),
     /// Compare the results
     Compare(CompareCommand),
+    /// Compare many results listed in manifest
+    Benchmark(BenchmarkCommand),
     /// Compare the clusters
     CompareClusters(CompareClustersCommand),
+    /// Evaluate the clusters without reference
+    EvaluateClusters(EvaluateClustersCommand),
     /// Search sequences in database
     Search(SearchCommand),
+    /// Calculate dissimilarity matrix and save it in binary format
+    Matrix(MatrixCommand),
+    /// Cluster dissimilarity matrix saved in binary format
+    ClusterMatrix(ClusterMatrixCommand),
+    /// Embed sequences with neural model and export embeddings
+    Embed(EmbedCommand),
 }
 
 /// Entry point of CLI application
 fn main() {
-    let cli = Cli::parse();
+    let arguments = expand_config(std::env::args_os().collect()).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    let cli = Cli::parse_from(arguments);
 
     // Initialize tracing
     let severity = cli
@@ -56,14 +77,24 @@ fn main() {
     tracing_subscriber::fmt().with_max_level(severity).init();
 
     let result = match cli.cmd {
-        Commands::Run(cmd) => run(cmd),
+        Commands::Run(cmd) => run(*cmd),
         Commands::Experiment(cmd) => experiment(cmd),
         Commands::Compare(cmd) => compare(cmd),
+        Commands::Benchmark(cmd) => benchmark(cmd),
         Commands::CompareClusters(cmd) => compare_clusters(cmd),
+        Commands::EvaluateClusters(cmd) => evaluate_clusters(cmd),
         Commands::Search(cmd) => search(cmd),
+        Commands::Matrix(cmd) => matrix(cmd),
+        Commands::ClusterMatrix(cmd) => cluster_matrix(cmd),
+        Commands::Embed(cmd) => embed(cmd),
     };
 
     match result {
+        Err(e) if e.kind() == ErrorKind::Interrupted => {
+            // Error of the cancelled work already names the cancelled stage
+            eprintln!("{}", e);
+            process::exit(130);
+        }
         Err(e) => {
             eprintln!("Error: {}", e);
             process::exit(1);
@@ -0,0 +1,276 @@
+//! Module with checkpoints of the pipeline persisted in the work directory
+//!
+//! Every artifact (loaded sequences, dissimilarity matrix, clusters, matches) is saved to a file
+//! named by the key derived from the checksum of the input and parameters of all stages up to the
+//! one creating the artifact. Resumed run reuses artifacts with matching keys and skips the stages
+//! which created them, so changing parameters of a later stage keeps artifacts of earlier stages.
+//!
+//! Dissimilarity matrix is saved in the binary array format, which is compact and keeps values
+//! exactly, while the other (small) artifacts are saved as JSON.
+
+use crate::clustering::dissimilarity::DissimilarityMatrix;
+use crate::io::binary;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::path::{Path, PathBuf};
+
+/// Number of hexadecimal characters of the key used in names of files
+const KEY_LENGTH: usize = 16;
+
+/// Artifact created by the stage of the pipeline, in order of the stages
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Artifact {
+    /// Identifiers, sequences and qualities of loaded records
+    Input,
+
+    /// Dissimilarity matrix of (unique) sequences
+    Matrix,
+
+    /// Clusters with their the most central members
+    Clusters,
+
+    /// Matches of queries found in the database
+    Matches,
+}
+
+impl Artifact {
+    const ALL: [Artifact; 4] = [
+        Artifact::Input,
+        Artifact::Matrix,
+        Artifact::Clusters,
+        Artifact::Matches,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Artifact::Input => "input",
+            Artifact::Matrix => "matrix",
+            Artifact::Clusters => "clusters",
+            Artifact::Matches => "matches",
+        }
+    }
+
+    /// Returns extension of the file with the artifact
+    fn extension(&self) -> &'static str {
+        match self {
+            Artifact::Matrix => "exqb",
+            _ => "json",
+        }
+    }
+}
+
+/// Checkpoints of the pipeline saved in the work directory
+#[derive(Clone, Debug)]
+pub struct Checkpoints {
+    directory: PathBuf,
+    input_checksum: String,
+    parameters: [String; 4],
+    resume: bool,
+}
+
+impl Checkpoints {
+    pub fn new(directory: &Path, input_checksum: &str) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+            input_checksum: input_checksum.to_string(),
+            parameters: Default::default(),
+            resume: false,
+        }
+    }
+
+    /// Sets parameters of the stage creating the artifact, e.g. serialized configuration
+    pub fn with_parameters(mut self, artifact: Artifact, parameters: &str) -> Self {
+        self.parameters[artifact as usize] = parameters.to_string();
+        self
+    }
+
+    /// Reuses saved artifacts instead of creating them again
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns key of the artifact derived from the input and parameters of preceding stages
+    pub fn key(&self, artifact: Artifact) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.input_checksum.as_bytes());
+
+        for stage in Artifact::ALL.iter().take(artifact as usize + 1) {
+            hasher.update([0u8]);
+            hasher.update(stage.name().as_bytes());
+            hasher.update([0u8]);
+            hasher.update(self.parameters[*stage as usize].as_bytes());
+        }
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Returns path of the file with the artifact
+    pub fn path(&self, artifact: Artifact) -> PathBuf {
+        let key = self.key(artifact);
+        self.directory.join(format!(
+            "{}-{}.{}",
+            artifact.name(),
+            &key[..KEY_LENGTH],
+            artifact.extension()
+        ))
+    }
+
+    /// Checks if the artifact will be reused
+    pub fn is_saved(&self, artifact: Artifact) -> bool {
+        self.resume && self.path(artifact).is_file()
+    }
+
+    /// Loads the artifact saved as JSON, if resuming and saved with matching key
+    pub fn load<T: DeserializeOwned>(&self, artifact: Artifact) -> IoResult<Option<T>> {
+        self.load_with(artifact, |reader| {
+            serde_json::from_reader(reader).map_err(IoError::from)
+        })
+    }
+
+    /// Saves the artifact as JSON
+    pub fn save<T: Serialize>(&self, artifact: Artifact, value: &T) -> IoResult<()> {
+        self.save_with(artifact, |writer| {
+            serde_json::to_writer(writer, value).map_err(IoError::from)
+        })
+    }
+
+    /// Loads the dissimilarity matrix saved in binary format, if resuming and saved with matching key
+    pub fn load_matrix(&self) -> IoResult<Option<DissimilarityMatrix>> {
+        self.load_with(Artifact::Matrix, |reader| {
+            binary::load_matrix(reader).map(|(_, matrix)| matrix)
+        })
+    }
+
+    /// Saves the dissimilarity matrix in binary format
+    pub fn save_matrix(&self, matrix: &DissimilarityMatrix) -> IoResult<()> {
+        self.save_with(Artifact::Matrix, |writer| {
+            binary::save_matrix(writer, matrix, &[])
+        })
+    }
+
+    fn load_with<T>(
+        &self,
+        artifact: Artifact,
+        read: impl FnOnce(&mut dyn Read) -> IoResult<T>,
+    ) -> IoResult<Option<T>> {
+        if !self.is_saved(artifact) {
+            return Ok(None);
+        }
+
+        let path = self.path(artifact);
+        let mut reader = BufReader::new(File::open(&path)?);
+
+        read(&mut reader).map(Some).map_err(|e| {
+            IoError::new(
+                ErrorKind::InvalidData,
+                format!("Invalid checkpoint {}: {}", path.to_string_lossy(), e),
+            )
+        })
+    }
+
+    /// Saves the artifact, replacing the file at once so interrupted run never leaves partial one
+    fn save_with(
+        &self,
+        artifact: Artifact,
+        write: impl FnOnce(&mut dyn Write) -> IoResult<()>,
+    ) -> IoResult<()> {
+        fs::create_dir_all(&self.directory)?;
+
+        let path = self.path(artifact);
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&temporary, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_checkpoint_keys() {
+        let checkpoints = Checkpoints::new(Path::new("work"), "abc")
+            .with_parameters(Artifact::Matrix, "kmer=4")
+            .with_parameters(Artifact::Clusters, "k=2");
+        let changed = checkpoints
+            .clone()
+            .with_parameters(Artifact::Clusters, "k=3");
+
+        assert_eq!(
+            checkpoints.key(Artifact::Matrix),
+            changed.key(Artifact::Matrix)
+        );
+        assert_ne!(
+            checkpoints.key(Artifact::Clusters),
+            changed.key(Artifact::Clusters)
+        );
+        assert_ne!(
+            checkpoints.key(Artifact::Matches),
+            changed.key(Artifact::Matches)
+        );
+        assert_ne!(
+            checkpoints.key(Artifact::Input),
+            Checkpoints::new(Path::new("work"), "abd").key(Artifact::Input)
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_save_load() {
+        let directory = tempdir().unwrap();
+        let checkpoints = Checkpoints::new(directory.path(), "abc");
+        let clusters = vec![vec![0usize, 1usize], vec![2usize]];
+
+        checkpoints.save(Artifact::Clusters, &clusters).unwrap();
+
+        // Saved artifacts are reused only by resumed run
+        assert!(checkpoints
+            .load::<Vec<Vec<usize>>>(Artifact::Clusters)
+            .unwrap()
+            .is_none());
+
+        let resumed = checkpoints.with_resume(true);
+        assert_eq!(resumed.load(Artifact::Clusters).unwrap(), Some(clusters));
+        assert!(resumed
+            .load::<Vec<Vec<usize>>>(Artifact::Matches)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_matrix() {
+        let directory = tempdir().unwrap();
+        let checkpoints = Checkpoints::new(directory.path(), "abc").with_resume(true);
+        let matrix = vec![vec![0f64, f64::NAN], vec![0.1f64 + 0.2f64, 0f64]];
+
+        checkpoints.save_matrix(&matrix).unwrap();
+        let loaded = checkpoints.load_matrix().unwrap().unwrap();
+
+        // Binary format keeps values exactly, including those not representable in JSON
+        assert!(checkpoints
+            .path(Artifact::Matrix)
+            .to_string_lossy()
+            .ends_with(".exqb"));
+        assert!(loaded[0][1].is_nan());
+        assert_eq!(loaded[1][0], 0.1f64 + 0.2f64);
+    }
+}
@@ -1,13 +1,18 @@
 //! Module with clustering implementations
 
 use crate::clustering::dissimilarity::DissimilarityMatrix;
+use crate::clustering::file::ClusteringFile;
 use crate::clustering::traits::Clustering;
-use crate::result::ExquisitorResult;
+use crate::progress::{report, NoObserver, Observer, Stage};
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
 use float_cmp::approx_eq;
 use kmedoids::ArrayAdapter;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::Formatter;
 use std::io::{Read, Result as IoResult, Write};
@@ -41,6 +46,46 @@ impl Cluster {
     pub fn sequence_ids(&self) -> &Vec<usize> {
         &self.elements_ids
     }
+
+    /// Returns all members of the cluster with representative as the first member
+    ///
+    /// Representative is included even if it is not listed in the elements.
+    pub fn members(&self) -> Vec<usize> {
+        let mut members = vec![self.representative_id];
+        members.extend(
+            self.elements_ids
+                .iter()
+                .filter(|&&id| id != self.representative_id),
+        );
+        members
+    }
+
+    /// Returns up to `n` members with the lowest mean dissimilarity to other members
+    ///
+    /// Members are ordered from the most central one. Ties are resolved in favour of the
+    /// representative and then of lower indices.
+    pub fn central_members(&self, distances: &DissimilarityMatrix, n: usize) -> Vec<usize> {
+        let members = self.members();
+
+        let mut centrality = members
+            .iter()
+            .enumerate()
+            .map(|(position, &member)| {
+                let total = members
+                    .iter()
+                    .map(|&other| distances[member][other])
+                    .sum::<f64>();
+                (total, position, member)
+            })
+            .collect::<Vec<_>>();
+        centrality.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        centrality
+            .into_iter()
+            .take(n)
+            .map(|(_, _, member)| member)
+            .collect()
+    }
 }
 
 impl fmt::Display for Cluster {
@@ -54,29 +99,69 @@ impl fmt::Display for Cluster {
 }
 
 /// Naive clustering method
+///
+/// Optionally sorts candidates by abundance (number of elements within max distance), so the
+/// most abundant elements become representatives regardless of the input order.
 pub struct NaiveClustering {
     max_distance: f64,
+    sort_by_abundance: bool,
 }
 
 impl NaiveClustering {
     pub fn new(max_distance: f64) -> Self {
-        Self { max_distance }
+        Self {
+            max_distance,
+            sort_by_abundance: false,
+        }
+    }
+
+    pub fn with_sort_by_abundance(mut self, sort_by_abundance: bool) -> Self {
+        self.sort_by_abundance = sort_by_abundance;
+        self
+    }
+
+    /// Returns the order in which elements are considered as representatives
+    fn candidates_order(&self, distances: &DissimilarityMatrix) -> Vec<usize> {
+        let mut order = (0..distances.len()).collect::<Vec<_>>();
+
+        if self.sort_by_abundance {
+            let abundances = distances
+                .iter()
+                .map(|row| row.iter().filter(|&&d| d < self.max_distance).count())
+                .collect::<Vec<_>>();
+
+            // Stable sort keeps the input order only for equally abundant elements
+            order.sort_by_key(|&i| Reverse(abundances[i]));
+        }
+
+        order
     }
 }
 
 impl Clustering<DissimilarityMatrix> for NaiveClustering {
     fn cluster(&self, distances: DissimilarityMatrix) -> ExquisitorResult<Vec<Cluster>> {
+        self.cluster_observed(distances, &NoObserver)
+    }
+
+    fn cluster_observed(
+        &self,
+        distances: DissimilarityMatrix,
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Cluster>> {
         let mut result = vec![];
         let mut used = vec![false; distances.len()];
+        let order = self.candidates_order(&distances);
+
+        for (position, &i) in order.iter().enumerate() {
+            report(observer, Stage::Clustering, position, order.len())?;
 
-        for i in 0..distances.len() {
             if used[i] {
                 continue;
             }
 
             used[i] = true;
             let mut ids = vec![];
-            for j in (i + 1)..distances.len() {
+            for &j in &order[(position + 1)..] {
                 if used[j] {
                     continue;
                 }
@@ -90,26 +175,57 @@ impl Clustering<DissimilarityMatrix> for NaiveClustering {
             result.push(Cluster::new(i, ids));
         }
 
+        report(observer, Stage::Clustering, order.len(), order.len())?;
+
         Ok(result)
     }
 }
 
+/// Initialization strategy of medoids
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub enum KMedoidInitialization {
+    /// Medoids sampled uniformly at random
+    Random,
+
+    /// Greedy BUILD step of the PAM algorithm
+    Build,
+
+    /// Medoids sampled with probability proportional to squared distance (k-means++)
+    KMeansPlusPlus,
+}
+
 /// K-Medoid clustering method
 ///
 /// Wraps external algorithm provided by k-medoid crate
 pub struct KMedoidClustering {
     k: usize,
+    initialization: KMedoidInitialization,
+    seed: u64,
 }
 
 impl KMedoidClustering {
     pub fn new(k: usize) -> Self {
-        Self { k }
+        Self {
+            k,
+            initialization: KMedoidInitialization::Random,
+            seed: 0,
+        }
+    }
+
+    pub fn with_initialization(mut self, initialization: KMedoidInitialization) -> Self {
+        self.initialization = initialization;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
     }
 }
 
-struct PackedDistanceMatrix(DissimilarityMatrix);
+struct PackedDistanceMatrix<'a>(&'a DissimilarityMatrix);
 
-impl ArrayAdapter<f64> for PackedDistanceMatrix {
+impl ArrayAdapter<f64> for PackedDistanceMatrix<'_> {
     fn len(&self) -> usize {
         self.0.len()
     }
@@ -123,45 +239,400 @@ impl ArrayAdapter<f64> for PackedDistanceMatrix {
     }
 }
 
+/// Maximal number of FasterPAM iterations
+const MAX_ITERATIONS: usize = 100;
+
+/// Runs FasterPAM from given initial medoids and returns loss with the final assignment
+fn run_fasterpam(distances: &PackedDistanceMatrix, medoids: &mut [usize]) -> (f64, Vec<usize>) {
+    let (loss, assignments, _, _): (f64, _, _, _) =
+        kmedoids::fasterpam(distances, medoids, MAX_ITERATIONS);
+    (loss, assignments)
+}
+
+/// Runs FasterPAM one iteration at a time, reporting each iteration to the observer
+///
+/// Iterations continue from the medoids of the previous one until no swap improves the loss.
+fn run_fasterpam_observed(
+    distances: &PackedDistanceMatrix,
+    medoids: &mut [usize],
+    observer: &dyn Observer,
+) -> ExquisitorResult<(f64, Vec<usize>)> {
+    report(observer, Stage::Clustering, 0, MAX_ITERATIONS)?;
+
+    let mut iteration = 0;
+    loop {
+        iteration += 1;
+        let (loss, assignments, _, swaps): (f64, _, _, usize) =
+            kmedoids::fasterpam(distances, medoids, 1);
+
+        if swaps == 0 || iteration == MAX_ITERATIONS {
+            report(observer, Stage::Clustering, MAX_ITERATIONS, MAX_ITERATIONS)?;
+            return Ok((loss, assignments));
+        }
+
+        report(observer, Stage::Clustering, iteration, MAX_ITERATIONS)?;
+    }
+}
+
+/// Chooses initial medoids using given strategy
+fn initial_medoids(
+    distances: &PackedDistanceMatrix,
+    k: usize,
+    initialization: KMedoidInitialization,
+    seed: u64,
+) -> ExquisitorResult<Vec<usize>> {
+    if k < 1 || k > distances.len() {
+        return Err(ExquisitorError::new(
+            ExquisitorErrorKind::InvalidParameter,
+            format!(
+                "Number of clusters should be in range 1..={}, got {}",
+                distances.len(),
+                k
+            ),
+        ));
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    Ok(match initialization {
+        KMedoidInitialization::Random => {
+            kmedoids::random_initialization(distances.len(), k, &mut rng)
+        }
+        KMedoidInitialization::Build => {
+            let (_, _, medoids): (f64, _, _) = kmedoids::pam_build(distances, k);
+            medoids
+        }
+        KMedoidInitialization::KMeansPlusPlus => {
+            kmeans_plus_plus_initialization(distances, k, &mut rng)
+        }
+    })
+}
+
+/// Chooses initial medoids using k-means++ seeding
+fn kmeans_plus_plus_initialization(
+    distances: &PackedDistanceMatrix,
+    k: usize,
+    rng: &mut StdRng,
+) -> Vec<usize> {
+    let n = distances.len();
+    let mut medoids = vec![rng.gen_range(0..n)];
+    let mut nearest = (0..n)
+        .map(|i| distances.get(i, medoids[0]))
+        .collect::<Vec<_>>();
+
+    while medoids.len() < k {
+        let weights = nearest.iter().map(|d| d * d).collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+
+        let next = if total > 0f64 {
+            let mut threshold = rng.gen_range(0f64..total);
+            let mut chosen = weights.iter().rposition(|w| *w > 0f64).unwrap();
+
+            for (idx, weight) in weights.iter().enumerate() {
+                if threshold < *weight {
+                    chosen = idx;
+                    break;
+                }
+                threshold -= weight;
+            }
+
+            chosen
+        } else {
+            // All remaining elements are duplicates of medoids, so pick the first unused one
+            (0..n).find(|idx| !medoids.contains(idx)).unwrap()
+        };
+
+        medoids.push(next);
+        for (idx, distance) in nearest.iter_mut().enumerate() {
+            *distance = f64::min(*distance, distances.get(idx, next));
+        }
+    }
+
+    medoids
+}
+
+/// Creates clusters from medoids and assignment of each element
+fn clusters_from_assignments(medoids: &[usize], assignments: &[usize]) -> Vec<Cluster> {
+    let mut clusters: Vec<Vec<usize>> = vec![Vec::new(); medoids.len()];
+
+    for (idx, assign) in assignments.iter().enumerate() {
+        clusters[*assign].push(idx);
+    }
+
+    medoids
+        .iter()
+        .zip(clusters)
+        .map(|(representative, members)| Cluster::new(*representative, members))
+        .collect::<Vec<_>>()
+}
+
 impl Clustering<DissimilarityMatrix> for KMedoidClustering {
     fn cluster(&self, distances: DissimilarityMatrix) -> ExquisitorResult<Vec<Cluster>> {
-        let distances = PackedDistanceMatrix(distances);
-        let mut medoids =
-            kmedoids::random_initialization(distances.len(), self.k, &mut rand::thread_rng());
-        let (_, assignments, _, _): (f64, _, _, _) =
-            kmedoids::fasterpam(&distances, &mut medoids, 100);
+        self.cluster_observed(distances, &NoObserver)
+    }
+
+    fn cluster_observed(
+        &self,
+        distances: DissimilarityMatrix,
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Cluster>> {
+        let distances = PackedDistanceMatrix(&distances);
+        let mut medoids = initial_medoids(&distances, self.k, self.initialization, self.seed)?;
+        let (_, assignments) = run_fasterpam_observed(&distances, &mut medoids, observer)?;
+
+        Ok(clusters_from_assignments(&medoids, &assignments))
+    }
+}
+
+/// Criterion used to select the number of clusters
+#[derive(Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub enum KSelectionCriterion {
+    /// Highest mean silhouette score
+    Silhouette,
+
+    /// Knee of the loss curve
+    Elbow,
+}
+
+/// Single point of the k selection curve
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct KSelectionPoint {
+    /// Number of clusters
+    pub k: usize,
+
+    /// Total deviation of the clustering
+    pub loss: f64,
 
-        let mut clusters: Vec<Vec<usize>> = vec![Vec::new(); self.k];
+    /// Mean silhouette score of the clustering
+    pub silhouette: f64,
+}
+
+/// Result of the k selection with the whole evaluated curve
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct KSelection {
+    k: usize,
+    criterion: KSelectionCriterion,
+    seed: u64,
+    curve: Vec<KSelectionPoint>,
+}
+
+impl KSelection {
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn criterion(&self) -> KSelectionCriterion {
+        self.criterion
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
 
-        for (idx, assign) in assignments.iter().enumerate() {
-            clusters[*assign].push(idx);
+    pub fn curve(&self) -> &Vec<KSelectionPoint> {
+        &self.curve
+    }
+}
+
+/// K-Medoid clustering method with automatic selection of k
+///
+/// Sweeps the range of k using FasterPAM and picks the best one by the given criterion
+pub struct AutoKMedoidClustering {
+    min_k: usize,
+    max_k: usize,
+    step: usize,
+    criterion: KSelectionCriterion,
+    initialization: KMedoidInitialization,
+    seed: u64,
+}
+
+impl AutoKMedoidClustering {
+    pub fn new(min_k: usize, max_k: usize, criterion: KSelectionCriterion, seed: u64) -> Self {
+        Self {
+            min_k,
+            max_k,
+            step: 1,
+            criterion,
+            initialization: KMedoidInitialization::Random,
+            seed,
         }
+    }
 
-        Ok(medoids
-            .iter()
-            .zip(clusters.iter())
-            .map(|(representative, members)| Cluster::new(*representative, members.to_owned()))
-            .collect::<Vec<_>>())
+    pub fn with_initialization(mut self, initialization: KMedoidInitialization) -> Self {
+        self.initialization = initialization;
+        self
+    }
+
+    pub fn with_step(mut self, step: usize) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Selects the number of clusters and returns the selection with the best clustering
+    pub fn select(
+        &self,
+        distances: &DissimilarityMatrix,
+    ) -> ExquisitorResult<(KSelection, Vec<Cluster>)> {
+        self.select_observed(distances, &NoObserver)
+    }
+
+    /// Selects the number of clusters, reporting each clustered k to the observer
+    pub fn select_observed(
+        &self,
+        distances: &DissimilarityMatrix,
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<(KSelection, Vec<Cluster>)> {
+        if self.min_k < 1 || self.step < 1 {
+            return Err(ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Minimal k and step should be positive, got min k = {}, step = {}",
+                    self.min_k, self.step
+                ),
+            ));
+        }
+
+        let max_k = usize::min(self.max_k, distances.len());
+        if self.min_k > max_k {
+            return Err(ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Cannot select k from range {}..={} for {} elements",
+                    self.min_k,
+                    self.max_k,
+                    distances.len()
+                ),
+            ));
+        }
+
+        let packed = PackedDistanceMatrix(distances);
+        let mut curve = vec![];
+        let mut candidates = vec![];
+        let total = (max_k - self.min_k) / self.step + 1;
+
+        report(observer, Stage::Clustering, 0, total)?;
+
+        for k in (self.min_k..=max_k).step_by(self.step) {
+            // Every k starts from the same seed, so the curve does not depend on the range
+            let mut medoids = initial_medoids(&packed, k, self.initialization, self.seed)?;
+            let (loss, assignments) = run_fasterpam(&packed, &mut medoids);
+            let (silhouette, _): (f64, _) = kmedoids::silhouette(&packed, &assignments, false);
+
+            curve.push(KSelectionPoint {
+                k,
+                loss,
+                silhouette,
+            });
+            candidates.push((medoids, assignments));
+
+            report(observer, Stage::Clustering, candidates.len(), total)?;
+        }
+
+        let best = match self.criterion {
+            KSelectionCriterion::Silhouette => best_by_silhouette(&curve),
+            KSelectionCriterion::Elbow => best_by_elbow(&curve),
+        };
+
+        let (medoids, assignments) = &candidates[best];
+        let clusters = clusters_from_assignments(medoids, assignments);
+
+        Ok((
+            KSelection {
+                k: curve[best].k,
+                criterion: self.criterion,
+                seed: self.seed,
+                curve,
+            },
+            clusters,
+        ))
     }
 }
 
-/// Saves clustering data to file
-pub fn save_clustering_data(buffer: &mut dyn Write, clusters: &Vec<Cluster>) -> IoResult<()> {
-    let json = serde_json::to_string(&clusters)?;
+impl Clustering<DissimilarityMatrix> for AutoKMedoidClustering {
+    fn cluster(&self, distances: DissimilarityMatrix) -> ExquisitorResult<Vec<Cluster>> {
+        let (_, clusters) = self.select(&distances)?;
+        Ok(clusters)
+    }
+
+    fn cluster_observed(
+        &self,
+        distances: DissimilarityMatrix,
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Cluster>> {
+        let (_, clusters) = self.select_observed(&distances, observer)?;
+        Ok(clusters)
+    }
+}
+
+/// Returns index of the point with the highest silhouette (the smallest k on ties)
+fn best_by_silhouette(curve: &[KSelectionPoint]) -> usize {
+    let mut best = 0;
+
+    for (idx, point) in curve.iter().enumerate() {
+        if point.silhouette > curve[best].silhouette {
+            best = idx;
+        }
+    }
+
+    best
+}
+
+/// Returns index of the knee of the loss curve
+///
+/// Knee is the point with the largest distance to the chord joining the first and the last
+/// point of the normalized curve.
+fn best_by_elbow(curve: &[KSelectionPoint]) -> usize {
+    if curve.len() < 3 {
+        return 0;
+    }
+
+    let (first, last) = (&curve[0], &curve[curve.len() - 1]);
+    let k_range = (last.k - first.k) as f64;
+    let loss_range = first.loss - last.loss;
+
+    if approx_eq!(f64, loss_range, 0f64) {
+        return 0;
+    }
+
+    let mut best = 0;
+    let mut best_distance = 0f64;
+
+    for (idx, point) in curve.iter().enumerate() {
+        let x = (point.k - first.k) as f64 / k_range;
+        let y = (first.loss - point.loss) / loss_range;
+        let distance = y - x;
+
+        if distance > best_distance {
+            best = idx;
+            best_distance = distance;
+        }
+    }
+
+    best
+}
+
+/// Saves k selection data to file
+pub fn save_k_selection(buffer: &mut dyn Write, selection: &KSelection) -> IoResult<()> {
+    let json = serde_json::to_string(&selection)?;
     buffer.write_all(json.as_bytes())?;
     Ok(())
 }
 
-/// Loads clustering data from file
+/// Saves clustering data to file
+pub fn save_clustering_data(buffer: &mut dyn Write, clusters: &Vec<Cluster>) -> IoResult<()> {
+    ClusteringFile::new(clusters.clone()).save(buffer)
+}
+
+/// Loads clustering data from file, ignoring its metadata
 pub fn load_clustering_data(buffer: &mut dyn Read) -> IoResult<Vec<Cluster>> {
-    let mut data = String::new();
-    buffer.read_to_string(&mut data)?;
-    let vec: Vec<Cluster> = serde_json::from_str(&data)?;
-    Ok(vec)
+    Ok(ClusteringFile::load(buffer)?.clusters)
 }
 
-/// Calculates the FMI score between two clustering result sets
-pub fn clusters_fmi_score(reference: &Vec<Cluster>, other: &Vec<Cluster>) -> f64 {
+/// Calculates the overlap of representatives between two clustering result sets
+///
+/// It is the fraction of reference representatives which are also representatives in the other
+/// clustering. Unlike FMI it depends on chosen representatives, not on the partitions.
+pub fn clusters_representatives_overlap_score(reference: &[Cluster], other: &[Cluster]) -> f64 {
     let all = reference.len() as f64;
 
     let reference: HashSet<usize> =
@@ -172,8 +643,400 @@ pub fn clusters_fmi_score(reference: &Vec<Cluster>, other: &Vec<Cluster>) -> f64
     reference.intersection(&other).count() as f64 / all
 }
 
-/// Calculates the entropy of the clusters set
-fn clusters_entropy(clusters: &Vec<Cluster>) -> f64 {
+/// Policy of handling elements present in only one of compared clusterings
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ElementsPolicy {
+    /// Ignores elements missing in any of the clusterings
+    Intersection,
+
+    /// Treats each element missing in a clustering as its separate singleton cluster
+    Singletons,
+
+    /// Fails if clusterings cover different elements
+    Strict,
+}
+
+/// Non-zero cell of the contingency table
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ContingencyCell {
+    pub row: usize,
+    pub column: usize,
+    pub count: usize,
+}
+
+/// Sparse contingency table between two clusterings
+///
+/// Rows correspond to reference clusters, columns to clusters of the other clustering. Elements
+/// of the clusters are expected to include their representatives. Only non-zero cells are
+/// stored, sorted by row and column. Singletons created by [`ElementsPolicy::Singletons`] are
+/// appended after the clusters of respective clustering.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ContingencyTable {
+    cells: Vec<ContingencyCell>,
+    row_sums: Vec<usize>,
+    column_sums: Vec<usize>,
+}
+
+impl ContingencyTable {
+    /// Builds contingency table of two clusterings in a single pass over their elements
+    pub fn new(
+        reference: &[Cluster],
+        other: &[Cluster],
+        policy: ElementsPolicy,
+    ) -> ExquisitorResult<Self> {
+        let missing = |element: usize, clustering: &str| {
+            ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Element {} is missing in the {} clustering",
+                    element, clustering
+                ),
+            )
+        };
+
+        let mut columns = HashMap::<usize, usize>::new();
+        for (column, cluster) in other.iter().enumerate() {
+            for element in cluster.members() {
+                columns.insert(element, column);
+            }
+        }
+
+        let mut cells = HashMap::<(usize, usize), usize>::new();
+        let mut row_sums = vec![0usize; reference.len()];
+        let mut column_sums = vec![0usize; other.len()];
+        let mut matched = HashSet::<usize>::new();
+
+        for (row, cluster) in reference.iter().enumerate() {
+            for element in cluster.members() {
+                let column = match columns.get(&element) {
+                    Some(&column) => {
+                        matched.insert(element);
+                        column
+                    }
+                    None => match policy {
+                        ElementsPolicy::Intersection => continue,
+                        ElementsPolicy::Singletons => {
+                            column_sums.push(0);
+                            column_sums.len() - 1
+                        }
+                        ElementsPolicy::Strict => return Err(missing(element, "other")),
+                    },
+                };
+
+                *cells.entry((row, column)).or_default() += 1;
+                row_sums[row] += 1;
+                column_sums[column] += 1;
+            }
+        }
+
+        if matched.len() < columns.len() {
+            for (column, cluster) in other.iter().enumerate() {
+                for element in cluster.members() {
+                    if columns[&element] != column || !matched.insert(element) {
+                        continue;
+                    }
+
+                    match policy {
+                        ElementsPolicy::Intersection => {}
+                        ElementsPolicy::Singletons => {
+                            cells.insert((row_sums.len(), column), 1);
+                            row_sums.push(1);
+                            column_sums[column] += 1;
+                        }
+                        ElementsPolicy::Strict => return Err(missing(element, "reference")),
+                    }
+                }
+            }
+        }
+
+        let mut cells = cells
+            .into_iter()
+            .map(|((row, column), count)| ContingencyCell { row, column, count })
+            .collect::<Vec<_>>();
+        cells.sort_by_key(|cell| (cell.row, cell.column));
+
+        Ok(Self {
+            cells,
+            row_sums,
+            column_sums,
+        })
+    }
+
+    /// Returns non-zero cells of the table
+    pub fn cells(&self) -> &[ContingencyCell] {
+        &self.cells
+    }
+
+    /// Returns number of common elements of given pair of clusters
+    pub fn count(&self, row: usize, column: usize) -> usize {
+        self.cells
+            .binary_search_by_key(&(row, column), |cell| (cell.row, cell.column))
+            .map_or(0, |idx| self.cells[idx].count)
+    }
+
+    /// Returns sizes of reference clusters
+    pub fn row_sums(&self) -> &[usize] {
+        &self.row_sums
+    }
+
+    /// Returns sizes of clusters of the other clustering
+    pub fn column_sums(&self) -> &[usize] {
+        &self.column_sums
+    }
+
+    /// Returns number of elements counted in table
+    pub fn total(&self) -> usize {
+        self.row_sums.iter().sum()
+    }
+
+    /// Returns numbers of pairs of elements in the same cluster: in both clusterings,
+    /// in reference clustering and in other clustering
+    fn pair_counts(&self) -> (f64, f64, f64) {
+        let pairs = |n: usize| (n * n.saturating_sub(1) / 2) as f64;
+
+        (
+            self.cells.iter().map(|cell| pairs(cell.count)).sum(),
+            self.row_sums.iter().map(|&n| pairs(n)).sum(),
+            self.column_sums.iter().map(|&n| pairs(n)).sum(),
+        )
+    }
+
+    /// Returns entropies (in nats) of reference and other clustering
+    fn entropies(&self) -> (f64, f64) {
+        let total = self.total() as f64;
+        let entropy = |sizes: &[usize]| -> f64 {
+            -sizes
+                .iter()
+                .filter(|&&n| n > 0)
+                .map(|&n| n as f64 / total)
+                .map(|p| p * p.ln())
+                .sum::<f64>()
+        };
+
+        (entropy(&self.row_sums), entropy(&self.column_sums))
+    }
+
+    /// Returns mutual information (in nats) of both clusterings
+    fn mutual_information(&self) -> f64 {
+        let total = self.total() as f64;
+
+        self.cells
+            .iter()
+            .map(|cell| {
+                let n = cell.count as f64;
+                let a = self.row_sums[cell.row] as f64;
+                let b = self.column_sums[cell.column] as f64;
+
+                n / total * (total * n / (a * b)).ln()
+            })
+            .sum()
+    }
+
+    /// Returns expected mutual information (in nats) of random clusterings with the same cluster
+    /// sizes, following the hypergeometric model
+    ///
+    /// Clusters of equal size contribute equally, so the sum runs over distinct sizes only.
+    fn expected_mutual_information(&self) -> f64 {
+        let total = self.total();
+        let n = total as f64;
+
+        let mut log_factorials = Vec::with_capacity(total + 1);
+        log_factorials.push(0f64);
+        for k in 1..=total {
+            log_factorials.push(log_factorials[k - 1] + (k as f64).ln());
+        }
+        let lf = |k: usize| log_factorials[k];
+
+        let group = |sizes: &[usize]| -> Vec<(usize, usize)> {
+            let mut groups = HashMap::<usize, usize>::new();
+            for &size in sizes.iter().filter(|&&size| size > 0) {
+                *groups.entry(size).or_default() += 1;
+            }
+            groups.into_iter().collect()
+        };
+
+        let rows = group(&self.row_sums);
+        let columns = group(&self.column_sums);
+        let mut expected = 0f64;
+
+        for &(a, a_count) in rows.iter() {
+            for &(b, b_count) in columns.iter() {
+                let start = usize::max(1, (a + b).saturating_sub(total));
+
+                let sum = (start..=usize::min(a, b))
+                    .map(|nij| {
+                        let log_probability = lf(a) + lf(b) + lf(total - a) + lf(total - b)
+                            - lf(total)
+                            - lf(nij)
+                            - lf(a - nij)
+                            - lf(b - nij)
+                            - lf(total + nij - a - b);
+                        let nij = nij as f64;
+
+                        nij / n * (n * nij / (a as f64 * b as f64)).ln() * log_probability.exp()
+                    })
+                    .sum::<f64>();
+
+                expected += (a_count * b_count) as f64 * sum;
+            }
+        }
+
+        expected
+    }
+}
+
+/// Comparison of two clusterings using external validity metrics
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ClusteringComparison {
+    /// Policy used for elements present in only one clustering
+    pub policy: ElementsPolicy,
+
+    /// Pair-counting Fowlkes-Mallows index
+    pub fmi: f64,
+
+    /// Adjusted Rand index
+    pub ari: f64,
+
+    /// Normalized mutual information
+    pub nmi: f64,
+
+    /// Adjusted mutual information
+    pub ami: f64,
+
+    /// Homogeneity of other clusters with respect to reference
+    pub homogeneity: f64,
+
+    /// Completeness of other clusters with respect to reference
+    pub completeness: f64,
+
+    /// Harmonic mean of homogeneity and completeness
+    pub v_measure: f64,
+
+    /// Fraction of common representatives
+    pub representatives_overlap: f64,
+
+    /// Contingency table of both clusterings
+    pub contingency: ContingencyTable,
+}
+
+/// Compares two clustering result sets using all available metrics
+pub fn compare_clusterings(
+    reference: &[Cluster],
+    other: &[Cluster],
+    policy: ElementsPolicy,
+) -> ExquisitorResult<ClusteringComparison> {
+    let contingency = ContingencyTable::new(reference, other, policy)?;
+    let (homogeneity, completeness, v_measure) = v_measure_from_contingency(&contingency);
+
+    Ok(ClusteringComparison {
+        policy,
+        fmi: fmi_from_contingency(&contingency),
+        ari: ari_from_contingency(&contingency),
+        nmi: nmi_from_contingency(&contingency),
+        ami: ami_from_contingency(&contingency),
+        homogeneity,
+        completeness,
+        v_measure,
+        representatives_overlap: clusters_representatives_overlap_score(reference, other),
+        contingency,
+    })
+}
+
+/// Builds contingency table of elements present in both clusterings
+fn common_contingency(reference: &[Cluster], other: &[Cluster]) -> ContingencyTable {
+    ContingencyTable::new(reference, other, ElementsPolicy::Intersection)
+        .expect("Intersection policy never fails")
+}
+
+/// Calculates the pair-counting FMI score between two clustering result sets
+pub fn clusters_fmi_score(reference: &[Cluster], other: &[Cluster]) -> f64 {
+    fmi_from_contingency(&common_contingency(reference, other))
+}
+
+fn fmi_from_contingency(contingency: &ContingencyTable) -> f64 {
+    let (both, reference, other) = contingency.pair_counts();
+
+    if approx_eq!(f64, both, 0f64) {
+        return 0f64;
+    }
+
+    both / (reference * other).sqrt()
+}
+
+/// Calculates the Adjusted Rand Index between two clustering result sets
+pub fn clusters_ari_score(reference: &[Cluster], other: &[Cluster]) -> f64 {
+    ari_from_contingency(&common_contingency(reference, other))
+}
+
+fn ari_from_contingency(contingency: &ContingencyTable) -> f64 {
+    let (both, reference, other) = contingency.pair_counts();
+    let total = contingency.total();
+    let pairs = (total * total.saturating_sub(1) / 2) as f64;
+
+    if approx_eq!(f64, pairs, 0f64) {
+        return 1f64;
+    }
+
+    let expected = reference * other / pairs;
+    let maximal = (reference + other) / 2f64;
+
+    // Both clusterings are trivial (single cluster or only singletons) and identical
+    if approx_eq!(f64, maximal, expected) {
+        return 1f64;
+    }
+
+    (both - expected) / (maximal - expected)
+}
+
+/// Calculates homogeneity, completeness and V-measure between two clustering result sets
+pub fn clusters_v_measure(reference: &[Cluster], other: &[Cluster]) -> (f64, f64, f64) {
+    v_measure_from_contingency(&common_contingency(reference, other))
+}
+
+fn v_measure_from_contingency(contingency: &ContingencyTable) -> (f64, f64, f64) {
+    let total = contingency.total() as f64;
+    if approx_eq!(f64, total, 0f64) {
+        return (1f64, 1f64, 1f64);
+    }
+
+    let (reference_entropy, other_entropy) = contingency.entropies();
+    let row_sums = contingency.row_sums();
+    let column_sums = contingency.column_sums();
+
+    // Conditional entropies H(reference | other) and H(other | reference)
+    let mut reference_given_other = 0f64;
+    let mut other_given_reference = 0f64;
+
+    for cell in contingency.cells() {
+        let n = cell.count as f64;
+        reference_given_other -= n / total * (n / column_sums[cell.column] as f64).ln();
+        other_given_reference -= n / total * (n / row_sums[cell.row] as f64).ln();
+    }
+
+    let homogeneity = if approx_eq!(f64, reference_entropy, 0f64) {
+        1f64
+    } else {
+        1f64 - reference_given_other / reference_entropy
+    };
+
+    let completeness = if approx_eq!(f64, other_entropy, 0f64) {
+        1f64
+    } else {
+        1f64 - other_given_reference / other_entropy
+    };
+
+    let v_measure = if approx_eq!(f64, homogeneity + completeness, 0f64) {
+        0f64
+    } else {
+        2f64 * homogeneity * completeness / (homogeneity + completeness)
+    };
+
+    (homogeneity, completeness, v_measure)
+}
+
+/// Calculates the entropy (in bits) of the clusters set
+pub fn clusters_entropy(clusters: &[Cluster]) -> f64 {
     let all = clusters
         .iter()
         .map(|c| c.elements_ids.len() as f64)
@@ -182,64 +1045,69 @@ fn clusters_entropy(clusters: &Vec<Cluster>) -> f64 {
     -clusters
         .iter()
         .map(|cluster| cluster.elements_ids.len() as f64 / all)
+        .filter(|&probability| probability > 0f64)
         .map(|probability: f64| probability * probability.log(2f64))
         .sum::<f64>()
 }
 
-/// Calculates mutual information about two clustering result sets
-fn clusters_mutual_information(u: &Vec<Cluster>, v: &Vec<Cluster>) -> f64 {
-    let count = |cluster: &Vec<Cluster>| -> f64 {
-        cluster
-            .iter()
-            .map(|cluster| cluster.elements_ids.len() as f64)
-            .sum()
-    };
-    let u_count = count(u);
-    let v_count = count(v);
-    let all = f64::max(u_count, v_count);
+/// Calculates mutual information (in bits) about two clustering result sets
+pub fn clusters_mutual_information(u: &[Cluster], v: &[Cluster]) -> f64 {
+    common_contingency(u, v).mutual_information() / std::f64::consts::LN_2
+}
 
-    let probability_in_both = |a: &Cluster, b: &Cluster| -> f64 {
-        a.elements_ids
-            .iter()
-            .map(|x| b.elements_ids.iter().filter(|&n| n == x).count() as f64)
-            .sum::<f64>()
-            / all
-    };
+/// Calculates the NMI score between two clustering result sets
+///
+/// Mutual information is normalized by the geometric mean of entropies of both clusterings.
+pub fn clusters_nmi_score(reference: &[Cluster], other: &[Cluster]) -> f64 {
+    nmi_from_contingency(&common_contingency(reference, other))
+}
 
-    u.iter()
-        .map(|a| {
-            v.iter()
-                .map(|b| {
-                    (
-                        probability_in_both(a, b),
-                        a.elements_ids.len() as f64 / u_count,
-                        b.elements_ids.len() as f64 / v_count,
-                    )
-                })
-                .map(|(p_uv, p_u, p_v)| {
-                    if approx_eq!(f64, p_uv, 0f64)
-                        || approx_eq!(f64, p_u, 0f64)
-                        || approx_eq!(f64, p_v, 0f64)
-                    {
-                        0f64
-                    } else {
-                        p_uv * (p_uv / (p_u * p_v)).log(2f64)
-                    }
-                })
-                .sum::<f64>()
-        })
-        .sum()
+fn nmi_from_contingency(contingency: &ContingencyTable) -> f64 {
+    let (reference_entropy, other_entropy) = contingency.entropies();
+    let normalizer = (reference_entropy * other_entropy).sqrt();
+
+    // At least one clustering is trivial, so they agree only if both are
+    if approx_eq!(f64, normalizer, 0f64) {
+        return if approx_eq!(f64, reference_entropy, other_entropy) {
+            1f64
+        } else {
+            0f64
+        };
+    }
+
+    contingency.mutual_information() / normalizer
 }
 
-/// Calculates the NMI score between two clustering result sets
-pub fn clusters_nmi_score(reference: &Vec<Cluster>, other: &Vec<Cluster>) -> f64 {
-    clusters_mutual_information(&reference, &other)
-        / (clusters_entropy(&reference) * clusters_entropy(&other)).sqrt()
+/// Calculates the Adjusted Mutual Information between two clustering result sets
+///
+/// Mutual information is adjusted for chance and normalized by the arithmetic mean of entropies
+/// of both clusterings.
+pub fn clusters_ami_score(reference: &[Cluster], other: &[Cluster]) -> f64 {
+    ami_from_contingency(&common_contingency(reference, other))
+}
+
+fn ami_from_contingency(contingency: &ContingencyTable) -> f64 {
+    let (reference_entropy, other_entropy) = contingency.entropies();
+    let mutual_information = contingency.mutual_information();
+    let normalizer = (reference_entropy + other_entropy) / 2f64;
+    let expected = contingency.expected_mutual_information();
+
+    // Agreement can not exceed the one expected by chance (e.g. both clusterings are trivial)
+    if approx_eq!(f64, normalizer, expected, epsilon = 1e-10) {
+        return if approx_eq!(f64, mutual_information, normalizer, epsilon = 1e-10) {
+            1f64
+        } else {
+            0f64
+        };
+    }
+
+    (mutual_information - expected) / (normalizer - expected)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::progress::{Progress, RecordingObserver};
     use float_cmp::assert_approx_eq;
 
     #[test]
@@ -295,15 +1163,319 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_naive_clustering_sort_by_abundance() {
+        let clustering = NaiveClustering::new(3.0f64).with_sort_by_abundance(true);
+
+        let distances = vec![
+            vec![0f64, 4f64, 2f64, 5f64],
+            vec![4f64, 0f64, 1f64, 6f64],
+            vec![2f64, 1f64, 0f64, 2f64],
+            vec![5f64, 6f64, 2f64, 0f64],
+        ];
+
+        let clusters = clustering.cluster(distances).unwrap();
+
+        assert_eq!(clusters, vec![Cluster::new(2, vec![0, 1, 3])]);
+    }
+
+    // region K-Medoid
+
+    fn create_two_groups_matrix() -> DissimilarityMatrix {
+        let points = [0f64, 1f64, 2f64, 20f64, 21f64, 22f64];
+
+        points
+            .iter()
+            .map(|a| points.iter().map(|b| (a - b).abs()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_k_medoid_clustering_deterministic() {
+        for initialization in [
+            KMedoidInitialization::Random,
+            KMedoidInitialization::Build,
+            KMedoidInitialization::KMeansPlusPlus,
+        ] {
+            let clustering = KMedoidClustering::new(2)
+                .with_initialization(initialization)
+                .with_seed(11);
+
+            let first = clustering.cluster(create_two_groups_matrix()).unwrap();
+            let second = clustering.cluster(create_two_groups_matrix()).unwrap();
+
+            assert_eq!(first, second);
+            assert_eq!(first.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_k_medoid_clustering_invalid_k() {
+        let clustering = KMedoidClustering::new(7);
+
+        let result = clustering.cluster(create_two_groups_matrix());
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+
+    #[test]
+    fn test_kmeans_plus_plus_initialization() {
+        let distances = create_two_groups_matrix();
+        let packed = PackedDistanceMatrix(&distances);
+
+        let medoids = kmeans_plus_plus_initialization(&packed, 2, &mut StdRng::seed_from_u64(5));
+
+        assert_eq!(medoids.len(), 2);
+        assert!(medoids.iter().any(|&m| m < 3));
+        assert!(medoids.iter().any(|&m| m >= 3));
+    }
+
+    // endregion
+
+    // region Automatic K-Medoid
+
+    #[test]
+    fn test_auto_k_medoid_clustering_silhouette() {
+        let clustering = AutoKMedoidClustering::new(2, 4, KSelectionCriterion::Silhouette, 7);
+
+        let (selection, clusters) = clustering.select(&create_two_groups_matrix()).unwrap();
+
+        assert_eq!(selection.k(), 2);
+        assert_eq!(selection.curve().len(), 3);
+        assert_eq!(clusters.len(), 2);
+
+        let mut representatives = clusters
+            .iter()
+            .map(|c| c.representative())
+            .collect::<Vec<_>>();
+        representatives.sort();
+        assert_eq!(representatives, vec![1, 4]);
+    }
+
+    #[test]
+    fn test_auto_k_medoid_clustering_deterministic() {
+        let clustering = AutoKMedoidClustering::new(1, 6, KSelectionCriterion::Elbow, 3);
+
+        let first = clustering.select(&create_two_groups_matrix()).unwrap();
+        let second = clustering.select(&create_two_groups_matrix()).unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_auto_k_medoid_clustering_invalid_range() {
+        let clustering = AutoKMedoidClustering::new(10, 20, KSelectionCriterion::Silhouette, 0);
+
+        let result = clustering.select(&create_two_groups_matrix());
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+
+    #[test]
+    fn test_best_by_elbow() {
+        let curve = [(1, 100f64), (2, 20f64), (3, 15f64), (4, 12f64), (5, 10f64)]
+            .iter()
+            .map(|&(k, loss)| KSelectionPoint {
+                k,
+                loss,
+                silhouette: 0f64,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(best_by_elbow(&curve), 1);
+    }
+
+    // endregion
+
+    // region Central members
+
+    #[test]
+    fn test_central_members() {
+        let matrix = create_two_groups_matrix();
+        let cluster = Cluster::new(0, vec![0, 1, 2]);
+
+        assert_eq!(cluster.central_members(&matrix, 1), vec![1]);
+        assert_eq!(cluster.central_members(&matrix, 2), vec![1, 0]);
+        assert_eq!(cluster.central_members(&matrix, 5), vec![1, 0, 2]);
+    }
+
+    // endregion
+
     // region FMI & NMI
 
+    #[test]
+    fn test_clusters_representatives_overlap_score() {
+        let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
+        let second = vec![Cluster::new(1, vec![1, 3]), Cluster::new(4, vec![2, 4, 5])];
+
+        let overlap_score = clusters_representatives_overlap_score(&first, &second);
+        assert_approx_eq!(f64, overlap_score, 0.5f64, epsilon = 1e-3f64);
+    }
+
     #[test]
     fn test_clusters_fmi_score() {
         let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
         let second = vec![Cluster::new(1, vec![1, 3]), Cluster::new(4, vec![2, 4, 5])];
 
+        // Pairs together in both: (2, 4); in first: 4 pairs; in second: 4 pairs
         let fmi_score = clusters_fmi_score(&first, &second);
-        assert_approx_eq!(f64, fmi_score, 0.5f64, epsilon = 1e-3f64);
+        assert_approx_eq!(f64, fmi_score, 0.25f64, epsilon = 1e-3f64);
+    }
+
+    #[test]
+    fn test_clusters_fmi_score_different_medoids() {
+        let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
+        let second = vec![Cluster::new(5, vec![1, 5]), Cluster::new(3, vec![2, 3, 4])];
+
+        assert_approx_eq!(f64, clusters_fmi_score(&first, &second), 1f64);
+        assert_approx_eq!(f64, clusters_ari_score(&first, &second), 1f64);
+        assert_approx_eq!(
+            f64,
+            clusters_representatives_overlap_score(&first, &second),
+            0f64
+        );
+    }
+
+    #[test]
+    fn test_kmedoid_progress() {
+        let points = [0f64, 1f64, 2f64, 10f64, 11f64, 12f64, 20f64, 21f64];
+        let distances = points
+            .iter()
+            .map(|a| points.iter().map(|b| (a - b).abs()).collect())
+            .collect::<DissimilarityMatrix>();
+
+        let packed = PackedDistanceMatrix(&distances);
+        let initial = initial_medoids(&packed, 3, KMedoidInitialization::Random, 5).unwrap();
+
+        let observer = RecordingObserver::new();
+        let mut medoids = initial.clone();
+        let (loss, assignments) = run_fasterpam_observed(&packed, &mut medoids, &observer).unwrap();
+
+        // Iterating one at a time converges to the same medoids as a single run
+        let mut expected_medoids = initial.clone();
+        let (expected_loss, expected_assignments, _, _): (f64, _, _, usize) =
+            kmedoids::fasterpam(&packed, &mut expected_medoids, MAX_ITERATIONS);
+        assert_eq!(medoids, expected_medoids);
+        assert_eq!(assignments, expected_assignments);
+        assert_approx_eq!(f64, loss, expected_loss);
+
+        let reported = observer.reported();
+        assert!(reported.windows(2).all(|pair| pair[0].done < pair[1].done));
+        assert_eq!(
+            reported[0],
+            Progress::new(Stage::Clustering, 0, MAX_ITERATIONS)
+        );
+        assert_eq!(
+            reported.last(),
+            Some(&Progress::new(
+                Stage::Clustering,
+                MAX_ITERATIONS,
+                MAX_ITERATIONS
+            ))
+        );
+    }
+
+    #[test]
+    fn test_contingency_table() {
+        let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
+        let second = vec![Cluster::new(1, vec![3]), Cluster::new(4, vec![2, 4, 5])];
+
+        let contingency =
+            ContingencyTable::new(&first, &second, ElementsPolicy::Intersection).unwrap();
+
+        // Representative 1 of the second clustering is counted although not listed in elements
+        assert_eq!(contingency.cells().len(), 4);
+        assert_eq!(contingency.count(0, 0), 1);
+        assert_eq!(contingency.count(0, 1), 2);
+        assert_eq!(contingency.count(1, 0), 1);
+        assert_eq!(contingency.count(1, 1), 1);
+        assert_eq!(contingency.row_sums(), &[3, 2]);
+        assert_eq!(contingency.column_sums(), &[2, 3]);
+        assert_eq!(contingency.total(), 5);
+    }
+
+    #[test]
+    fn test_contingency_table_singletons() {
+        let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
+        let second = vec![Cluster::new(3, vec![3, 6]), Cluster::new(4, vec![2, 4, 5])];
+
+        let contingency =
+            ContingencyTable::new(&first, &second, ElementsPolicy::Singletons).unwrap();
+
+        // Element 1 becomes a singleton column, element 6 a singleton row
+        assert_eq!(contingency.count(1, 2), 1);
+        assert_eq!(contingency.count(2, 0), 1);
+        assert_eq!(contingency.row_sums(), &[3, 2, 1]);
+        assert_eq!(contingency.column_sums(), &[2, 3, 1]);
+        assert_eq!(contingency.total(), 6);
+    }
+
+    #[test]
+    fn test_contingency_table_strict() {
+        let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
+        let second = vec![Cluster::new(3, vec![1, 3]), Cluster::new(4, vec![2, 4, 5])];
+        let missing = vec![Cluster::new(3, vec![3]), Cluster::new(4, vec![2, 4, 5])];
+
+        assert!(ContingencyTable::new(&first, &second, ElementsPolicy::Strict).is_ok());
+
+        let result = ContingencyTable::new(&first, &missing, ElementsPolicy::Strict);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+
+    #[test]
+    fn test_compare_clusterings() {
+        let first = vec![Cluster::new(2, vec![2, 3, 4]), Cluster::new(1, vec![1, 5])];
+        let second = vec![Cluster::new(5, vec![1, 5]), Cluster::new(3, vec![2, 3, 4])];
+
+        let comparison = compare_clusterings(&first, &second, ElementsPolicy::Strict).unwrap();
+
+        assert_approx_eq!(f64, comparison.fmi, 1f64);
+        assert_approx_eq!(f64, comparison.nmi, 1f64, epsilon = 1e-9f64);
+        assert_approx_eq!(f64, comparison.ami, 1f64, epsilon = 1e-9f64);
+        assert_approx_eq!(f64, comparison.v_measure, 1f64, epsilon = 1e-9f64);
+    }
+
+    #[test]
+    fn test_clusters_ari_score() {
+        let first = vec![
+            Cluster::new(0, vec![0, 1, 2]),
+            Cluster::new(3, vec![3, 4, 5]),
+        ];
+        let second = vec![
+            Cluster::new(0, vec![0, 1]),
+            Cluster::new(2, vec![2, 3, 4, 5]),
+        ];
+
+        // Index = 1 + 3 = 4, expected = 6 * 7 / 15 = 2.8, max = 6.5
+        let ari_score = clusters_ari_score(&first, &second);
+        assert_approx_eq!(f64, ari_score, 1.2f64 / 3.7f64, epsilon = 1e-6f64);
+    }
+
+    #[test]
+    fn test_clusters_v_measure() {
+        let first = vec![Cluster::new(0, vec![0, 1]), Cluster::new(2, vec![2, 3])];
+        let second = vec![
+            Cluster::new(0, vec![0]),
+            Cluster::new(1, vec![1]),
+            Cluster::new(2, vec![2, 3]),
+        ];
+
+        let (homogeneity, completeness, v_measure) = clusters_v_measure(&first, &second);
+
+        // Splitting reference clusters keeps homogeneity but decreases completeness
+        assert_approx_eq!(f64, homogeneity, 1f64);
+        assert_approx_eq!(f64, completeness, 2f64 / 3f64, epsilon = 1e-6f64);
+        assert_approx_eq!(f64, v_measure, 0.8f64, epsilon = 1e-6f64);
     }
 
     #[test]
@@ -340,5 +1512,29 @@ mod tests {
         assert_approx_eq!(f64, nmi_score, 0.469f64, epsilon = 1e-3f64);
     }
 
+    #[test]
+    fn test_clusters_ami_score() {
+        let first = vec![Cluster::new(0, vec![0, 1]), Cluster::new(2, vec![2, 3])];
+        let second = vec![Cluster::new(0, vec![0, 2]), Cluster::new(1, vec![1, 3])];
+
+        // MI = 0, expected MI = ln(2) / 3 and both entropies are ln(2)
+        let ami_score = clusters_ami_score(&first, &second);
+        assert_approx_eq!(f64, ami_score, -0.5f64, epsilon = 1e-9f64);
+    }
+
+    #[test]
+    fn test_clusters_ami_score_trivial() {
+        let single = vec![Cluster::new(0, vec![0, 1, 2])];
+        let singletons = vec![
+            Cluster::new(0, vec![0]),
+            Cluster::new(1, vec![1]),
+            Cluster::new(2, vec![2]),
+        ];
+
+        assert_approx_eq!(f64, clusters_ami_score(&single, &single), 1f64);
+        assert_approx_eq!(f64, clusters_ami_score(&singletons, &singletons), 1f64);
+        assert_approx_eq!(f64, clusters_ami_score(&single, &singletons), 0f64);
+    }
+
     // endregion
 }
@@ -0,0 +1,512 @@
+//! Module with consensus sequences of clusters
+
+use crate::clustering::cluster::Cluster;
+use crate::io::sequence::Sequence;
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Result as IoResult, Write};
+
+/// Symbol of the gap in alignment columns
+const GAP: u8 = b'-';
+
+/// Consensus sequence of the cluster with per-position depth and support
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Consensus {
+    /// Representative (medoid) the members were aligned to
+    pub representative: usize,
+
+    /// Number of members used to build the consensus
+    pub size: usize,
+
+    /// Consensus sequence
+    pub sequence: Sequence,
+
+    /// Number of members with a base at each consensus position
+    pub depth: Vec<usize>,
+
+    /// Weighted fraction of members agreeing with each consensus position
+    pub support: Vec<f64>,
+}
+
+impl Consensus {
+    /// Returns mean depth of the consensus positions
+    pub fn mean_depth(&self) -> f64 {
+        if self.depth.is_empty() {
+            return 0f64;
+        }
+
+        self.depth.iter().sum::<usize>() as f64 / self.depth.len() as f64
+    }
+
+    /// Returns quality of the consensus as mean support of its positions
+    pub fn quality(&self) -> f64 {
+        if self.support.is_empty() {
+            return 0f64;
+        }
+
+        self.support.iter().sum::<f64>() / self.support.len() as f64
+    }
+}
+
+/// Step of the pairwise alignment between representative and member
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum Step {
+    /// Representative position aligned to member position
+    Match(usize, usize),
+
+    /// Representative position missing in member
+    Deletion(usize),
+
+    /// Member position missing in representative
+    Insertion(usize),
+}
+
+/// Weighted votes of members for the symbol of single alignment column
+#[derive(Clone, Default, Debug)]
+struct Votes {
+    weights: BTreeMap<u8, f64>,
+    depth: usize,
+    voters: f64,
+}
+
+impl Votes {
+    fn add(&mut self, symbol: u8, weight: f64) {
+        *self.weights.entry(symbol).or_default() += weight;
+        if symbol != GAP {
+            self.depth += 1;
+        }
+    }
+
+    /// Returns symbol with the highest weight, preferring given symbol on ties
+    fn best(&self, preferred: u8) -> (u8, f64) {
+        let mut best = (
+            preferred,
+            self.weights.get(&preferred).copied().unwrap_or(0f64),
+        );
+
+        for (&symbol, &weight) in self.weights.iter() {
+            if weight > best.1 {
+                best = (symbol, weight);
+            }
+        }
+
+        best
+    }
+}
+
+/// Consensus builder
+///
+/// Aligns every member of the cluster against its representative (star alignment) and takes a
+/// per-position majority. Votes are weighted by base qualities when they are available.
+pub struct ConsensusBuilder {
+    phred_offset: u8,
+}
+
+impl ConsensusBuilder {
+    pub fn new() -> Self {
+        Self { phred_offset: 33 }
+    }
+
+    pub fn with_phred_offset(mut self, phred_offset: u8) -> Self {
+        self.phred_offset = phred_offset;
+        self
+    }
+
+    /// Builds consensus of the cluster from sequences and optional qualities of their bases
+    pub fn build(
+        &self,
+        cluster: &Cluster,
+        sequences: &[Sequence],
+        qualities: Option<&[Sequence]>,
+    ) -> ExquisitorResult<Consensus> {
+        let members = cluster.members();
+        let representative = self.sequence(sequences, members[0])?.content().as_bytes();
+
+        let mut columns = vec![Votes::default(); representative.len()];
+        let mut insertions: Vec<Vec<Votes>> = vec![vec![]; representative.len() + 1];
+        let mut total = 0f64;
+
+        for &member in members.iter() {
+            let content = self.sequence(sequences, member)?.content().as_bytes();
+            let weights = self.weights(member, content.len(), qualities)?;
+            let weight = if weights.is_empty() {
+                0f64
+            } else {
+                weights.iter().sum::<f64>() / weights.len() as f64
+            };
+            total += weight;
+
+            let mut slot = 0;
+            let mut offset = 0;
+
+            for step in align(representative, content) {
+                match step {
+                    Step::Match(r, q) => {
+                        columns[r].add(content[q], weights[q]);
+                        slot = r + 1;
+                        offset = 0;
+                    }
+                    Step::Deletion(r) => {
+                        columns[r].add(GAP, weight);
+                        slot = r + 1;
+                        offset = 0;
+                    }
+                    Step::Insertion(q) => {
+                        if insertions[slot].len() <= offset {
+                            insertions[slot].push(Votes::default());
+                        }
+                        insertions[slot][offset].add(content[q], weights[q]);
+                        insertions[slot][offset].voters += weight;
+                        offset += 1;
+                    }
+                }
+            }
+        }
+
+        let mut sequence = String::new();
+        let mut depth = vec![];
+        let mut support = vec![];
+
+        for slot in 0..=representative.len() {
+            for votes in insertions[slot].iter() {
+                // Members without insertion at this offset vote for the gap
+                let (symbol, weight) = votes.best(GAP);
+                if weight > total - votes.voters {
+                    sequence.push(symbol as char);
+                    depth.push(votes.depth);
+                    support.push(ratio(weight, total));
+                }
+            }
+
+            if let Some(votes) = columns.get(slot) {
+                let (symbol, weight) = votes.best(representative[slot]);
+                if symbol != GAP {
+                    sequence.push(symbol as char);
+                    depth.push(votes.depth);
+                    support.push(ratio(weight, votes.weights.values().sum()));
+                }
+            }
+        }
+
+        Ok(Consensus {
+            representative: members[0],
+            size: members.len(),
+            sequence: Sequence::new(&sequence),
+            depth,
+            support,
+        })
+    }
+
+    /// Builds consensus for each of the clusters
+    pub fn build_all(
+        &self,
+        clusters: &[Cluster],
+        sequences: &[Sequence],
+        qualities: Option<&[Sequence]>,
+    ) -> ExquisitorResult<Vec<Consensus>> {
+        clusters
+            .iter()
+            .map(|cluster| self.build(cluster, sequences, qualities))
+            .collect()
+    }
+
+    fn sequence<'a>(
+        &self,
+        sequences: &'a [Sequence],
+        idx: usize,
+    ) -> ExquisitorResult<&'a Sequence> {
+        sequences.get(idx).ok_or_else(|| {
+            ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Element {} is out of range of {} sequences",
+                    idx,
+                    sequences.len()
+                ),
+            )
+        })
+    }
+
+    /// Returns probabilities of correct base calls, or ones if qualities are not available
+    fn weights(
+        &self,
+        idx: usize,
+        length: usize,
+        qualities: Option<&[Sequence]>,
+    ) -> ExquisitorResult<Vec<f64>> {
+        let Some(qualities) = qualities else {
+            return Ok(vec![1f64; length]);
+        };
+
+        let quality = self.sequence(qualities, idx)?.content().as_bytes();
+        if quality.len() != length {
+            return Err(ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Quality of element {} has length {} instead of {}",
+                    idx,
+                    quality.len(),
+                    length
+                ),
+            ));
+        }
+
+        Ok(quality
+            .iter()
+            .map(|&q| {
+                let phred = q.saturating_sub(self.phred_offset) as f64;
+                1f64 - 10f64.powf(-phred / 10f64)
+            })
+            .collect())
+    }
+}
+
+impl Default for ConsensusBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Saves consensus sequences with their depth and support to file
+pub fn save_consensus(buffer: &mut dyn Write, consensus: &[Consensus]) -> IoResult<()> {
+    let json = serde_json::to_string(&consensus)?;
+    buffer.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+fn ratio(weight: f64, total: f64) -> f64 {
+    if total > 0f64 {
+        weight / total
+    } else {
+        0f64
+    }
+}
+
+/// Initial number of diagonals on each side of the band used by alignment
+const INITIAL_BAND: usize = 16;
+
+/// Globally aligns member to representative with unit costs of substitutions and gaps
+///
+/// Costs are computed only within a band of diagonals, widened until the alignment cost fits in
+/// it. Paths leaving such band cost more than the alignment, so it is optimal, while memory is
+/// proportional to the length of the representative times the width of the band.
+fn align(representative: &[u8], member: &[u8]) -> Vec<Step> {
+    let (n, m) = (representative.len(), member.len());
+    let mut band = INITIAL_BAND;
+
+    loop {
+        let costs = BandedCosts::new(representative, member, band);
+
+        if costs.get(n, m) <= band || band >= usize::max(n, m) {
+            return costs.traceback(representative, member);
+        }
+
+        band *= 2;
+    }
+}
+
+/// Costs of aligning prefixes, stored for diagonals of the band only
+struct BandedCosts {
+    /// Lowest diagonal (member position minus representative position) of the band
+    low: isize,
+    diagonals: usize,
+    costs: Vec<usize>,
+}
+
+impl BandedCosts {
+    fn new(representative: &[u8], member: &[u8], band: usize) -> Self {
+        let (n, m) = (representative.len(), member.len());
+        let difference = m as isize - n as isize;
+        let low = isize::min(0, difference) - band as isize;
+        let high = isize::max(0, difference) + band as isize;
+        let diagonals = (high - low + 1) as usize;
+
+        let mut costs = Self {
+            low,
+            diagonals,
+            costs: vec![usize::MAX; (n + 1) * diagonals],
+        };
+
+        for i in 0..=n {
+            for diagonal in low..=high {
+                let j = i as isize + diagonal;
+                if j < 0 || j > m as isize {
+                    continue;
+                }
+                let j = j as usize;
+
+                let cost = if i == 0 {
+                    j
+                } else if j == 0 {
+                    i
+                } else {
+                    let substitution = costs.get(i - 1, j - 1)
+                        + usize::from(representative[i - 1] != member[j - 1]);
+                    substitution
+                        .min(costs.get(i - 1, j).saturating_add(1))
+                        .min(costs.get(i, j - 1).saturating_add(1))
+                };
+
+                let idx = costs.index(i, j).unwrap();
+                costs.costs[idx] = cost;
+            }
+        }
+
+        costs
+    }
+
+    fn index(&self, i: usize, j: usize) -> Option<usize> {
+        let diagonal = j as isize - i as isize - self.low;
+        (0..self.diagonals as isize)
+            .contains(&diagonal)
+            .then(|| i * self.diagonals + diagonal as usize)
+    }
+
+    /// Returns cost of aligning prefixes, or maximal value outside of the band
+    fn get(&self, i: usize, j: usize) -> usize {
+        self.index(i, j).map_or(usize::MAX, |idx| self.costs[idx])
+    }
+
+    fn traceback(&self, representative: &[u8], member: &[u8]) -> Vec<Step> {
+        let mut steps = vec![];
+        let (mut i, mut j) = (representative.len(), member.len());
+
+        while i > 0 || j > 0 {
+            if i > 0
+                && j > 0
+                && self.get(i, j)
+                    == self
+                        .get(i - 1, j - 1)
+                        .saturating_add(usize::from(representative[i - 1] != member[j - 1]))
+            {
+                steps.push(Step::Match(i - 1, j - 1));
+                i -= 1;
+                j -= 1;
+            } else if i > 0 && self.get(i, j) == self.get(i - 1, j).saturating_add(1) {
+                steps.push(Step::Deletion(i - 1));
+                i -= 1;
+            } else {
+                steps.push(Step::Insertion(j - 1));
+                j -= 1;
+            }
+        }
+
+        steps.reverse();
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    fn create_sequences(contents: &[&str]) -> Vec<Sequence> {
+        contents.iter().map(|c| Sequence::new(c)).collect()
+    }
+
+    #[test]
+    fn test_align() {
+        assert_eq!(
+            align(b"ACTG", b"AGTG"),
+            vec![
+                Step::Match(0, 0),
+                Step::Match(1, 1),
+                Step::Match(2, 2),
+                Step::Match(3, 3)
+            ]
+        );
+        assert_eq!(
+            align(b"ACTG", b"ACG"),
+            vec![
+                Step::Match(0, 0),
+                Step::Match(1, 1),
+                Step::Deletion(2),
+                Step::Match(3, 2)
+            ]
+        );
+        assert_eq!(
+            align(b"ACG", b"ACTG"),
+            vec![
+                Step::Match(0, 0),
+                Step::Match(1, 1),
+                Step::Insertion(2),
+                Step::Match(2, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_align_wide_band() {
+        // Insertion longer than the initial band forces widening of the band
+        let representative = b"ACGTACGTAC".repeat(4);
+        let mut member = representative[..20].to_vec();
+        member.extend(b"T".repeat(3 * INITIAL_BAND));
+        member.extend(&representative[20..]);
+
+        let steps = align(&representative, &member);
+
+        let insertions = steps
+            .iter()
+            .filter(|step| matches!(step, Step::Insertion(_)))
+            .count();
+        assert_eq!(insertions, 3 * INITIAL_BAND);
+        assert_eq!(steps.len(), member.len());
+        assert!(steps
+            .iter()
+            .all(|step| !matches!(step, Step::Match(r, q) if representative[*r] != member[*q])));
+    }
+
+    #[test]
+    fn test_consensus_majority() {
+        // Representative carries an error at position 2 and misses a base
+        let sequences = create_sequences(&["ACGGTA", "ACTGTCA", "ACTGTCA", "ACTGTCA"]);
+        let cluster = Cluster::new(0, vec![1, 2, 3]);
+
+        let consensus = ConsensusBuilder::new()
+            .build(&cluster, &sequences, None)
+            .unwrap();
+
+        assert_eq!(consensus.sequence.content(), "ACTGTCA");
+        assert_eq!(consensus.representative, 0);
+        assert_eq!(consensus.size, 4);
+        assert_eq!(consensus.depth, vec![4, 4, 4, 4, 4, 3, 4]);
+        assert_approx_eq!(f64, consensus.support[2], 0.75);
+        assert_approx_eq!(f64, consensus.support[5], 0.75);
+        assert_approx_eq!(f64, consensus.mean_depth(), 27f64 / 7f64);
+    }
+
+    #[test]
+    fn test_consensus_quality_weighting() {
+        let sequences = create_sequences(&["ACTG", "ACAG", "ACAG"]);
+        let qualities = create_sequences(&["IIII", "II#I", "II#I"]);
+        let cluster = Cluster::new(0, vec![0, 1, 2]);
+
+        let unweighted = ConsensusBuilder::new()
+            .build(&cluster, &sequences, None)
+            .unwrap();
+        let weighted = ConsensusBuilder::new()
+            .build(&cluster, &sequences, Some(&qualities))
+            .unwrap();
+
+        // Two low quality calls (Q2) are outweighed by one high quality call (Q40)
+        assert_eq!(unweighted.sequence.content(), "ACAG");
+        assert_eq!(weighted.sequence.content(), "ACTG");
+        assert!(weighted.support[2] > 0.5);
+    }
+
+    #[test]
+    fn test_consensus_invalid_qualities() {
+        let sequences = create_sequences(&["ACTG", "ACTG"]);
+        let qualities = create_sequences(&["III", "IIII"]);
+        let cluster = Cluster::new(0, vec![1]);
+
+        let result = ConsensusBuilder::new().build(&cluster, &sequences, Some(&qualities));
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Module with dereplication of identical sequences
+
+use crate::clustering::cluster::Cluster;
+use crate::io::sequence::Sequence;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Unique sequences with indices of all original sequences collapsed into them
+///
+/// The first member of each unique is the original sequence it was created from.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Dereplication {
+    uniques: Vec<Sequence>,
+    members: Vec<Vec<usize>>,
+}
+
+impl Dereplication {
+    /// Returns unique sequences
+    pub fn uniques(&self) -> &Vec<Sequence> {
+        &self.uniques
+    }
+
+    /// Returns indices of original sequences collapsed into given unique
+    pub fn members(&self, unique: usize) -> &Vec<usize> {
+        &self.members[unique]
+    }
+
+    /// Returns number of original sequences collapsed into each unique
+    pub fn abundances(&self) -> Vec<usize> {
+        self.members.iter().map(|m| m.len()).collect()
+    }
+
+    /// Returns number of unique sequences
+    pub fn len(&self) -> usize {
+        self.uniques.len()
+    }
+
+    /// Checks if there are no unique sequences
+    pub fn is_empty(&self) -> bool {
+        self.uniques.is_empty()
+    }
+
+    /// Expands clusters of unique sequences to clusters of original sequences
+    ///
+    /// Representative becomes the original sequence of the representative unique. Duplicates of
+    /// the representative are added to the elements even if the clustering method does not
+    /// include the representative itself in the elements.
+    pub fn expand(&self, clusters: &[Cluster]) -> Vec<Cluster> {
+        clusters
+            .iter()
+            .map(|cluster| {
+                let representative = &self.members[cluster.representative()];
+                let mut elements = vec![];
+
+                if !cluster.sequence_ids().contains(&cluster.representative()) {
+                    elements.extend(&representative[1..]);
+                }
+
+                for &unique in cluster.sequence_ids() {
+                    elements.extend(&self.members[unique]);
+                }
+
+                Cluster::new(representative[0], elements)
+            })
+            .collect()
+    }
+}
+
+/// Dereplication method
+///
+/// Always collapses identical sequences. Optionally collapses sequences identical to a prefix of
+/// a longer sequence and sequences identical to the reverse complement of other sequence.
+pub struct Dereplicator {
+    prefix: bool,
+    reverse_complement: bool,
+}
+
+impl Dereplicator {
+    pub fn new() -> Self {
+        Self {
+            prefix: false,
+            reverse_complement: false,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    pub fn with_reverse_complement(mut self, reverse_complement: bool) -> Self {
+        self.reverse_complement = reverse_complement;
+        self
+    }
+
+    /// Collapses sequences into uniques, preserving the order of first occurrences
+    pub fn dereplicate(&self, sequences: &[Sequence]) -> Dereplication {
+        let mut positions = HashMap::<String, usize>::new();
+        let mut uniques = vec![];
+        let mut members: Vec<Vec<usize>> = vec![];
+
+        for (idx, sequence) in sequences.iter().enumerate() {
+            let key = self.key(sequence);
+
+            match positions.get(&key) {
+                Some(&position) => members[position].push(idx),
+                None => {
+                    positions.insert(key, uniques.len());
+                    uniques.push(sequence.clone());
+                    members.push(vec![idx]);
+                }
+            }
+        }
+
+        let dereplication = Dereplication { uniques, members };
+
+        if self.prefix {
+            self.collapse_prefixes(dereplication)
+        } else {
+            dereplication
+        }
+    }
+
+    /// Returns the key under which identical sequences are collapsed
+    fn key(&self, sequence: &Sequence) -> String {
+        if !self.reverse_complement {
+            return sequence.content().to_string();
+        }
+
+        let mut complement = sequence.clone();
+        complement.reverse_complement();
+
+        String::min(
+            sequence.content().to_string(),
+            complement.content().to_string(),
+        )
+    }
+
+    /// Collapses uniques which are prefixes of longer uniques into them
+    ///
+    /// Uniques are accepted from the longest one and every other unique collapses into the first
+    /// accepted unique it is a prefix of. With reverse complement, a unique also collapses into an
+    /// accepted unique whose reverse complement it is a prefix of, or which is extended by its
+    /// reverse complement.
+    fn collapse_prefixes(&self, dereplication: Dereplication) -> Dereplication {
+        let mut order = (0..dereplication.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| Reverse(dereplication.uniques[i].length()));
+
+        let complements = if self.reverse_complement {
+            dereplication
+                .uniques
+                .iter()
+                .map(|sequence| {
+                    let mut complement = sequence.clone();
+                    complement.reverse_complement();
+                    complement
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let mut forward = PrefixIndex::new(&dereplication.uniques);
+        let mut reverse = PrefixIndex::new(&complements);
+        let mut target = vec![0; dereplication.len()];
+
+        for (rank, &unique) in order.iter().enumerate() {
+            let sequence = dereplication.uniques[unique].content();
+            let mut found = forward.extending(sequence);
+
+            if self.reverse_complement {
+                let complement = complements[unique].content();
+                found = [
+                    found,
+                    forward.extending(complement),
+                    reverse.extending(sequence),
+                ]
+                .into_iter()
+                .flatten()
+                .min();
+            }
+
+            match found {
+                Some(longer) => target[unique] = order[longer],
+                None => {
+                    target[unique] = unique;
+                    forward.accept(unique, rank);
+                    if self.reverse_complement {
+                        reverse.accept(unique, rank);
+                    }
+                }
+            }
+        }
+
+        let mut positions = HashMap::<usize, usize>::new();
+        let mut uniques = vec![];
+        let mut members: Vec<Vec<usize>> = vec![];
+
+        for (unique, &longer) in target.iter().enumerate() {
+            if longer == unique {
+                positions.insert(unique, uniques.len());
+                uniques.push(dereplication.uniques[unique].clone());
+                members.push(dereplication.members[unique].clone());
+            }
+        }
+
+        for (unique, &longer) in target.iter().enumerate() {
+            if longer != unique {
+                members[positions[&longer]].extend(&dereplication.members[unique]);
+            }
+        }
+
+        for elements in members.iter_mut() {
+            elements[1..].sort();
+        }
+
+        Dereplication { uniques, members }
+    }
+}
+
+/// Sequences sorted by content, so sequences extending a prefix are a contiguous range
+struct PrefixIndex<'a> {
+    sequences: &'a [Sequence],
+    sorted: Vec<usize>,
+    position: Vec<usize>,
+
+    /// Ranks in the order of acceptance of accepted sequences, by their sorted position
+    accepted: MinimumTree,
+}
+
+impl<'a> PrefixIndex<'a> {
+    fn new(sequences: &'a [Sequence]) -> Self {
+        let mut sorted = (0..sequences.len()).collect::<Vec<_>>();
+        sorted.sort_by(|&a, &b| sequences[a].content().cmp(sequences[b].content()));
+
+        let mut position = vec![0; sequences.len()];
+        for (i, &sequence) in sorted.iter().enumerate() {
+            position[sequence] = i;
+        }
+
+        Self {
+            sequences,
+            sorted,
+            position,
+            accepted: MinimumTree::new(sequences.len()),
+        }
+    }
+
+    fn accept(&mut self, sequence: usize, rank: usize) {
+        self.accepted.set(self.position[sequence], rank);
+    }
+
+    /// Returns the lowest rank of accepted sequences starting with the prefix, if any
+    fn extending(&self, prefix: &str) -> Option<usize> {
+        let start = self
+            .sorted
+            .partition_point(|&i| self.sequences[i].content() < prefix);
+        let end = start
+            + self.sorted[start..]
+                .partition_point(|&i| self.sequences[i].content().starts_with(prefix));
+
+        self.accepted.minimum(start, end)
+    }
+}
+
+/// Segment tree with minimum of values set at positions
+struct MinimumTree {
+    size: usize,
+    values: Vec<usize>,
+}
+
+impl MinimumTree {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            values: vec![usize::MAX; 2 * size],
+        }
+    }
+
+    fn set(&mut self, position: usize, value: usize) {
+        let mut node = position + self.size;
+        self.values[node] = value;
+
+        while node > 1 {
+            node /= 2;
+            self.values[node] = usize::min(self.values[2 * node], self.values[2 * node + 1]);
+        }
+    }
+
+    /// Returns minimum of values set in the range of positions, if any
+    fn minimum(&self, start: usize, end: usize) -> Option<usize> {
+        let (mut left, mut right) = (start + self.size, end + self.size);
+        let mut minimum = usize::MAX;
+
+        while left < right {
+            if left % 2 == 1 {
+                minimum = usize::min(minimum, self.values[left]);
+                left += 1;
+            }
+            if right % 2 == 1 {
+                right -= 1;
+                minimum = usize::min(minimum, self.values[right]);
+            }
+            left /= 2;
+            right /= 2;
+        }
+
+        (minimum != usize::MAX).then_some(minimum)
+    }
+}
+
+impl Default for Dereplicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_sequences(contents: &[&str]) -> Vec<Sequence> {
+        contents.iter().map(|c| Sequence::new(c)).collect()
+    }
+
+    #[test]
+    fn test_dereplicate_exact() {
+        let sequences = create_sequences(&["ACTG", "AAAA", "ACTG", "CAGT", "AAAA", "ACTG"]);
+
+        let dereplication = Dereplicator::new().dereplicate(&sequences);
+
+        assert_eq!(dereplication.len(), 3);
+        assert_eq!(dereplication.uniques()[0].content(), "ACTG");
+        assert_eq!(dereplication.members(0), &vec![0, 2, 5]);
+        assert_eq!(dereplication.members(1), &vec![1, 4]);
+        assert_eq!(dereplication.abundances(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_dereplicate_reverse_complement() {
+        let sequences = create_sequences(&["ACTG", "CAGT", "AACC"]);
+
+        let exact = Dereplicator::new().dereplicate(&sequences);
+        let both = Dereplicator::new()
+            .with_reverse_complement(true)
+            .dereplicate(&sequences);
+
+        assert_eq!(exact.len(), 3);
+        assert_eq!(both.len(), 2);
+        assert_eq!(both.members(0), &vec![0, 1]);
+    }
+
+    #[test]
+    fn test_dereplicate_prefix() {
+        let sequences = create_sequences(&["ACT", "GGG", "ACTGA", "AC", "ACT"]);
+
+        let dereplication = Dereplicator::new()
+            .with_prefix(true)
+            .dereplicate(&sequences);
+
+        assert_eq!(dereplication.len(), 2);
+        assert_eq!(dereplication.uniques()[0].content(), "GGG");
+        assert_eq!(dereplication.uniques()[1].content(), "ACTGA");
+        assert_eq!(dereplication.members(1), &vec![2, 0, 3, 4]);
+    }
+
+    #[test]
+    fn test_dereplicate_prefix_first_longest() {
+        let sequences = create_sequences(&["ACG", "ACGTT", "ACGAAAA", "TTG", "AC", "ACGTTC"]);
+
+        let dereplication = Dereplicator::new()
+            .with_prefix(true)
+            .dereplicate(&sequences);
+
+        // Shared prefixes collapse into the longest sequence, the others into their extension
+        assert_eq!(dereplication.len(), 3);
+        assert_eq!(dereplication.uniques()[0].content(), "ACGAAAA");
+        assert_eq!(dereplication.members(0), &vec![2, 0, 4]);
+        assert_eq!(dereplication.uniques()[2].content(), "ACGTTC");
+        assert_eq!(dereplication.members(2), &vec![5, 1]);
+    }
+
+    #[test]
+    fn test_dereplicate_prefix_reverse_complement() {
+        // Reverse complement of "CGT" is "ACG", a prefix of "ACGTA"
+        let sequences = create_sequences(&["CGT", "ACGTA", "GGA"]);
+
+        let dereplication = Dereplicator::new()
+            .with_prefix(true)
+            .with_reverse_complement(true)
+            .dereplicate(&sequences);
+
+        assert_eq!(dereplication.len(), 2);
+        assert_eq!(dereplication.uniques()[0].content(), "ACGTA");
+        assert_eq!(dereplication.members(0), &vec![1, 0]);
+    }
+
+    #[test]
+    fn test_dereplicate_prefix_of_reverse_complement() {
+        // "TAC" is a prefix of "TACGT", the reverse complement of "ACGTA"
+        let sequences = create_sequences(&["TAC", "ACGTA", "GGA"]);
+
+        let dereplication = Dereplicator::new()
+            .with_prefix(true)
+            .with_reverse_complement(true)
+            .dereplicate(&sequences);
+
+        assert_eq!(dereplication.len(), 2);
+        assert_eq!(dereplication.uniques()[0].content(), "ACGTA");
+        assert_eq!(dereplication.members(0), &vec![1, 0]);
+    }
+
+    #[test]
+    fn test_dereplication_expand() {
+        let sequences = create_sequences(&["ACTG", "AAAA", "ACTG", "CAGT", "AAAA"]);
+        let dereplication = Dereplicator::new().dereplicate(&sequences);
+
+        let with_representative = vec![Cluster::new(1, vec![1, 2]), Cluster::new(0, vec![0])];
+        let without_representative = vec![Cluster::new(1, vec![2]), Cluster::new(0, vec![])];
+
+        assert_eq!(
+            dereplication.expand(&with_representative),
+            vec![Cluster::new(1, vec![1, 4, 3]), Cluster::new(0, vec![0, 2])]
+        );
+        assert_eq!(
+            dereplication.expand(&without_representative),
+            vec![Cluster::new(1, vec![4, 3]), Cluster::new(0, vec![2])]
+        );
+    }
+}
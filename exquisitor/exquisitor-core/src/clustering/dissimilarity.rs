@@ -1,8 +1,9 @@
 //! Module implementing dissimilarity
 
-use crate::clustering::traits::DissimilarityMeasure;
+use crate::clustering::traits::{Dissimilarities, DissimilarityMeasure};
 use crate::clustering::ALPHABET;
 use crate::io::sequence::Sequence;
+use crate::progress::{report, NoObserver, Observer, Stage};
 use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
 use num_traits::{pow, One};
 use std::cmp::min;
@@ -15,23 +16,46 @@ pub type DissimilarityMatrix = Vec<Vec<f64>>;
 
 /// Calculates dissimilarity matrix between elements using given measure
 pub fn dissimilarity_matrix<Element>(
-    elements: &Vec<Element>,
+    elements: &[Element],
     metric: &dyn DissimilarityMeasure<Element>,
+) -> ExquisitorResult<DissimilarityMatrix> {
+    dissimilarity_matrix_observed(elements, metric, &NoObserver)
+}
+
+/// Calculates dissimilarity matrix between elements, reporting each calculated row
+pub fn dissimilarity_matrix_observed<Element>(
+    elements: &[Element],
+    metric: &dyn DissimilarityMeasure<Element>,
+    observer: &dyn Observer,
 ) -> ExquisitorResult<DissimilarityMatrix> {
     let size = elements.len();
     let mut matrix = vec![vec![0.0; size]; size];
 
+    report(observer, Stage::Matrix, 0, size)?;
+
     for i in 0..size {
         for j in 0..size {
             let dissimilarity = metric.dissimilarity(&elements[i], &elements[j])?;
             matrix[i][j] = dissimilarity;
             matrix[j][i] = dissimilarity;
         }
+
+        report(observer, Stage::Matrix, i + 1, size)?;
     }
 
     Ok(matrix)
 }
 
+impl<M: DissimilarityMeasure<Sequence>> Dissimilarities for M {
+    fn matrix(
+        &self,
+        sequences: &[Sequence],
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<DissimilarityMatrix> {
+        dissimilarity_matrix_observed(sequences, self, observer)
+    }
+}
+
 /// Calculates Euclidean distance between elements
 pub struct EuclideanDistance;
 
@@ -0,0 +1,188 @@
+//! Module with versioned clustering file format
+
+use crate::clustering::cluster::Cluster;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current version of the clustering file schema
+///
+/// Version 0 denotes legacy files containing only a bare array of clusters.
+pub const CLUSTERING_SCHEMA_VERSION: u32 = 1;
+
+/// Description of the run which produced the clustering
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ClusteringMetadata {
+    /// Path to the clustered input file
+    pub input: Option<String>,
+
+    /// SHA-256 checksum of the clustered input file
+    pub input_checksum: Option<String>,
+
+    /// Identifiers of the input records, indexed by element
+    pub record_ids: Vec<String>,
+
+    /// Pipeline used to calculate dissimilarities between sequences
+    pub pipeline: Option<String>,
+
+    /// Parameters of the dissimilarity measure and clustering method
+    pub parameters: serde_json::Value,
+
+    /// Unix timestamp (in seconds) of the run start
+    pub started_at: Option<u64>,
+
+    /// Unix timestamp (in seconds) of the clustering completion
+    pub created_at: Option<u64>,
+}
+
+/// Clustering together with the schema version and metadata of its run
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ClusteringFile {
+    pub schema_version: u32,
+    pub metadata: ClusteringMetadata,
+    pub clusters: Vec<Cluster>,
+}
+
+impl ClusteringFile {
+    pub fn new(clusters: Vec<Cluster>) -> Self {
+        Self {
+            schema_version: CLUSTERING_SCHEMA_VERSION,
+            metadata: ClusteringMetadata {
+                created_at: Some(unix_timestamp()),
+                ..ClusteringMetadata::default()
+            },
+            clusters,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: ClusteringMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Saves clustering file as JSON
+    pub fn save(&self, buffer: &mut dyn Write) -> IoResult<()> {
+        let json = serde_json::to_string(self)?;
+        buffer.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads clustering file, accepting also legacy bare arrays of clusters
+    pub fn load(buffer: &mut dyn Read) -> IoResult<Self> {
+        let mut data = String::new();
+        buffer.read_to_string(&mut data)?;
+        let value: serde_json::Value = serde_json::from_str(&data)?;
+
+        if value.is_array() {
+            return Ok(Self {
+                schema_version: 0,
+                metadata: ClusteringMetadata::default(),
+                clusters: serde_json::from_value(value)?,
+            });
+        }
+
+        let version = value
+            .get("schema_version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| {
+                IoError::new(
+                    ErrorKind::InvalidData,
+                    "Clustering file should be an array of clusters or contain schema version",
+                )
+            })?;
+
+        if version > CLUSTERING_SCHEMA_VERSION as u64 {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Unsupported clustering schema version {} (supported up to {})",
+                    version, CLUSTERING_SCHEMA_VERSION
+                ),
+            ));
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// Calculates SHA-256 checksum of the data as hexadecimal string
+pub fn checksum(buffer: &mut dyn Read) -> IoResult<String> {
+    let mut hasher = Sha256::new();
+    let mut chunk = [0u8; 8192];
+
+    loop {
+        let read = buffer.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&chunk[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Returns current Unix timestamp in seconds
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_clustering_file_save_load() {
+        let clustering = ClusteringFile::new(vec![Cluster::new(0, vec![0, 1])]).with_metadata(
+            ClusteringMetadata {
+                input: Some("input.fasta".to_string()),
+                record_ids: vec!["first".to_string(), "second".to_string()],
+                parameters: serde_json::json!({"clustering": "naive", "max_distance": 0.5}),
+                ..ClusteringMetadata::default()
+            },
+        );
+
+        let mut buffer = vec![];
+        clustering.save(&mut buffer).unwrap();
+        let loaded = ClusteringFile::load(&mut Cursor::new(buffer)).unwrap();
+
+        assert_eq!(loaded, clustering);
+        assert_eq!(loaded.schema_version, CLUSTERING_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_clustering_file_load_legacy() {
+        let data = r#"[{"representative_id":0,"elements_ids":[0,1]}]"#;
+
+        let loaded = ClusteringFile::load(&mut Cursor::new(data)).unwrap();
+
+        assert_eq!(loaded.schema_version, 0);
+        assert_eq!(loaded.metadata, ClusteringMetadata::default());
+        assert_eq!(loaded.clusters, vec![Cluster::new(0, vec![0, 1])]);
+    }
+
+    #[test]
+    fn test_clustering_file_load_unsupported_version() {
+        let data = r#"{"schema_version":999,"metadata":{},"clusters":[]}"#;
+
+        let result = ClusteringFile::load(&mut Cursor::new(data));
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(
+            checksum(&mut Cursor::new("abc")).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
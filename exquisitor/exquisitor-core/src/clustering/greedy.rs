@@ -0,0 +1,255 @@
+//! Module with greedy centroid clustering (CD-HIT / VSEARCH style)
+
+use crate::clustering::cluster::Cluster;
+use crate::clustering::dereplication::Dereplicator;
+use crate::clustering::traits::Clustering;
+use crate::io::sequence::Sequence;
+use crate::progress::{report, NoObserver, Observer, Stage};
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
+use std::cmp::{max, Reverse};
+use std::collections::{HashMap, HashSet};
+
+/// Centroid created by greedy clustering
+struct Centroid {
+    unique: usize,
+    members: Vec<usize>,
+    words: usize,
+}
+
+/// Greedy centroid clustering
+///
+/// Dereplicates identical sequences, sorts unique sequences by abundance and length, and assigns
+/// each of them to the first centroid within identity threshold. Centroids are prefiltered by
+/// the number of shared k-mers, so full comparisons are run only for promising candidates.
+pub struct GreedyClustering {
+    identity: f64,
+    word_length: usize,
+    dereplicator: Dereplicator,
+}
+
+impl GreedyClustering {
+    pub fn new(identity: f64) -> Self {
+        Self {
+            identity,
+            word_length: 8,
+            dereplicator: Dereplicator::new(),
+        }
+    }
+
+    pub fn with_dereplicator(mut self, dereplicator: Dereplicator) -> Self {
+        self.dereplicator = dereplicator;
+        self
+    }
+
+    pub fn with_word_length(mut self, word_length: usize) -> Self {
+        self.word_length = word_length;
+        self
+    }
+
+    /// Returns set of k-mers of the sequence
+    fn words<'a>(&self, content: &'a [u8]) -> HashSet<&'a [u8]> {
+        if content.len() < self.word_length {
+            return HashSet::new();
+        }
+
+        content.windows(self.word_length).collect()
+    }
+
+    /// Minimal number of shared k-mers between sequences with identity above threshold
+    ///
+    /// Each difference destroys at most `word_length` k-mers of the sequence with fewer k-mers.
+    fn min_shared_words(&self, words: usize, length: usize) -> usize {
+        let differences = ((1f64 - self.identity) * length as f64).floor() as usize;
+
+        words.saturating_sub(differences * self.word_length)
+    }
+}
+
+/// Calculates identity of two sequences as one minus edit distance relative to longer sequence
+pub fn sequence_identity(a: &[u8], b: &[u8]) -> f64 {
+    let length = max(a.len(), b.len());
+    if length == 0 {
+        return 1f64;
+    }
+
+    1f64 - edit_distance(a, b) as f64 / length as f64
+}
+
+/// Calculates Levenshtein distance between two sequences
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+impl Clustering<&[Sequence]> for GreedyClustering {
+    fn cluster(&self, sequences: &[Sequence]) -> ExquisitorResult<Vec<Cluster>> {
+        self.cluster_observed(sequences, &NoObserver)
+    }
+
+    fn cluster_observed(
+        &self,
+        sequences: &[Sequence],
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Cluster>> {
+        if !(0f64..=1f64).contains(&self.identity) || self.word_length < 1 {
+            return Err(ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Identity should be in range 0..=1 and word length positive, got {} and {}",
+                    self.identity, self.word_length
+                ),
+            ));
+        }
+
+        let dereplication = self.dereplicator.dereplicate(sequences);
+        let uniques = dereplication.uniques();
+
+        // Most abundant and the longest unique sequences become centroids first
+        let mut order = (0..dereplication.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&u| {
+            (
+                Reverse(dereplication.members(u).len()),
+                Reverse(uniques[u].length()),
+            )
+        });
+
+        let mut centroids: Vec<Centroid> = vec![];
+        let mut index = HashMap::<&[u8], Vec<usize>>::new();
+
+        for (position, unique) in order.into_iter().enumerate() {
+            report(observer, Stage::Clustering, position, dereplication.len())?;
+
+            let content = uniques[unique].content().as_bytes();
+            let words = self.words(content);
+            let mut shared = vec![0usize; centroids.len()];
+
+            for word in words.iter() {
+                for &centroid in index.get(word).into_iter().flatten() {
+                    shared[centroid] += 1;
+                }
+            }
+
+            let assigned = centroids.iter().enumerate().position(|(idx, centroid)| {
+                let other = uniques[centroid.unique].content().as_bytes();
+                let min_shared = self.min_shared_words(
+                    usize::min(words.len(), centroid.words),
+                    max(content.len(), other.len()),
+                );
+
+                shared[idx] >= min_shared && sequence_identity(content, other) >= self.identity
+            });
+
+            match assigned {
+                Some(centroid) => centroids[centroid]
+                    .members
+                    .extend(dereplication.members(unique)),
+                None => {
+                    let count = words.len();
+                    for word in words {
+                        index.entry(word).or_default().push(centroids.len());
+                    }
+
+                    centroids.push(Centroid {
+                        unique,
+                        members: dereplication.members(unique).clone(),
+                        words: count,
+                    });
+                }
+            }
+        }
+
+        report(
+            observer,
+            Stage::Clustering,
+            dereplication.len(),
+            dereplication.len(),
+        )?;
+
+        Ok(centroids
+            .into_iter()
+            .map(|centroid| {
+                Cluster::new(dereplication.members(centroid.unique)[0], centroid.members)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(b"ACTG", b"ACTG"), 0);
+        assert_eq!(edit_distance(b"ACTG", b"ATTG"), 1);
+        assert_eq!(edit_distance(b"ACTG", b"CTG"), 1);
+        assert_eq!(edit_distance(b"", b"ACT"), 3);
+    }
+
+    #[test]
+    fn test_sequence_identity() {
+        assert_approx_eq!(f64, sequence_identity(b"ACTGACTGAC", b"ACTGACTGAC"), 1.0);
+        assert_approx_eq!(f64, sequence_identity(b"ACTGACTGAC", b"ACTGTCTGAC"), 0.9);
+    }
+
+    #[test]
+    fn test_greedy_clustering_abundance_order() {
+        let sequences = [
+            "ACTGACTGACTGACTGACTG",
+            "TTTTGGGGCCCCAAAATTTT",
+            "ACTGACTGACTGACTGACTC",
+            "ACTGACTGACTGACTGACTC",
+            "TTTTGGGGCCCCAAAATTTT",
+            "ACTGACTGACTGACTGACTC",
+        ]
+        .map(Sequence::new);
+
+        let clustering = GreedyClustering::new(0.9).with_word_length(4);
+        let clusters = clustering.cluster(sequences.as_slice()).unwrap();
+
+        assert_eq!(
+            clusters,
+            vec![
+                Cluster::new(2, vec![2, 3, 5, 0]),
+                Cluster::new(1, vec![1, 4])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_greedy_clustering_threshold() {
+        let sequences = ["ACTGACTGAC", "ACTGTCTGAC"].map(Sequence::new);
+
+        let strict = GreedyClustering::new(0.95).with_word_length(3);
+        let loose = GreedyClustering::new(0.85).with_word_length(3);
+
+        assert_eq!(strict.cluster(sequences.as_slice()).unwrap().len(), 2);
+        assert_eq!(loose.cluster(sequences.as_slice()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_greedy_clustering_invalid_identity() {
+        let sequences = [Sequence::new("ACTG")];
+
+        let result = GreedyClustering::new(1.5).cluster(sequences.as_slice());
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+}
@@ -1,7 +1,12 @@
 //! Module for clustering related functionalities
 pub mod cluster;
+pub mod consensus;
+pub mod dereplication;
 pub mod dissimilarity;
+pub mod file;
+pub mod greedy;
 pub mod neural;
+pub mod quality;
 pub mod traits;
 
 /// Nucleotide alphabet for DNA sequences
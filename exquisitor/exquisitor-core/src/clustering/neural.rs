@@ -1,22 +1,49 @@
 //! Module containing implementation of Neural Embedder
 
+use crate::clustering::dissimilarity::{
+    dissimilarity_matrix_observed, CosineDissimilarity, DissimilarityMatrix,
+};
+use crate::clustering::traits::Dissimilarities;
 use crate::clustering::ALPHABET;
 use crate::io::sequence::{Alignment, Sequence};
-use crate::neural::data::encode_sequence;
+use crate::neural::data::{encode_sequence, PADDING};
 use crate::neural::model::Model;
 use crate::neural::training::TrainingConfig;
+use crate::progress::{report, NoObserver, Observer, Stage};
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
 use burn::config::Config;
 use burn::module::Module;
 use burn::prelude::Backend;
-use burn::record::{BinGzFileRecorder, FullPrecisionSettings, Recorder};
 use burn::tensor::Tensor;
 use std::io::Error as IoError;
 use std::io::Result as IoResult;
 
+/// Default number of sequences embedded at once
+pub const DEFAULT_BATCH_SIZE: usize = 256;
+
+/// Pooling of embeddings of windows into embedding of the whole sequence
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum WindowPooling {
+    /// Element-wise mean of embeddings of windows
+    #[default]
+    Mean,
+
+    /// Element-wise maximum of embeddings of windows
+    Max,
+}
+
 /// Neural embedder
+///
+/// Sequences are embedded in batches of `batch_size`, so memory used by the model is bounded
+/// regardless of the number of sequences. Sequences shorter than the model input are padded with
+/// masked symbol. Longer ones are truncated, unless the window stride is set; then they are split
+/// into windows of the model input length, embeddings of which are pooled.
 pub struct NeuralEmbedder<B: Backend> {
     model: Model<B>,
     sequence_length: usize,
+    batch_size: usize,
+    window_stride: Option<usize>,
+    window_pooling: WindowPooling,
 }
 
 impl<B: Backend> NeuralEmbedder<B> {
@@ -28,8 +55,9 @@ impl<B: Backend> NeuralEmbedder<B> {
             )
         })?;
 
-        let record = BinGzFileRecorder::<FullPrecisionSettings>::new()
-            .load(format!("{artifact_dir}/model.bin").into(), &device)
+        let record = config
+            .model
+            .load_record::<B>(format!("{artifact_dir}/model.bin").into(), &device)
             .map_err(|e| {
                 IoError::new(std::io::ErrorKind::Other, format!("Cannot load model: {e}"))
             })?;
@@ -43,24 +71,388 @@ impl<B: Backend> NeuralEmbedder<B> {
             )
             .load_record(record);
 
-        Ok(Self {
+        Ok(Self::from_model(model, config.sequence_length))
+    }
+
+    /// Creates embedder of already initialized model accepting sequences of given length
+    pub fn from_model(model: Model<B>, sequence_length: usize) -> Self {
+        Self {
             model,
-            sequence_length: config.sequence_length,
-        })
+            sequence_length,
+            batch_size: DEFAULT_BATCH_SIZE,
+            window_stride: None,
+            window_pooling: WindowPooling::default(),
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
     }
 
-    /// Creates embedding of given sequence
-    pub fn embed(&self, device: B::Device, sequences: &Vec<Sequence>) -> Tensor<B, 2> {
+    /// Splits sequences longer than the model input into windows starting every `stride` symbols
+    pub fn with_window_stride(mut self, stride: Option<usize>) -> Self {
+        self.window_stride = stride.map(|stride| stride.max(1));
+        self
+    }
+
+    pub fn with_window_pooling(mut self, pooling: WindowPooling) -> Self {
+        self.window_pooling = pooling;
+        self
+    }
+
+    /// Creates embedding of given sequences in a single batch
+    ///
+    /// Every sequence is fitted to the length of the model input only when encoded, so the
+    /// sequences are not copied up front.
+    pub fn embed(&self, device: B::Device, sequences: &[Sequence]) -> Tensor<B, 2> {
         let encoded = sequences
-            .clone()
-            .iter_mut()
-            .map(|s| s.pad(self.sequence_length, 'A'.into(), Alignment::Center))
-            .map(|s| s.truncate(self.sequence_length, Alignment::Center))
-            .map(|s| encode_sequence::<B>(&device, s.content().as_ref(), ALPHABET))
-            // .map(|tensor| tensor.unsqueeze_dim::<2>(0))
+            .iter()
+            .map(|sequence| {
+                let mut sequence = sequence.clone();
+                sequence
+                    .pad(self.sequence_length, PADDING, Alignment::Center)
+                    .truncate(self.sequence_length, Alignment::Center);
+
+                encode_sequence::<B>(&device, sequence.content(), ALPHABET)
+            })
             .collect::<Vec<_>>();
 
         let batch = Tensor::cat(encoded, 0);
         self.model.forward(batch)
     }
+
+    /// Creates embeddings of given sequences, pooling embeddings of their windows
+    ///
+    /// Windows of all sequences are passed through the model in batches of `batch_size`.
+    fn embed_windows(
+        &self,
+        device: B::Device,
+        sequences: &[Sequence],
+    ) -> ExquisitorResult<Vec<Vec<f32>>> {
+        let windows = sequences
+            .iter()
+            .enumerate()
+            .flat_map(|(index, sequence)| {
+                split_windows(sequence, self.sequence_length, self.window_stride)
+                    .into_iter()
+                    .map(move |window| (index, window))
+            })
+            .collect::<Vec<_>>();
+
+        let mut pooled: Vec<Option<(Vec<f32>, usize)>> = vec![None; sequences.len()];
+
+        for batch in windows.chunks(self.batch_size) {
+            let windows = batch
+                .iter()
+                .map(|(_, window)| window.clone())
+                .collect::<Vec<_>>();
+            let vectors = to_vectors(self.embed(device.clone(), &windows))?;
+
+            for ((index, _), vector) in batch.iter().zip(vectors) {
+                pooled[*index] = Some(match pooled[*index].take() {
+                    None => (vector, 1),
+                    Some((mut pool, count)) => {
+                        for (value, other) in pool.iter_mut().zip(vector) {
+                            *value = match self.window_pooling {
+                                WindowPooling::Mean => *value + other,
+                                WindowPooling::Max => value.max(other),
+                            };
+                        }
+                        (pool, count + 1)
+                    }
+                });
+            }
+        }
+
+        Ok(pooled
+            .into_iter()
+            .flatten()
+            .map(|(mut pool, count)| {
+                if self.window_pooling == WindowPooling::Mean {
+                    pool.iter_mut().for_each(|value| *value /= count as f32);
+                }
+                pool
+            })
+            .collect())
+    }
+
+    /// Embeds sequences batch by batch, yielding embeddings of each batch once it is ready
+    pub fn embed_batches<'s>(
+        &'s self,
+        device: B::Device,
+        sequences: &'s [Sequence],
+    ) -> impl Iterator<Item = ExquisitorResult<Vec<Vec<f32>>>> + 's {
+        sequences
+            .chunks(self.batch_size)
+            .map(move |batch| match self.window_stride {
+                None => to_vectors(self.embed(device.clone(), batch)),
+                Some(_) => self.embed_windows(device.clone(), batch),
+            })
+    }
+
+    /// Creates embeddings of given sequences, one vector per sequence
+    pub fn embed_vectors(
+        &self,
+        device: B::Device,
+        sequences: &[Sequence],
+    ) -> ExquisitorResult<Vec<Vec<f32>>> {
+        self.embed_vectors_observed(device, sequences, &NoObserver)
+    }
+
+    /// Creates embeddings of given sequences, reporting each embedded batch to the observer
+    pub fn embed_vectors_observed(
+        &self,
+        device: B::Device,
+        sequences: &[Sequence],
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Vec<f32>>> {
+        let batches = sequences.len().div_ceil(self.batch_size);
+        let mut embeddings = Vec::with_capacity(sequences.len());
+
+        report(observer, Stage::Embedding, 0, batches)?;
+
+        for (batch, vectors) in self.embed_batches(device, sequences).enumerate() {
+            embeddings.extend(vectors?);
+            report(observer, Stage::Embedding, batch + 1, batches)?;
+        }
+
+        Ok(embeddings)
+    }
+}
+
+/// Splits the sequence into windows of given length starting every `stride` symbols
+///
+/// The last window is aligned to the end of the sequence, so every symbol is covered. Sequence not
+/// longer than the window, or without the stride, is kept as a single window.
+fn split_windows(sequence: &Sequence, length: usize, stride: Option<usize>) -> Vec<Sequence> {
+    let content = sequence.content();
+
+    match stride {
+        Some(stride) if content.len() > length => {
+            let last = content.len() - length;
+            let mut starts = (0..=last).step_by(stride).collect::<Vec<_>>();
+            if starts.last() != Some(&last) {
+                starts.push(last);
+            }
+
+            starts
+                .into_iter()
+                .map(|start| Sequence::new(&content[start..start + length]))
+                .collect()
+        }
+        _ => vec![sequence.clone()],
+    }
+}
+
+/// Converts batch of embeddings into vectors, one per sequence
+fn to_vectors<B: Backend>(embeddings: Tensor<B, 2>) -> ExquisitorResult<Vec<Vec<f32>>> {
+    let [count, dimension] = embeddings.dims();
+    let values = embeddings.into_data().to_vec::<f32>().map_err(|e| {
+        ExquisitorError::new(
+            ExquisitorErrorKind::InvalidParameter,
+            format!("Cannot read embedding: {:?}", e),
+        )
+    })?;
+
+    if dimension == 0 {
+        return Ok(vec![vec![]; count]);
+    }
+
+    Ok(values
+        .chunks(dimension)
+        .map(|chunk| chunk.to_vec())
+        .collect())
+}
+
+/// Cosine dissimilarity between neural embeddings of sequences
+pub struct NeuralDissimilarity<B: Backend> {
+    embedder: NeuralEmbedder<B>,
+    device: B::Device,
+}
+
+impl<B: Backend> NeuralDissimilarity<B> {
+    pub fn new(embedder: NeuralEmbedder<B>, device: B::Device) -> Self {
+        Self { embedder, device }
+    }
+}
+
+impl<B: Backend> Dissimilarities for NeuralDissimilarity<B> {
+    fn matrix(
+        &self,
+        sequences: &[Sequence],
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<DissimilarityMatrix> {
+        let embeddings =
+            self.embedder
+                .embed_vectors_observed(self.device.clone(), sequences, observer)?;
+
+        dissimilarity_matrix_observed(&embeddings, &CosineDissimilarity, observer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neural::backend::TestBackend;
+    use crate::neural::model::ModelConfig;
+    use float_cmp::assert_approx_eq;
+
+    #[test]
+    fn test_embed_batches() {
+        let device = Default::default();
+        let sequence_length = 40;
+        let model =
+            ModelConfig::new().init::<TestBackend>(&device, sequence_length * ALPHABET.len(), 0.0);
+        let embedder = NeuralEmbedder::from_model(model, sequence_length);
+
+        let sequences = ["ACGT", "TTGCA", "GGGGGGCCCC", "ACGTACGTACGTACGT", "CA"]
+            .iter()
+            .map(|s| Sequence::new(s))
+            .collect::<Vec<_>>();
+
+        let whole = embedder
+            .embed_vectors(Default::default(), &sequences)
+            .unwrap();
+
+        let embedder = embedder.with_batch_size(2);
+        let batches = embedder
+            .embed_batches(Default::default(), &sequences)
+            .map(|batch| batch.unwrap().len())
+            .collect::<Vec<_>>();
+        let batched = embedder
+            .embed_vectors(Default::default(), &sequences)
+            .unwrap();
+
+        assert_eq!(batches, vec![2, 2, 1]);
+        assert_eq!(whole.len(), sequences.len());
+        for (a, b) in whole.iter().zip(batched.iter()) {
+            assert_eq!(a.len(), b.len());
+            for (x, y) in a.iter().zip(b.iter()) {
+                assert_approx_eq!(f32, *x, *y, epsilon = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_embed_batches_single_shot() {
+        let device = Default::default();
+        let sequence_length = 40;
+        let model =
+            ModelConfig::new().init::<TestBackend>(&device, sequence_length * ALPHABET.len(), 0.0);
+        let embedder = NeuralEmbedder::from_model(model.clone(), sequence_length);
+
+        let sequences = [
+            "ACGT",
+            "TTGCA",
+            "GGGGGGCCCC",
+            "ACGTACGTACGTACGT",
+            "CA",
+            "TTT",
+            "G",
+        ]
+        .iter()
+        .map(|s| Sequence::new(s))
+        .collect::<Vec<_>>();
+
+        // Whole input embedded by the model at once
+        let single_shot = to_vectors(embedder.embed(Default::default(), &sequences)).unwrap();
+
+        for batch_size in [2, 3, 4] {
+            assert_ne!(sequences.len() % batch_size, 0);
+
+            let batched = NeuralEmbedder::from_model(model.clone(), sequence_length)
+                .with_batch_size(batch_size)
+                .embed_vectors(Default::default(), &sequences)
+                .unwrap();
+
+            assert_eq!(batched.len(), single_shot.len());
+            for (a, b) in single_shot.iter().zip(batched.iter()) {
+                assert_eq!(a.len(), b.len());
+                for (x, y) in a.iter().zip(b.iter()) {
+                    assert_approx_eq!(f32, *x, *y, epsilon = 1e-5);
+                }
+            }
+        }
+
+        let embedder = embedder.with_batch_size(2);
+        assert_eq!(embedder.embed_batches(Default::default(), &[]).count(), 0);
+        assert!(embedder
+            .embed_vectors(Default::default(), &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_split_windows() {
+        let sequence = Sequence::new("AACCGGTTAC");
+
+        let windows = split_windows(&sequence, 4, Some(3))
+            .iter()
+            .map(|window| window.content().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(windows, vec!["AACC", "CGGT", "TTAC"]);
+
+        let windows = split_windows(&sequence, 4, Some(5))
+            .iter()
+            .map(|window| window.content().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(windows, vec!["AACC", "GTTA", "TTAC"]);
+
+        assert_eq!(split_windows(&sequence, 4, None).len(), 1);
+        assert_eq!(split_windows(&sequence, 12, Some(2)).len(), 1);
+    }
+
+    #[test]
+    fn test_embed_windows() {
+        let sequence_length = 40;
+        let model = ModelConfig::new().init::<TestBackend>(
+            &Default::default(),
+            sequence_length * ALPHABET.len(),
+            0.0,
+        );
+        let embedder = NeuralEmbedder::from_model(model, sequence_length).with_batch_size(3);
+
+        let content = "ACGTTGCAAGGCTTACGATCGATCGGATCCATGCATGCAATTGGCCAATTCGCGATAT";
+        let sequences = vec![Sequence::new(content), Sequence::new("ACGT")];
+        let windows = split_windows(&sequences[0], sequence_length, Some(7));
+        let expected = embedder
+            .embed_vectors(Default::default(), &windows)
+            .unwrap();
+
+        let pooled = |pooling| {
+            NeuralEmbedder::from_model(embedder.model.clone(), sequence_length)
+                .with_batch_size(3)
+                .with_window_stride(Some(7))
+                .with_window_pooling(pooling)
+                .embed_vectors(Default::default(), &sequences)
+                .unwrap()
+        };
+
+        let mean = pooled(WindowPooling::Mean);
+        let max = pooled(WindowPooling::Max);
+
+        assert_eq!(windows.len(), 4);
+        assert_eq!(mean.len(), sequences.len());
+        for (i, value) in mean[0].iter().enumerate() {
+            let average = expected.iter().map(|e| e[i]).sum::<f32>() / expected.len() as f32;
+            assert_approx_eq!(f32, *value, average, epsilon = 1e-5);
+        }
+        for (i, value) in max[0].iter().enumerate() {
+            let maximum = expected.iter().map(|e| e[i]).fold(f32::MIN, f32::max);
+            assert_approx_eq!(f32, *value, maximum, epsilon = 1e-5);
+        }
+
+        // Sequence shorter than the model input is a single window
+        let short = embedder
+            .embed_vectors(Default::default(), &sequences[1..])
+            .unwrap();
+        for (x, y) in mean[1].iter().zip(short[0].iter()) {
+            assert_approx_eq!(f32, *x, *y, epsilon = 1e-5);
+        }
+    }
 }
@@ -0,0 +1,307 @@
+//! Module for calculating internal quality of clustering
+
+use crate::clustering::cluster::Cluster;
+use crate::clustering::dissimilarity::DissimilarityMatrix;
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
+use serde::{Deserialize, Serialize};
+
+/// Summary of distances between pairs of elements
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct DistanceSummary {
+    /// Number of pairs
+    pub count: usize,
+
+    /// Mean distance (0 if there are no pairs)
+    pub mean: f64,
+
+    /// Minimal distance (0 if there are no pairs)
+    pub min: f64,
+
+    /// Maximal distance (0 if there are no pairs)
+    pub max: f64,
+}
+
+/// Running summary of distances, accumulated without storing them
+struct DistanceAccumulator {
+    count: usize,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl DistanceAccumulator {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            sum: 0f64,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    fn add(&mut self, distance: f64) {
+        self.count += 1;
+        self.sum += distance;
+        self.min = f64::min(self.min, distance);
+        self.max = f64::max(self.max, distance);
+    }
+
+    fn summary(&self) -> DistanceSummary {
+        if self.count == 0 {
+            return DistanceSummary {
+                count: 0,
+                mean: 0f64,
+                min: 0f64,
+                max: 0f64,
+            };
+        }
+
+        DistanceSummary {
+            count: self.count,
+            mean: self.sum / self.count as f64,
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+/// Internal quality of single cluster
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ClusterQuality {
+    /// Representative of the cluster
+    pub representative: usize,
+
+    /// Number of elements in the cluster (including representative)
+    pub size: usize,
+
+    /// Mean silhouette of the cluster elements
+    pub silhouette: f64,
+
+    /// Mean distance of the elements to the representative
+    pub scatter: f64,
+}
+
+/// Internal quality of clustering computed without reference clustering
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ClusteringQuality {
+    /// Mean silhouette of all elements
+    pub silhouette: f64,
+
+    /// Davies-Bouldin index using representatives as cluster centers (lower is better)
+    pub davies_bouldin: f64,
+
+    /// Summary of distances between elements of the same cluster
+    pub intra_cluster: DistanceSummary,
+
+    /// Summary of distances between elements of different clusters
+    pub inter_cluster: DistanceSummary,
+
+    /// Quality of each cluster
+    pub clusters: Vec<ClusterQuality>,
+}
+
+/// Calculates internal quality of clustering using dissimilarity matrix of elements
+///
+/// Representatives are treated as members of their clusters. Elements not assigned to any
+/// cluster are ignored.
+pub fn evaluate_clustering(
+    distances: &DissimilarityMatrix,
+    clusters: &[Cluster],
+) -> ExquisitorResult<ClusteringQuality> {
+    let members = clusters.iter().map(Cluster::members).collect::<Vec<_>>();
+    let mut assignments = vec![None; distances.len()];
+
+    for (cluster, elements) in members.iter().enumerate() {
+        for &element in elements {
+            match assignments.get_mut(element) {
+                Some(assignment @ None) => *assignment = Some(cluster),
+                Some(Some(_)) => {
+                    return Err(ExquisitorError::new(
+                        ExquisitorErrorKind::InvalidParameter,
+                        format!("Element {} is assigned to multiple clusters", element),
+                    ))
+                }
+                None => {
+                    return Err(ExquisitorError::new(
+                        ExquisitorErrorKind::InvalidParameter,
+                        format!(
+                            "Element {} is out of range of {} elements",
+                            element,
+                            distances.len()
+                        ),
+                    ))
+                }
+            }
+        }
+    }
+
+    // Silhouette
+    let mut silhouettes = vec![0f64; distances.len()];
+
+    for (element, assignment) in assignments.iter().enumerate() {
+        let Some(own) = *assignment else {
+            continue;
+        };
+
+        if members[own].len() < 2 {
+            continue;
+        }
+
+        let mean_distance = |cluster: usize| {
+            members[cluster]
+                .iter()
+                .filter(|&&other| other != element)
+                .map(|&other| distances[element][other])
+                .sum::<f64>()
+                / (members[cluster].len() - usize::from(cluster == own)) as f64
+        };
+
+        let a = mean_distance(own);
+        let b = (0..members.len())
+            .filter(|&cluster| cluster != own && !members[cluster].is_empty())
+            .map(mean_distance)
+            .fold(f64::INFINITY, f64::min);
+
+        if b.is_finite() && f64::max(a, b) > 0f64 {
+            silhouettes[element] = (b - a) / f64::max(a, b);
+        }
+    }
+
+    let assigned = assignments.iter().filter(|a| a.is_some()).count();
+    let silhouette = if assigned > 0 {
+        silhouettes.iter().sum::<f64>() / assigned as f64
+    } else {
+        0f64
+    };
+
+    // Scatter and Davies-Bouldin index
+    let scatters = members
+        .iter()
+        .map(|elements| {
+            elements
+                .iter()
+                .map(|&element| distances[element][elements[0]])
+                .sum::<f64>()
+                / elements.len() as f64
+        })
+        .collect::<Vec<_>>();
+
+    let davies_bouldin = if members.len() > 1 {
+        (0..members.len())
+            .map(|i| {
+                (0..members.len())
+                    .filter(|&j| j != i)
+                    .map(|j| {
+                        let separation = distances[members[i][0]][members[j][0]];
+                        if separation > 0f64 {
+                            (scatters[i] + scatters[j]) / separation
+                        } else {
+                            0f64
+                        }
+                    })
+                    .fold(0f64, f64::max)
+            })
+            .sum::<f64>()
+            / members.len() as f64
+    } else {
+        0f64
+    };
+
+    // Intra and inter cluster distances
+    let mut intra = DistanceAccumulator::new();
+    let mut inter = DistanceAccumulator::new();
+
+    for i in 0..distances.len() {
+        for j in (i + 1)..distances.len() {
+            match (assignments[i], assignments[j]) {
+                (Some(a), Some(b)) if a == b => intra.add(distances[i][j]),
+                (Some(_), Some(_)) => inter.add(distances[i][j]),
+                _ => {}
+            }
+        }
+    }
+
+    let clusters = members
+        .iter()
+        .enumerate()
+        .map(|(cluster, elements)| ClusterQuality {
+            representative: elements[0],
+            size: elements.len(),
+            silhouette: elements.iter().map(|&e| silhouettes[e]).sum::<f64>()
+                / elements.len() as f64,
+            scatter: scatters[cluster],
+        })
+        .collect();
+
+    Ok(ClusteringQuality {
+        silhouette,
+        davies_bouldin,
+        intra_cluster: intra.summary(),
+        inter_cluster: inter.summary(),
+        clusters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    fn create_matrix(points: &[f64]) -> DissimilarityMatrix {
+        points
+            .iter()
+            .map(|a| points.iter().map(|b| (a - b).abs()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate_clustering() {
+        let distances = create_matrix(&[0f64, 2f64, 10f64, 12f64]);
+        let clusters = vec![Cluster::new(0, vec![0, 1]), Cluster::new(2, vec![3])];
+
+        let quality = evaluate_clustering(&distances, &clusters).unwrap();
+
+        // a = 2 for all elements, b = 11, 9, 9, 11 respectively
+        let expected_silhouette = (9f64 / 11f64 + 7f64 / 9f64) / 2f64;
+        assert_approx_eq!(f64, quality.silhouette, expected_silhouette);
+
+        // Scatter is 1 for both clusters and representatives are 10 apart
+        assert_approx_eq!(f64, quality.davies_bouldin, 0.2);
+
+        assert_eq!(quality.intra_cluster.count, 2);
+        assert_approx_eq!(f64, quality.intra_cluster.mean, 2f64);
+        assert_eq!(quality.inter_cluster.count, 4);
+        assert_approx_eq!(f64, quality.inter_cluster.min, 8f64);
+        assert_approx_eq!(f64, quality.inter_cluster.max, 12f64);
+
+        assert_eq!(quality.clusters.len(), 2);
+        assert_eq!(quality.clusters[1].representative, 2);
+        assert_eq!(quality.clusters[1].size, 2);
+    }
+
+    #[test]
+    fn test_evaluate_clustering_singletons() {
+        let distances = create_matrix(&[0f64, 5f64]);
+        let clusters = vec![Cluster::new(0, vec![]), Cluster::new(1, vec![])];
+
+        let quality = evaluate_clustering(&distances, &clusters).unwrap();
+
+        assert_approx_eq!(f64, quality.silhouette, 0f64);
+        assert_approx_eq!(f64, quality.davies_bouldin, 0f64);
+        assert_eq!(quality.intra_cluster.count, 0);
+        assert_eq!(quality.inter_cluster.count, 1);
+    }
+
+    #[test]
+    fn test_evaluate_clustering_out_of_range() {
+        let distances = create_matrix(&[0f64, 5f64]);
+        let clusters = vec![Cluster::new(0, vec![3])];
+
+        let result = evaluate_clustering(&distances, &clusters);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+}
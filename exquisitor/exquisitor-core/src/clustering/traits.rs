@@ -1,4 +1,7 @@
 use crate::clustering::cluster::Cluster;
+use crate::clustering::dissimilarity::DissimilarityMatrix;
+use crate::io::sequence::Sequence;
+use crate::progress::{report, Observer, Stage};
 use crate::result::ExquisitorResult;
 
 pub trait DissimilarityMeasure<R: ?Sized> {
@@ -6,7 +9,34 @@ pub trait DissimilarityMeasure<R: ?Sized> {
     fn dissimilarity(&self, a: &R, b: &R) -> ExquisitorResult<f64>;
 }
 
+pub trait Dissimilarities {
+    /// Calculates dissimilarity matrix between all sequences, reporting progress to the observer
+    fn matrix(
+        &self,
+        sequences: &[Sequence],
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<DissimilarityMatrix>;
+}
+
 pub trait Clustering<T: ?Sized> {
     /// Clusters the objects represents by dissimilarity matrix
     fn cluster(&self, dissimilarities: T) -> ExquisitorResult<Vec<Cluster>>;
+
+    /// Clusters the objects, reporting iterations to the observer
+    ///
+    /// Methods without iterations report only the start and the end of the clustering.
+    fn cluster_observed(
+        &self,
+        dissimilarities: T,
+        observer: &dyn Observer,
+    ) -> ExquisitorResult<Vec<Cluster>>
+    where
+        T: Sized,
+    {
+        report(observer, Stage::Clustering, 0, 1)?;
+        let clusters = self.cluster(dissimilarities)?;
+        report(observer, Stage::Clustering, 1, 1)?;
+
+        Ok(clusters)
+    }
 }
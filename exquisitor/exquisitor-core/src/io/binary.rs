@@ -0,0 +1,651 @@
+//! Module with compact binary format of dissimilarity matrices and embeddings
+//!
+//! File consists of the header followed by the body with values of all rows:
+//!
+//! | Field       | Type             | Description                                      |
+//! |-------------|------------------|--------------------------------------------------|
+//! | magic       | 4 bytes          | `EXQB`                                           |
+//! | version     | u16              | version of the format                            |
+//! | kind        | u8               | 0 for dissimilarity matrix, 1 for embeddings     |
+//! | dtype       | u8               | 0 for f32, 1 for f64                             |
+//! | rows        | u64              | number of rows                                   |
+//! | columns     | u64              | number of columns                                |
+//! | ids length  | u64              | length of the identifiers block in bytes         |
+//! | ids         | (u32, bytes)*    | UTF-8 identifiers of records, one per row or none|
+//! | padding     | bytes            | zeros aligning the body to 8 bytes               |
+//! | body        | dtype*           | values in row-major order                        |
+//!
+//! All numbers are little-endian. Body can be read at once or memory-mapped with `MappedArray`.
+//! Lengths in the header are never trusted for allocation: blocks are read only as far as the
+//! data actually present, so corrupt header fails with `InvalidData` instead of exhausting memory.
+
+use crate::clustering::dissimilarity::DissimilarityMatrix;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::path::Path;
+
+/// Magic bytes starting every binary array file
+const MAGIC: &[u8; 4] = b"EXQB";
+
+/// Current version of the binary array format
+pub const BINARY_FORMAT_VERSION: u16 = 1;
+
+/// Length of the fixed part of the header in bytes
+const FIXED_HEADER_LENGTH: usize = 32;
+
+/// Alignment of the body in bytes
+const BODY_ALIGNMENT: usize = 8;
+
+/// Kind of the array stored in file
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ArrayKind {
+    /// Square dissimilarity matrix between records
+    Matrix,
+
+    /// Embeddings of records, one per row
+    Embeddings,
+}
+
+/// Type of values stored in file
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DataType {
+    F32,
+    F64,
+}
+
+impl DataType {
+    /// Returns size of the value in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            DataType::F32 => 4,
+            DataType::F64 => 8,
+        }
+    }
+}
+
+/// Header of the binary array file
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArrayHeader {
+    pub kind: ArrayKind,
+    pub dtype: DataType,
+    pub rows: usize,
+    pub columns: usize,
+
+    /// Identifiers of records, indexed by row (empty if not saved)
+    pub ids: Vec<String>,
+}
+
+impl ArrayHeader {
+    pub fn new(kind: ArrayKind, dtype: DataType, rows: usize, columns: usize) -> Self {
+        Self {
+            kind,
+            dtype,
+            rows,
+            columns,
+            ids: vec![],
+        }
+    }
+
+    pub fn with_ids(mut self, ids: Vec<String>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Returns length of the body in bytes, or error if it overflows
+    pub fn body_length(&self) -> IoResult<usize> {
+        self.rows
+            .checked_mul(self.row_length()?)
+            .ok_or_else(|| invalid_data("Body of binary array is too large".to_string()))
+    }
+
+    /// Returns length of the single row in bytes, or error if it overflows
+    fn row_length(&self) -> IoResult<usize> {
+        self.columns
+            .checked_mul(self.dtype.size())
+            .ok_or_else(|| invalid_data("Rows of binary array are too large".to_string()))
+    }
+
+    /// Writes header to the buffer and returns its length including padding
+    pub fn write(&self, buffer: &mut dyn Write) -> IoResult<usize> {
+        if !self.ids.is_empty() && self.ids.len() != self.rows {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Number of identifiers {} does not match number of rows {}",
+                    self.ids.len(),
+                    self.rows
+                ),
+            ));
+        }
+
+        let mut ids = vec![];
+        for id in &self.ids {
+            ids.extend_from_slice(&(id.len() as u32).to_le_bytes());
+            ids.extend_from_slice(id.as_bytes());
+        }
+
+        buffer.write_all(MAGIC)?;
+        buffer.write_all(&BINARY_FORMAT_VERSION.to_le_bytes())?;
+        buffer.write_all(&[encode_kind(self.kind), encode_dtype(self.dtype)])?;
+        buffer.write_all(&(self.rows as u64).to_le_bytes())?;
+        buffer.write_all(&(self.columns as u64).to_le_bytes())?;
+        buffer.write_all(&(ids.len() as u64).to_le_bytes())?;
+        buffer.write_all(&ids)?;
+
+        let length = FIXED_HEADER_LENGTH + ids.len();
+        let padding = padding(length);
+        buffer.write_all(&vec![0u8; padding])?;
+
+        Ok(length + padding)
+    }
+
+    /// Reads header from the buffer and returns it with its length including padding
+    pub fn read(buffer: &mut dyn Read) -> IoResult<(Self, usize)> {
+        let mut fixed = [0u8; FIXED_HEADER_LENGTH];
+        buffer.read_exact(&mut fixed)?;
+
+        if &fixed[0..4] != MAGIC {
+            return Err(invalid_data("Not a binary array file".to_string()));
+        }
+
+        let version = u16::from_le_bytes([fixed[4], fixed[5]]);
+        if version != BINARY_FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported version {} of binary array file",
+                version
+            )));
+        }
+
+        let kind = decode_kind(fixed[6])?;
+        let dtype = decode_dtype(fixed[7])?;
+        let rows = read_length(&fixed[8..16])?;
+        let columns = read_length(&fixed[16..24])?;
+        let ids_length = read_length(&fixed[24..32])?;
+
+        // Rows without columns would be read without consuming any data
+        if columns == 0 && rows > 0 {
+            return Err(invalid_data(format!("{} rows without columns", rows)));
+        }
+
+        let ids = decode_ids(&read_block(buffer, ids_length)?)?;
+
+        if !ids.is_empty() && ids.len() != rows {
+            return Err(invalid_data(format!(
+                "Number of identifiers {} does not match number of rows {}",
+                ids.len(),
+                rows
+            )));
+        }
+
+        let length = FIXED_HEADER_LENGTH + ids_length;
+        let padding = padding(length);
+        buffer.read_exact(&mut [0u8; BODY_ALIGNMENT][..padding])?;
+
+        let header = Self {
+            kind,
+            dtype,
+            rows,
+            columns,
+            ids,
+        };
+        header.body_length()?;
+
+        Ok((header, length + padding))
+    }
+}
+
+/// Saves dissimilarity matrix with identifiers of records in binary format
+pub fn save_matrix(
+    buffer: &mut dyn Write,
+    matrix: &DissimilarityMatrix,
+    ids: &[String],
+) -> IoResult<()> {
+    let header = ArrayHeader::new(ArrayKind::Matrix, DataType::F64, matrix.len(), matrix.len())
+        .with_ids(ids.to_vec());
+    header.write(buffer)?;
+
+    for row in matrix {
+        if row.len() != matrix.len() {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Dissimilarity matrix should be square",
+            ));
+        }
+
+        let bytes = row
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        buffer.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Loads dissimilarity matrix and header with identifiers of records from binary format
+pub fn load_matrix(buffer: &mut dyn Read) -> IoResult<(ArrayHeader, DissimilarityMatrix)> {
+    let (header, _) = ArrayHeader::read(buffer)?;
+    expect_kind(&header, ArrayKind::Matrix)?;
+    expect_square(&header)?;
+
+    let row_length = header.row_length()?;
+    let mut matrix = vec![];
+
+    for _ in 0..header.rows {
+        matrix.push(decode_row(&read_block(buffer, row_length)?, header.dtype));
+    }
+
+    Ok((header, matrix))
+}
+
+/// Saves embeddings with identifiers of records in binary format
+pub fn save_embeddings(
+    buffer: &mut dyn Write,
+    embeddings: &[Vec<f32>],
+    ids: &[String],
+) -> IoResult<()> {
+    let columns = embeddings.first().map_or(0, |embedding| embedding.len());
+    if columns == 0 && !embeddings.is_empty() {
+        return Err(IoError::new(
+            ErrorKind::InvalidInput,
+            "Embeddings should have at least one dimension",
+        ));
+    }
+
+    let header = ArrayHeader::new(
+        ArrayKind::Embeddings,
+        DataType::F32,
+        embeddings.len(),
+        columns,
+    )
+    .with_ids(ids.to_vec());
+    header.write(buffer)?;
+
+    for embedding in embeddings {
+        if embedding.len() != columns {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Embeddings should have equal dimensions",
+            ));
+        }
+
+        let bytes = embedding
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        buffer.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Loads embeddings and header with identifiers of records from binary format
+pub fn load_embeddings(buffer: &mut dyn Read) -> IoResult<(ArrayHeader, Vec<Vec<f32>>)> {
+    let (header, _) = ArrayHeader::read(buffer)?;
+    expect_kind(&header, ArrayKind::Embeddings)?;
+
+    let row_length = header.row_length()?;
+    let mut embeddings = vec![];
+
+    for _ in 0..header.rows {
+        embeddings.push(
+            decode_row(&read_block(buffer, row_length)?, header.dtype)
+                .into_iter()
+                .map(|value| value as f32)
+                .collect(),
+        );
+    }
+
+    Ok((header, embeddings))
+}
+
+/// Binary array file mapped into memory
+///
+/// Values are read on demand, so rows of arrays larger than the available memory can be
+/// accessed without loading the whole file.
+pub struct MappedArray {
+    header: ArrayHeader,
+    offset: usize,
+    map: Mmap,
+}
+
+impl MappedArray {
+    /// Maps the binary array file into memory
+    pub fn open(path: &Path) -> IoResult<Self> {
+        let file = File::open(path)?;
+
+        // SAFETY: the file is only read, modifications by other processes are not supported
+        let map = unsafe { Mmap::map(&file)? };
+
+        let (header, offset) = ArrayHeader::read(&mut &map[..])?;
+        let end = offset.checked_add(header.body_length()?);
+        if end.is_none_or(|end| map.len() < end) {
+            return Err(invalid_data(format!(
+                "Binary array file {} is truncated",
+                path.to_string_lossy()
+            )));
+        }
+
+        Ok(Self {
+            header,
+            offset,
+            map,
+        })
+    }
+
+    pub fn header(&self) -> &ArrayHeader {
+        &self.header
+    }
+
+    /// Returns value at given row and column
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        let size = self.header.dtype.size();
+        let start = self.offset + (row * self.header.columns + column) * size;
+
+        decode_value(&self.map[start..start + size], self.header.dtype)
+    }
+
+    /// Returns values of given row
+    pub fn row(&self, row: usize) -> Vec<f64> {
+        let length = self.header.columns * self.header.dtype.size();
+        let start = self.offset + row * length;
+
+        decode_row(&self.map[start..start + length], self.header.dtype)
+    }
+
+    /// Copies all rows of the mapped dissimilarity matrix
+    pub fn to_matrix(&self) -> IoResult<DissimilarityMatrix> {
+        expect_kind(&self.header, ArrayKind::Matrix)?;
+        expect_square(&self.header)?;
+
+        Ok((0..self.header.rows).map(|row| self.row(row)).collect())
+    }
+}
+
+fn encode_kind(kind: ArrayKind) -> u8 {
+    match kind {
+        ArrayKind::Matrix => 0,
+        ArrayKind::Embeddings => 1,
+    }
+}
+
+fn decode_kind(value: u8) -> IoResult<ArrayKind> {
+    match value {
+        0 => Ok(ArrayKind::Matrix),
+        1 => Ok(ArrayKind::Embeddings),
+        _ => Err(invalid_data(format!(
+            "Unknown kind {} of binary array",
+            value
+        ))),
+    }
+}
+
+fn encode_dtype(dtype: DataType) -> u8 {
+    match dtype {
+        DataType::F32 => 0,
+        DataType::F64 => 1,
+    }
+}
+
+fn decode_dtype(value: u8) -> IoResult<DataType> {
+    match value {
+        0 => Ok(DataType::F32),
+        1 => Ok(DataType::F64),
+        _ => Err(invalid_data(format!(
+            "Unknown data type {} of binary array",
+            value
+        ))),
+    }
+}
+
+fn decode_ids(mut block: &[u8]) -> IoResult<Vec<String>> {
+    let mut ids = vec![];
+
+    while !block.is_empty() {
+        if block.len() < 4 {
+            return Err(invalid_data("Truncated identifier".to_string()));
+        }
+
+        let length = u32::from_le_bytes([block[0], block[1], block[2], block[3]]) as usize;
+        let end = 4 + length;
+        if block.len() < end {
+            return Err(invalid_data("Truncated identifier".to_string()));
+        }
+
+        let id = String::from_utf8(block[4..end].to_vec())
+            .map_err(|e| invalid_data(format!("Invalid identifier: {}", e)))?;
+        ids.push(id);
+        block = &block[end..];
+    }
+
+    Ok(ids)
+}
+
+fn decode_row(bytes: &[u8], dtype: DataType) -> Vec<f64> {
+    bytes
+        .chunks_exact(dtype.size())
+        .map(|value| decode_value(value, dtype))
+        .collect()
+}
+
+fn decode_value(bytes: &[u8], dtype: DataType) -> f64 {
+    match dtype {
+        DataType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        DataType::F64 => f64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]),
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes([
+        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+    ])
+}
+
+/// Reads length from the header, which should fit in memory addresses
+fn read_length(bytes: &[u8]) -> IoResult<usize> {
+    let length = read_u64(bytes);
+    usize::try_from(length)
+        .map_err(|_| invalid_data(format!("Length {} of binary array is too large", length)))
+}
+
+/// Reads block of given length, allocating only as much as the data actually present
+fn read_block(buffer: &mut dyn Read, length: usize) -> IoResult<Vec<u8>> {
+    let mut block = vec![];
+    buffer.take(length as u64).read_to_end(&mut block)?;
+
+    if block.len() != length {
+        return Err(invalid_data(format!(
+            "Binary array is truncated, expected block of {} bytes, found {}",
+            length,
+            block.len()
+        )));
+    }
+
+    Ok(block)
+}
+
+fn padding(length: usize) -> usize {
+    (BODY_ALIGNMENT - length % BODY_ALIGNMENT) % BODY_ALIGNMENT
+}
+
+fn expect_kind(header: &ArrayHeader, kind: ArrayKind) -> IoResult<()> {
+    if header.kind != kind {
+        return Err(invalid_data(format!(
+            "Expected {:?} in binary array file, found {:?}",
+            kind, header.kind
+        )));
+    }
+
+    Ok(())
+}
+
+fn expect_square(header: &ArrayHeader) -> IoResult<()> {
+    if header.rows != header.columns {
+        return Err(invalid_data(format!(
+            "Dissimilarity matrix should be square, found {}x{}",
+            header.rows, header.columns
+        )));
+    }
+
+    Ok(())
+}
+
+fn invalid_data(message: String) -> IoError {
+    IoError::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::NamedTempFile;
+
+    fn ids(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("read-{}", i)).collect()
+    }
+
+    #[test]
+    fn test_matrix_save_load() {
+        let matrix = vec![
+            vec![0.0, 0.25, 1.0],
+            vec![0.25, 0.0, 0.5],
+            vec![1.0, 0.5, 0.0],
+        ];
+
+        let mut buffer = vec![];
+        save_matrix(&mut buffer, &matrix, &ids(3)).unwrap();
+        let (header, loaded) = load_matrix(&mut Cursor::new(buffer)).unwrap();
+
+        assert_eq!(loaded, matrix);
+        assert_eq!(header.kind, ArrayKind::Matrix);
+        assert_eq!(header.dtype, DataType::F64);
+        assert_eq!(header.ids, ids(3));
+    }
+
+    #[test]
+    fn test_embeddings_save_load() {
+        let embeddings = vec![vec![0.5f32, -1.0], vec![2.0, 0.125]];
+
+        let mut buffer = vec![];
+        save_embeddings(&mut buffer, &embeddings, &[]).unwrap();
+
+        // Header without identifiers is 32 bytes long, so the body starts right after it
+        assert_eq!(buffer.len(), 32 + 4 * 4);
+        assert_eq!(&buffer[32..36], &0.5f32.to_le_bytes());
+
+        let (header, loaded) = load_embeddings(&mut Cursor::new(buffer)).unwrap();
+        assert_eq!(loaded, embeddings);
+        assert!(header.ids.is_empty());
+    }
+
+    #[test]
+    fn test_header_alignment() {
+        let header = ArrayHeader::new(ArrayKind::Matrix, DataType::F64, 1, 1).with_ids(ids(1));
+
+        let mut buffer = vec![];
+        let length = header.write(&mut buffer).unwrap();
+
+        assert_eq!(length % BODY_ALIGNMENT, 0);
+        assert_eq!(buffer.len(), length);
+        assert_eq!(
+            ArrayHeader::read(&mut Cursor::new(buffer)).unwrap(),
+            (header, length)
+        );
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        let mut buffer = vec![];
+        save_embeddings(&mut buffer, &[vec![1f32]], &[]).unwrap();
+
+        assert_eq!(
+            load_matrix(&mut Cursor::new(buffer)).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            load_matrix(&mut Cursor::new(b"JSON{}".repeat(8)))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    /// Creates header of the embeddings with given dimensions and identifiers block length
+    fn corrupt_header(rows: u64, columns: u64, ids_length: u64) -> Vec<u8> {
+        let mut buffer = vec![];
+        save_embeddings(&mut buffer, &[vec![1f32, 2f32]], &[]).unwrap();
+
+        buffer[8..16].copy_from_slice(&rows.to_le_bytes());
+        buffer[16..24].copy_from_slice(&columns.to_le_bytes());
+        buffer[24..32].copy_from_slice(&ids_length.to_le_bytes());
+        buffer
+    }
+
+    #[test]
+    fn test_load_corrupt_header() {
+        for buffer in [
+            // Identifiers block longer than the file
+            corrupt_header(1, 2, u64::MAX / 2),
+            // Body length overflows
+            corrupt_header(u64::MAX / 2, u64::MAX / 2, 0),
+            // Rows longer than the file
+            corrupt_header(1, u64::MAX / 8, 0),
+            // More rows than in the file
+            corrupt_header(1 << 40, 2, 0),
+            // Rows without columns
+            corrupt_header(1 << 40, 0, 0),
+        ] {
+            assert_eq!(
+                load_embeddings(&mut Cursor::new(buffer))
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_non_square_matrix() {
+        let mut buffer = vec![];
+        save_matrix(&mut buffer, &vec![vec![0.0, 1.0], vec![1.0, 0.0]], &[]).unwrap();
+        buffer[8..16].copy_from_slice(&1u64.to_le_bytes());
+
+        assert_eq!(
+            load_matrix(&mut Cursor::new(buffer)).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_mapped_array_corrupt_header() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&corrupt_header(1 << 40, 2, 0)).unwrap();
+
+        let result = MappedArray::open(file.path());
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::InvalidData);
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&corrupt_header(u64::MAX / 2, u64::MAX / 2, 0))
+            .unwrap();
+
+        let result = MappedArray::open(file.path());
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_mapped_array() {
+        let matrix = vec![vec![0.0, 0.75], vec![0.75, 0.0]];
+        let mut file = NamedTempFile::new().unwrap();
+        save_matrix(&mut file, &matrix, &ids(2)).unwrap();
+
+        let mapped = MappedArray::open(file.path()).unwrap();
+
+        assert_eq!(mapped.header().ids, ids(2));
+        assert_eq!(mapped.get(1, 0), 0.75);
+        assert_eq!(mapped.row(0), vec![0.0, 0.75]);
+        assert_eq!(mapped.to_matrix().unwrap(), matrix);
+    }
+}
@@ -0,0 +1,126 @@
+//! Module with export of embeddings to formats read by other tools (CSV, NumPy)
+
+use csv::Writer as CsvWriter;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult, Write};
+
+/// Saves embeddings in CSV format with header
+pub fn save_csv(buffer: &mut dyn Write, embeddings: &[Vec<f32>], ids: &[String]) -> IoResult<()> {
+    let dimension = embeddings.first().map_or(0, |embedding| embedding.len());
+    let mut writer = CsvWriter::from_writer(buffer);
+
+    let header = std::iter::once("id".to_string())
+        .chain((0..dimension).map(|i| format!("dim_{}", i)))
+        .collect::<Vec<_>>();
+    writer.write_record(&header)?;
+
+    for (id, embedding) in ids.iter().zip(embeddings) {
+        let record = std::iter::once(id.clone())
+            .chain(embedding.iter().map(|value| value.to_string()))
+            .collect::<Vec<_>>();
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()
+}
+
+/// Saves embeddings as two-dimensional NumPy array of little-endian float32 (format version 1.0)
+pub fn save_npy(buffer: &mut dyn Write, embeddings: &[Vec<f32>]) -> IoResult<()> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+    let dimension = embeddings.first().map_or(0, |embedding| embedding.len());
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        embeddings.len(),
+        dimension
+    );
+
+    // Magic, length of the header and the header ending with new line are aligned to 64 bytes
+    let length = MAGIC.len() + 2 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - length % 64) % 64));
+    header.push('\n');
+
+    buffer.write_all(MAGIC)?;
+    buffer.write_all(&(header.len() as u16).to_le_bytes())?;
+    buffer.write_all(header.as_bytes())?;
+
+    for embedding in embeddings {
+        if embedding.len() != dimension {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                "Embeddings should have equal dimensions",
+            ));
+        }
+
+        let bytes = embedding
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        buffer.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_embeddings() -> Vec<Vec<f32>> {
+        vec![vec![1f32, -2f32], vec![0.5f32, 3f32]]
+    }
+
+    #[test]
+    fn test_save_csv() {
+        let mut buffer = vec![];
+        save_csv(
+            &mut buffer,
+            &create_embeddings(),
+            &["a".to_string(), "b".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "id,dim_0,dim_1\na,1,-2\nb,0.5,3\n"
+        );
+    }
+
+    #[test]
+    fn test_save_npy() {
+        let mut buffer = vec![];
+        save_npy(&mut buffer, &create_embeddings()).unwrap();
+
+        // Magic string and version 1.0
+        assert_eq!(&buffer[..6], b"\x93NUMPY");
+        assert_eq!(&buffer[6..8], &[1u8, 0u8]);
+
+        // Header is padded with spaces, so the body starts at multiple of 64 bytes
+        let header_length = u16::from_le_bytes([buffer[8], buffer[9]]) as usize;
+        let body_start = 10 + header_length;
+        assert_eq!(body_start % 64, 0);
+
+        let header = std::str::from_utf8(&buffer[10..body_start]).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 2), }"));
+        assert!(header.ends_with('\n'));
+        assert!(header.trim_end().ends_with('}'));
+
+        // Little-endian float32 values in row-major order
+        assert_eq!(
+            &buffer[body_start..],
+            &[
+                0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x00, 0x3f, 0x00, 0x00,
+                0x40, 0x40,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_npy_unequal_dimensions() {
+        let embeddings = vec![vec![1f32, 2f32], vec![3f32]];
+
+        assert_eq!(
+            save_npy(&mut vec![], &embeddings).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+}
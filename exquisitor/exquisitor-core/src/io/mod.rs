@@ -1,5 +1,7 @@
 //! Module for I/O related functionalities
 
+pub mod binary;
+pub mod export;
 pub mod fasta;
 pub mod fastq;
 pub mod record;
@@ -49,6 +49,29 @@ impl Sequence {
         self
     }
 
+    /// Replaces the sequence content with its reverse complement.
+    ///
+    /// Symbols other than nucleotides are left unchanged.
+    pub fn reverse_complement(&mut self) -> &mut Self {
+        self.sequence = self
+            .sequence
+            .chars()
+            .rev()
+            .map(|c| match c {
+                'A' => 'T',
+                'T' => 'A',
+                'C' => 'G',
+                'G' => 'C',
+                'a' => 't',
+                't' => 'a',
+                'c' => 'g',
+                'g' => 'c',
+                other => other,
+            })
+            .collect();
+        self
+    }
+
     /// Truncates the sequence to the specified length based on the given alignment.
     pub fn truncate(&mut self, length: usize, alignment: Alignment) -> &mut Self {
         if length > self.length() {
@@ -150,6 +173,14 @@ mod tests {
         assert_eq!(seq.length(), 5);
     }
 
+    #[test]
+    fn test_sequence_reverse_complement() {
+        let mut seq = Sequence::new("AACTGN");
+        seq.reverse_complement();
+
+        assert_eq!(seq.content(), "NCAGTT");
+    }
+
     #[test]
     fn test_truncate_left() {
         let mut sequence = Sequence::new("AAGTCC");
@@ -2,8 +2,11 @@
 //!
 //! Provides taxonomic classification using three different methods.
 
+pub mod checkpoint;
 pub mod clustering;
 pub mod io;
 pub mod neural;
+pub mod pipeline;
+pub mod progress;
 pub mod result;
 pub mod searching;
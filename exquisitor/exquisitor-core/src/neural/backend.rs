@@ -0,0 +1,108 @@
+//! Module with backends running artificial neural network
+//!
+//! Backends are enabled by cargo features: `wgpu` runs the network on GPU and `ndarray` runs it on
+//! CPU, so neural pipelines work also on machines without GPU or Vulkan driver.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[cfg(not(any(feature = "wgpu", feature = "ndarray")))]
+compile_error!("At least one of `wgpu` and `ndarray` features should be enabled");
+
+/// Backend running the network on GPU
+#[cfg(feature = "wgpu")]
+pub type GpuBackend = burn::backend::Wgpu<f32, i32>;
+
+/// Backend running the network on CPU
+#[cfg(feature = "ndarray")]
+pub type CpuBackend = burn::backend::NdArray<f32>;
+
+/// Backend used by tests, preferring CPU which is available on every machine
+#[cfg(all(test, feature = "ndarray"))]
+pub(crate) type TestBackend = CpuBackend;
+
+/// Backend used by tests, preferring CPU which is available on every machine
+#[cfg(all(test, not(feature = "ndarray")))]
+pub(crate) type TestBackend = GpuBackend;
+
+/// Device running the network, limited to backends enabled at compile time
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Device {
+    /// GPU, through wgpu backend
+    #[cfg(feature = "wgpu")]
+    Gpu,
+
+    /// CPU, through ndarray backend
+    #[cfg(feature = "ndarray")]
+    Cpu,
+}
+
+impl Device {
+    /// Returns names of all available devices
+    pub fn names() -> Vec<&'static str> {
+        vec![
+            #[cfg(feature = "wgpu")]
+            Device::Gpu.name(),
+            #[cfg(feature = "ndarray")]
+            Device::Cpu.name(),
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "wgpu")]
+            Device::Gpu => "gpu",
+            #[cfg(feature = "ndarray")]
+            Device::Cpu => "cpu",
+        }
+    }
+}
+
+impl Default for Device {
+    /// Prefers GPU, if enabled
+    fn default() -> Self {
+        #[cfg(feature = "wgpu")]
+        return Device::Gpu;
+
+        #[cfg(not(feature = "wgpu"))]
+        return Device::Cpu;
+    }
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Device {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            #[cfg(feature = "wgpu")]
+            "gpu" => Ok(Device::Gpu),
+            #[cfg(feature = "ndarray")]
+            "cpu" => Ok(Device::Cpu),
+            other => Err(format!(
+                "Unknown device `{}`, available: {}",
+                other,
+                Device::names().join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_names() {
+        for name in Device::names() {
+            assert_eq!(name.parse::<Device>().unwrap().name(), name);
+        }
+
+        assert!("tpu".parse::<Device>().is_err());
+    }
+}
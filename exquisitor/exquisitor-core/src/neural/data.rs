@@ -27,7 +27,13 @@ pub struct SequencesDataset {
     dataset: SqliteDataset<SequencesRecord>,
 }
 
-/// Encoded the sequences using one-hot encoding with given alphabet
+/// Symbol padding sequences shorter than the model input
+///
+/// Padding is masked out of the input: its one-hot encoding has only zeros, so the model does not
+/// read it as a nucleotide while the layout of the input stays the same.
+pub const PADDING: char = '-';
+
+/// Encoded the sequences using one-hot encoding with given alphabet, masking the padding
 fn one_hot(s: &str, alphabet: &[char]) -> Vec<f32> {
     let mut char_index = HashMap::new();
 
@@ -38,6 +44,10 @@ fn one_hot(s: &str, alphabet: &[char]) -> Vec<f32> {
     let mut encoded = vec![0.0; s.len() * alphabet.len()];
 
     for (idx, char) in s.chars().enumerate() {
+        if char == PADDING {
+            continue;
+        }
+
         let position: usize = match char_index.get(&char) {
             None => 0,
             Some(p) => *p,
@@ -53,6 +63,7 @@ fn one_hot(s: &str, alphabet: &[char]) -> Vec<f32> {
 pub fn encode_sequence<B: Backend>(device: &B::Device, s: &str, alphabet: &[char]) -> Tensor<B, 2> {
     Tensor::<B, 1>::from_data(one_hot(s, alphabet).as_slice(), device).unsqueeze_dim::<2>(0)
 }
+
 impl SequencesDataset {
     pub fn new(path: &str) -> IoResult<Self> {
         let dataset = SqliteDataset::from_db_file(path, "data").unwrap();
@@ -117,3 +128,23 @@ impl<B: Backend> Batcher<SequencesRecord, SequencesBatch<B>> for SequencesBatche
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_hot_padding() {
+        let encoded = one_hot("-AG-", ALPHABET);
+
+        assert_eq!(
+            encoded,
+            vec![
+                0.0, 0.0, 0.0, 0.0, //
+                1.0, 0.0, 0.0, 0.0, //
+                0.0, 0.0, 0.0, 1.0, //
+                0.0, 0.0, 0.0, 0.0,
+            ]
+        );
+    }
+}
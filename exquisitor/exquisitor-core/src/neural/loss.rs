@@ -1,6 +1,8 @@
-//! Module with implementation of loss function for artificial neural network
+//! Module with implementation of loss functions for artificial neural network
 
 use burn::prelude::{Backend, Config, Module, Tensor};
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// Configuration of Contrastive Loss
 #[derive(Config, Debug)]
@@ -64,16 +66,239 @@ impl ContrastiveLoss {
     }
 }
 
+/// Configuration of Triplet Margin Loss
+#[derive(Config, Debug)]
+pub struct TripletMarginLossConfig {
+    /// Margin between distances of negative and positive cases
+    #[config(default = 1.0)]
+    pub margin: f64,
+}
+
+impl TripletMarginLossConfig {
+    pub fn init(&self) -> TripletMarginLoss {
+        TripletMarginLoss {
+            margin: self.margin,
+        }
+    }
+}
+
+/// Triplet margin loss
+///
+/// Penalizes anchors whose euclidean distance to the negative case is not greater by at least the
+/// margin than the distance to the positive case.
+#[derive(Module, Clone, Debug)]
+pub struct TripletMarginLoss {
+    /// Margin between distances of negative and positive cases
+    pub margin: f64,
+}
+
+impl TripletMarginLoss {
+    pub fn forward<B: Backend>(
+        &self,
+        anchors: Tensor<B, 2>,
+        positives: Tensor<B, 2>,
+        negatives: Tensor<B, 2>,
+    ) -> Tensor<B, 1> {
+        let distance = |tensor: Tensor<B, 2>, other: Tensor<B, 2>| {
+            tensor.sub(other).powi_scalar(2).sum_dim(1).sqrt()
+        };
+
+        let pos = distance(anchors.clone(), positives);
+        let neg = distance(anchors, negatives);
+
+        (pos - neg).add_scalar(self.margin).clamp_min(0.0).mean()
+    }
+}
+
+/// Configuration of InfoNCE (NT-Xent) Loss
+#[derive(Config, Debug)]
+pub struct InfoNceLossConfig {
+    /// Temperature scaling cosine similarities
+    #[config(default = 0.1)]
+    pub temperature: f64,
+}
+
+impl InfoNceLossConfig {
+    pub fn init(&self) -> InfoNceLoss {
+        InfoNceLoss {
+            temperature: self.temperature,
+        }
+    }
+}
+
+/// In-batch InfoNCE (NT-Xent) loss
+///
+/// Every anchor is classified against positive and negative cases of the whole batch, so all other
+/// items of the batch serve as its negatives. The loss is the cross entropy of the softmax of
+/// cosine similarities divided by the temperature, with the own positive case as the target.
+#[derive(Module, Clone, Debug)]
+pub struct InfoNceLoss {
+    /// Temperature scaling cosine similarities
+    pub temperature: f64,
+}
+
+impl InfoNceLoss {
+    pub fn forward<B: Backend>(
+        &self,
+        anchors: Tensor<B, 2>,
+        positives: Tensor<B, 2>,
+        negatives: Tensor<B, 2>,
+    ) -> Tensor<B, 1> {
+        let normalize = |tensor: Tensor<B, 2>| {
+            let magnitude = tensor
+                .clone()
+                .powi_scalar(2)
+                .sum_dim(1)
+                .sqrt()
+                .clamp_min(1e-12);
+            tensor.div(magnitude)
+        };
+
+        let anchors = normalize(anchors);
+        let positives = normalize(positives);
+        let candidates = Tensor::cat(vec![positives.clone(), normalize(negatives)], 0);
+
+        let logits = anchors
+            .clone()
+            .matmul(candidates.transpose())
+            .div_scalar(self.temperature);
+        let targets = anchors
+            .mul(positives)
+            .sum_dim(1)
+            .div_scalar(self.temperature);
+
+        // Log-sum-exp shifted by the maximum for numerical stability
+        let maximum = logits.clone().max_dim(1);
+        let log_sum = logits
+            .sub(maximum.clone())
+            .exp()
+            .sum_dim(1)
+            .log()
+            .add(maximum);
+
+        log_sum.sub(targets).mean()
+    }
+}
+
+/// Configuration of the loss function used for training
+///
+/// Missing parameters take default values, e.g. `{"type": "info-nce"}` has temperature 0.1.
+/// Negative margins and non-positive temperature are rejected when the configuration is loaded.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum LossConfig {
+    /// Contrastive loss with margins of cosine similarity
+    Contrastive {
+        #[serde(default = "default_margin", deserialize_with = "non_negative")]
+        margin_positive: f64,
+        #[serde(default = "default_margin_negative", deserialize_with = "non_negative")]
+        margin_negative: f64,
+    },
+
+    /// Triplet margin loss
+    TripletMargin {
+        #[serde(default = "default_margin", deserialize_with = "non_negative")]
+        margin: f64,
+    },
+
+    /// In-batch InfoNCE (NT-Xent) loss
+    InfoNce {
+        #[serde(default = "default_temperature", deserialize_with = "positive")]
+        temperature: f64,
+    },
+}
+
+fn default_margin() -> f64 {
+    1.0
+}
+
+fn default_margin_negative() -> f64 {
+    0.25
+}
+
+fn default_temperature() -> f64 {
+    0.1
+}
+
+fn non_negative<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value.is_nan() || value < 0.0 {
+        return Err(D::Error::custom(format!(
+            "margin should be non-negative, got {}",
+            value
+        )));
+    }
+
+    Ok(value)
+}
+
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    if value.is_nan() || value <= 0.0 {
+        return Err(D::Error::custom(format!(
+            "temperature should be positive, got {}",
+            value
+        )));
+    }
+
+    Ok(value)
+}
+
+impl Config for LossConfig {}
+
+impl LossConfig {
+    pub fn init<B: Backend>(&self) -> Loss {
+        match self {
+            LossConfig::Contrastive {
+                margin_positive,
+                margin_negative,
+            } => Loss::Contrastive(
+                ContrastiveLossConfig::new().init::<B>(*margin_positive, *margin_negative),
+            ),
+            LossConfig::TripletMargin { margin } => {
+                Loss::TripletMargin(TripletMarginLossConfig::new().with_margin(*margin).init())
+            }
+            LossConfig::InfoNce { temperature } => Loss::InfoNce(
+                InfoNceLossConfig::new()
+                    .with_temperature(*temperature)
+                    .init(),
+            ),
+        }
+    }
+}
+
+/// Loss function used for training
+#[derive(Clone, Debug)]
+pub enum Loss {
+    Contrastive(ContrastiveLoss),
+    TripletMargin(TripletMarginLoss),
+    InfoNce(InfoNceLoss),
+}
+
+impl Loss {
+    pub fn forward<B: Backend>(
+        &self,
+        anchors: Tensor<B, 2>,
+        positives: Tensor<B, 2>,
+        negatives: Tensor<B, 2>,
+    ) -> Tensor<B, 1> {
+        match self {
+            Loss::Contrastive(loss) => loss.forward(anchors, positives, negatives),
+            Loss::TripletMargin(loss) => loss.forward(anchors, positives, negatives),
+            Loss::InfoNce(loss) => loss.forward(anchors, positives, negatives),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use burn::backend::Wgpu;
+    use crate::neural::backend::TestBackend;
     use burn::prelude::TensorData;
     use float_cmp::assert_approx_eq;
 
     #[test]
     fn test_contrastive_loss() {
-        type TestBackend = Wgpu;
         let device = &Default::default();
         let loss = ContrastiveLossConfig::new().init::<TestBackend>(1.0, 0.0f64);
 
@@ -93,4 +318,113 @@ mod tests {
         let l = loss.forward(anchor, positive, negative);
         assert_approx_eq!(f32, l.into_data().to_vec::<f32>().unwrap()[0], 0.7928932f32);
     }
+
+    /// Anchors, positive and negative cases shared by tests of losses
+    fn triplets() -> (
+        Tensor<TestBackend, 2>,
+        Tensor<TestBackend, 2>,
+        Tensor<TestBackend, 2>,
+    ) {
+        let device = &Default::default();
+
+        (
+            Tensor::from_data(TensorData::from([[1.0, 0.0], [0.0, 1.0]]), device),
+            Tensor::from_data(TensorData::from([[0.5, 0.5], [0.5, 0.5]]), device),
+            Tensor::from_data(TensorData::from([[-1.0, 0.0], [0.0, 1.0]]), device),
+        )
+    }
+
+    #[test]
+    fn test_triplet_margin_loss() {
+        let (anchor, positive, negative) = triplets();
+        let loss = TripletMarginLossConfig::new().with_margin(1.0).init();
+
+        // max(0.7071 - 2 + 1, 0) and max(0.7071 - 0 + 1, 0)
+        let l = loss.forward(anchor, positive, negative);
+        assert_approx_eq!(
+            f32,
+            l.into_data().to_vec::<f32>().unwrap()[0],
+            0.8535534f32,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_info_nce_loss() {
+        let (anchor, positive, negative) = triplets();
+
+        // Cross entropy over similarities to both positive cases (0.7071) and negative cases:
+        // -0.7071 + ln(2e^0.7071 + e^-1 + e^0) and -0.7071 + ln(2e^0.7071 + e^0 + e^1)
+        let loss = InfoNceLossConfig::new().with_temperature(1.0).init();
+        let l = loss.forward(anchor.clone(), positive.clone(), negative.clone());
+        assert_approx_eq!(
+            f32,
+            l.into_data().to_vec::<f32>().unwrap()[0],
+            1.1637454f32,
+            epsilon = 1e-6
+        );
+
+        let loss = InfoNceLossConfig::new().with_temperature(0.5).init();
+        let l = loss.forward(anchor, positive, negative);
+        assert_approx_eq!(
+            f32,
+            l.into_data().to_vec::<f32>().unwrap()[0],
+            1.1092769f32,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_loss_config_defaults() {
+        let parse = |json: &str| serde_json::from_str::<LossConfig>(json).unwrap();
+
+        assert_eq!(
+            parse(r#"{"type": "info-nce"}"#),
+            LossConfig::InfoNce { temperature: 0.1 }
+        );
+        assert_eq!(
+            parse(r#"{"type": "triplet-margin"}"#),
+            LossConfig::TripletMargin { margin: 1.0 }
+        );
+        assert_eq!(
+            parse(r#"{"type": "contrastive", "margin_negative": 0.5}"#),
+            LossConfig::Contrastive {
+                margin_positive: 1.0,
+                margin_negative: 0.5
+            }
+        );
+    }
+
+    #[test]
+    fn test_loss_config_invalid() {
+        for json in [
+            r#"{"type": "info-nce", "temperature": 0.0}"#,
+            r#"{"type": "info-nce", "temperature": -0.1}"#,
+            r#"{"type": "triplet-margin", "margin": -1.0}"#,
+            r#"{"type": "contrastive", "margin_positive": -0.5}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<LossConfig>(json).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn test_loss_config() {
+        let config: LossConfig =
+            serde_json::from_str(r#"{"type": "triplet-margin", "margin": 1.0}"#).unwrap();
+        let (anchor, positive, negative) = triplets();
+
+        let l = config
+            .init::<TestBackend>()
+            .forward(anchor, positive, negative);
+        assert_approx_eq!(
+            f32,
+            l.into_data().to_vec::<f32>().unwrap()[0],
+            0.8535534f32,
+            epsilon = 1e-6
+        );
+    }
 }
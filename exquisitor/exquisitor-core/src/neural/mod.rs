@@ -1,5 +1,6 @@
 //! Module containing submodules dedicated for artificial neural network
 
+pub mod backend;
 pub mod data;
 pub mod loss;
 pub mod model;
@@ -1,48 +1,63 @@
 //! Module containing model of artificial neural network
 
 use crate::neural::data::SequencesBatch;
-use crate::neural::loss::{ContrastiveLoss, ContrastiveLossConfig};
+use crate::neural::loss::{ContrastiveLoss, Loss, LossConfig};
+use burn::module::Ignored;
 use burn::nn::conv::{Conv1d, Conv1dConfig};
 use burn::nn::{BatchNorm, BatchNormConfig, Dropout, DropoutConfig, Gelu, Linear, LinearConfig};
 use burn::prelude::{Backend, Config, Module, Tensor};
+use burn::record::{BinGzFileRecorder, FullPrecisionSettings, Record, Recorder, RecorderError};
 use burn::tensor::backend::AutodiffBackend;
 use burn::tensor::ops::conv::calculate_conv_output_size;
 use burn::train::{RegressionOutput, TrainOutput, TrainStep, ValidStep};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// Exqusitior artificial neural network model
 #[derive(Module, Debug)]
 pub struct Model<B: Backend> {
-    conv1: Conv1dBlock<B>,
-    conv2: Conv1dBlock<B>,
-    fc1: Linear<B>,
-    fc2: Linear<B>,
-    fc3: Linear<B>,
+    convs: Vec<Conv1dBlock<B>>,
+    linears: Vec<Linear<B>>,
+    pooling: Ignored<Pooling>,
     dropout: Dropout,
     activation: Gelu,
-    loss: ContrastiveLoss,
+    loss: Ignored<Loss>,
 }
 
 impl<B: Backend> Model<B> {
+    /// Replaces the loss function used for training
+    pub fn with_loss(mut self, loss: Loss) -> Self {
+        self.loss = Ignored(loss);
+        self
+    }
+
     /// Forwards the sample
     pub fn forward(&self, input: Tensor<B, 2>) -> Tensor<B, 2> {
         let [batch_size, sequence_length] = input.dims();
 
-        let x = input.reshape([batch_size, 1, sequence_length]).detach();
-        let x = self.conv1.forward(x);
-        let x = self.conv2.forward(x);
+        let mut x = input.reshape([batch_size, 1, sequence_length]).detach();
+        for conv in &self.convs {
+            x = conv.forward(x);
+        }
 
         let [batch_size, channels, sequence_length] = x.dims();
-        let x = x.reshape([batch_size, channels * sequence_length]).detach();
-
-        let x = self.fc1.forward(x);
-        let x = self.activation.forward(x);
-        let x = self.dropout.forward(x);
+        let mut x = match self.pooling.0 {
+            Pooling::Flatten => x.reshape([batch_size, channels * sequence_length]),
+            Pooling::Mean => x.mean_dim(2).reshape([batch_size, channels]),
+            Pooling::Max => x.max_dim(2).reshape([batch_size, channels]),
+        }
+        .detach();
 
-        let x = self.fc2.forward(x);
-        let x = self.activation.forward(x);
-        let x = self.dropout.forward(x);
+        // Every linear layer except the last one, which creates the embedding, is activated
+        let last = self.linears.len().saturating_sub(1);
+        for (i, linear) in self.linears.iter().enumerate() {
+            x = linear.forward(x);
 
-        let x = self.fc3.forward(x);
+            if i < last {
+                x = self.activation.forward(x);
+                x = self.dropout.forward(x);
+            }
+        }
 
         x
     }
@@ -58,7 +73,7 @@ impl<B: Backend> Model<B> {
         let positive_embed = self.forward(positive);
         let negative_embed = self.forward(negative);
 
-        let loss = self.loss.forward(
+        let loss = self.loss.0.forward(
             anchors_embed.clone(),
             positive_embed.clone(),
             negative_embed.clone(),
@@ -81,36 +96,239 @@ impl<B: Backend> ValidStep<SequencesBatch<B>, RegressionOutput<B>> for Model<B>
     }
 }
 
-/// Configuration of the model
+/// Reduction of the output of convolutions before linear layers
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pooling {
+    /// Flattens channels of all positions
+    #[default]
+    Flatten,
+
+    /// Averages every channel over positions
+    Mean,
+
+    /// Takes maximum of every channel over positions
+    Max,
+}
+
+/// Configuration of the convolution block
 #[derive(Config, Debug)]
-pub struct ModelConfig {}
+pub struct ConvBlockConfig {
+    /// Number of output channels
+    pub channels: usize,
+
+    /// Size of the kernel
+    pub kernel_size: usize,
+
+    /// Stride of the kernel
+    #[config(default = 1)]
+    pub stride: usize,
+
+    /// Dilation of the kernel
+    #[config(default = 1)]
+    pub dilation: usize,
+}
+
+/// Version of the layout of the model, saved with its configuration
+pub const MODEL_VERSION: usize = 1;
+
+/// Configuration of the model
+///
+/// Missing fields take default values, which describe the architecture used before it became
+/// configurable, so configuration of older artifacts still loads.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// Version of the layout of saved model; artifacts without it use the layout of version 0
+    #[serde(default)]
+    pub version: usize,
+
+    /// Convolution blocks, applied in order
+    pub conv_blocks: Vec<ConvBlockConfig>,
+
+    /// Reduction of the output of convolutions
+    pub pooling: Pooling,
+
+    /// Sizes of hidden linear layers
+    pub hidden_sizes: Vec<usize>,
+
+    /// Dimension of the embedding
+    pub embedding_dimension: usize,
+
+    /// Margin of contrastive loss for positive cases, unless the training sets other loss
+    pub margin_positive: f64,
+
+    /// Margin of contrastive loss for negative cases, unless the training sets other loss
+    pub margin_negative: f64,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            version: MODEL_VERSION,
+            conv_blocks: vec![
+                ConvBlockConfig::new(16, 16).with_stride(4),
+                ConvBlockConfig::new(32, 8),
+            ],
+            pooling: Pooling::Flatten,
+            hidden_sizes: vec![4096, 512],
+            embedding_dimension: 64,
+            margin_positive: 1.0,
+            margin_negative: 0.25,
+        }
+    }
+}
+
+impl Config for ModelConfig {}
 
 impl ModelConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_conv_blocks(mut self, conv_blocks: Vec<ConvBlockConfig>) -> Self {
+        self.conv_blocks = conv_blocks;
+        self
+    }
+
+    pub fn with_pooling(mut self, pooling: Pooling) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    pub fn with_hidden_sizes(mut self, hidden_sizes: Vec<usize>) -> Self {
+        self.hidden_sizes = hidden_sizes;
+        self
+    }
+
+    pub fn with_embedding_dimension(mut self, embedding_dimension: usize) -> Self {
+        self.embedding_dimension = embedding_dimension;
+        self
+    }
+
+    pub fn with_margins(mut self, margin_positive: f64, margin_negative: f64) -> Self {
+        self.margin_positive = margin_positive;
+        self.margin_negative = margin_negative;
+        self
+    }
+
+    /// Returns configuration of the contrastive loss with margins of the model
+    pub fn loss_config(&self) -> LossConfig {
+        LossConfig::Contrastive {
+            margin_positive: self.margin_positive,
+            margin_negative: self.margin_negative,
+        }
+    }
+
+    /// Returns number of features entering linear layers for the input of given size
+    pub fn linear_input_size(&self, input_size: usize) -> usize {
+        let mut size = input_size;
+        let mut channels = 1;
+
+        for block in &self.conv_blocks {
+            size = calculate_conv_output_size(
+                block.kernel_size,
+                block.stride,
+                0,
+                block.dilation,
+                size,
+            );
+            channels = block.channels;
+        }
+
+        match self.pooling {
+            Pooling::Flatten => size * channels,
+            Pooling::Mean | Pooling::Max => channels,
+        }
+    }
+
     pub fn init<B: Backend>(
         &self,
         device: &B::Device,
         input_size: usize,
         dropout: f64,
     ) -> Model<B> {
-        let linear_input_size = calculate_conv_output_size(
-            8,
-            1,
-            0,
-            1,
-            calculate_conv_output_size(16, 4, 0, 1, input_size),
-        );
+        let mut channels_in = 1;
+        let convs = self
+            .conv_blocks
+            .iter()
+            .map(|block| {
+                let conv = Conv1dBlock::new(
+                    channels_in,
+                    block.channels,
+                    block.kernel_size,
+                    block.dilation,
+                    block.stride,
+                    dropout,
+                    device,
+                );
+                channels_in = block.channels;
+                conv
+            })
+            .collect();
+
+        let sizes = std::iter::once(self.linear_input_size(input_size))
+            .chain(self.hidden_sizes.iter().copied())
+            .chain(std::iter::once(self.embedding_dimension))
+            .collect::<Vec<_>>();
+        let linears = sizes
+            .windows(2)
+            .map(|sizes| LinearConfig::new(sizes[0], sizes[1]).init(device))
+            .collect();
 
         Model {
-            conv1: Conv1dBlock::new(1, 16, 16, 1, 4, dropout, device),
-            conv2: Conv1dBlock::new(16, 32, 8, 1, 1, dropout, device),
-            fc1: LinearConfig::new(linear_input_size * 32, 4096).init(device),
-            fc2: LinearConfig::new(4096, 512).init(device),
-            fc3: LinearConfig::new(512, 64).init(device),
-            loss: ContrastiveLossConfig::new().init::<B>(1.0, 0.25),
+            convs,
+            linears,
+            pooling: Ignored(self.pooling),
+            loss: Ignored(self.loss_config().init::<B>()),
             dropout: DropoutConfig::new(dropout).init(),
             activation: Gelu::default(),
         }
     }
+
+    /// Loads record of the model saved in the layout of the version of this configuration
+    pub fn load_record<B: Backend>(
+        &self,
+        path: PathBuf,
+        device: &B::Device,
+    ) -> Result<ModelRecord<B>, RecorderError> {
+        let recorder = BinGzFileRecorder::<FullPrecisionSettings>::new();
+
+        match self.version {
+            0 => recorder
+                .load::<LegacyModelRecord<B>>(path, device)
+                .map(Into::into),
+            _ => recorder.load(path, device),
+        }
+    }
+}
+
+/// Record of the model saved before the architecture became configurable (version 0)
+///
+/// The layout had two convolution blocks and three linear layers, each saved in its own field.
+#[derive(Record)]
+pub struct LegacyModelRecord<B: Backend> {
+    conv1: <Conv1dBlock<B> as Module<B>>::Record,
+    conv2: <Conv1dBlock<B> as Module<B>>::Record,
+    fc1: <Linear<B> as Module<B>>::Record,
+    fc2: <Linear<B> as Module<B>>::Record,
+    fc3: <Linear<B> as Module<B>>::Record,
+    dropout: <Dropout as Module<B>>::Record,
+    activation: <Gelu as Module<B>>::Record,
+    loss: <ContrastiveLoss as Module<B>>::Record,
+}
+
+impl<B: Backend> From<LegacyModelRecord<B>> for ModelRecord<B> {
+    fn from(value: LegacyModelRecord<B>) -> Self {
+        Self {
+            convs: vec![value.conv1, value.conv2],
+            linears: vec![value.fc1, value.fc2, value.fc3],
+            pooling: Default::default(),
+            dropout: value.dropout,
+            activation: value.activation,
+            loss: Default::default(),
+        }
+    }
 }
 
 /// Convolution block used by model
@@ -151,3 +369,92 @@ impl<B: Backend> Conv1dBlock<B> {
         self.dropout.forward(x)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neural::backend::TestBackend;
+    use crate::neural::loss::ContrastiveLossConfig;
+    use burn::module::ConstantRecord;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_model_config_defaults() {
+        let config: ModelConfig = serde_json::from_str("{}").unwrap();
+
+        assert_eq!(config.version, 0);
+        assert_eq!(config.conv_blocks.len(), 2);
+        assert_eq!(config.hidden_sizes, vec![4096, 512]);
+        assert_eq!(config.embedding_dimension, 64);
+        assert_eq!(config.linear_input_size(600), 32 * 140);
+        assert_eq!(ModelConfig::new().version, MODEL_VERSION);
+    }
+
+    #[test]
+    fn test_model_config_loss() {
+        let config = ModelConfig::new().with_margins(0.8, 0.1);
+
+        assert_eq!(
+            config.loss_config(),
+            LossConfig::Contrastive {
+                margin_positive: 0.8,
+                margin_negative: 0.1
+            }
+        );
+    }
+
+    #[test]
+    fn test_model_architecture() {
+        let config = ModelConfig::new()
+            .with_conv_blocks(vec![ConvBlockConfig::new(4, 3)])
+            .with_pooling(Pooling::Max)
+            .with_hidden_sizes(vec![8])
+            .with_embedding_dimension(5);
+        let device = Default::default();
+        let model = config.init::<TestBackend>(&device, 40, 0.0);
+
+        assert_eq!(config.linear_input_size(40), 4);
+        assert_eq!(model.convs.len(), 1);
+        assert_eq!(model.linears.len(), 2);
+
+        let input = Tensor::<TestBackend, 2>::zeros([3, 40], &device);
+        assert_eq!(model.forward(input).dims(), [3, 5]);
+    }
+
+    #[test]
+    fn test_load_legacy_record() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("model.bin");
+        let device = Default::default();
+        let model = ModelConfig::new().init::<TestBackend>(&device, 200, 0.0);
+
+        let mut record = model.clone().into_record();
+        let legacy = LegacyModelRecord::<TestBackend> {
+            conv2: record.convs.pop().unwrap(),
+            conv1: record.convs.pop().unwrap(),
+            fc3: record.linears.pop().unwrap(),
+            fc2: record.linears.pop().unwrap(),
+            fc1: record.linears.pop().unwrap(),
+            dropout: ConstantRecord,
+            activation: ConstantRecord,
+            loss: Module::<TestBackend>::into_record(
+                ContrastiveLossConfig::new().init::<TestBackend>(1.0, 0.25),
+            ),
+        };
+        BinGzFileRecorder::<FullPrecisionSettings>::new()
+            .record(legacy, path.clone())
+            .unwrap();
+
+        let config: ModelConfig = serde_json::from_str("{}").unwrap();
+        let record = config.load_record::<TestBackend>(path, &device).unwrap();
+        let loaded = config
+            .init::<TestBackend>(&device, 200, 0.0)
+            .load_record(record);
+
+        let input = Tensor::<TestBackend, 2>::ones([2, 200], &device);
+        model
+            .forward(input.clone())
+            .into_data()
+            .assert_approx_eq(&loaded.forward(input).into_data(), 5);
+    }
+}
@@ -2,7 +2,8 @@
 
 use crate::clustering::ALPHABET;
 use crate::neural::data::{SequencesBatcher, SequencesDataset};
-use crate::neural::model::ModelConfig;
+use crate::neural::loss::LossConfig;
+use crate::neural::model::{ModelConfig, MODEL_VERSION};
 use burn::config::Config;
 use burn::data::dataloader::DataLoaderBuilder;
 use burn::lr_scheduler::exponential::ExponentialLrSchedulerConfig;
@@ -52,10 +53,31 @@ pub struct TrainingConfig {
 
     /// Seed for the backend
     pub seed: u64,
+
+    /// Loss function; contrastive loss with margins of the model, if unset
+    ///
+    /// Takes precedence over margins of the model, which are ignored once the loss is set.
+    pub loss: Option<LossConfig>,
+}
+
+impl TrainingConfig {
+    /// Returns configuration of the loss function used for training
+    pub fn loss_config(&self) -> LossConfig {
+        self.loss
+            .clone()
+            .unwrap_or_else(|| self.model.loss_config())
+    }
 }
 
 /// Trains the given model using given device and stores it's artifacts in given directory
-pub fn train<B: AutodiffBackend>(artifact_dir: &str, config: TrainingConfig, device: B::Device) {
+pub fn train<B: AutodiffBackend>(
+    artifact_dir: &str,
+    mut config: TrainingConfig,
+    device: B::Device,
+) {
+    // Trained model is saved in the current layout, whatever the configuration was read from
+    config.model.version = MODEL_VERSION;
+
     // Create directory for artifacts
     if std::fs::exists(artifact_dir).unwrap() {
         std::fs::remove_dir_all(artifact_dir).unwrap();
@@ -85,6 +107,16 @@ pub fn train<B: AutodiffBackend>(artifact_dir: &str, config: TrainingConfig, dev
         .shuffle(config.seed)
         .build(SequencesDataset::new("data/validation.db").unwrap());
 
+    // Model
+    let model = config
+        .model
+        .init::<B>(
+            &device,
+            config.sequence_length * ALPHABET.len(),
+            config.dropout,
+        )
+        .with_loss(config.loss_config().init::<B>());
+
     // Learning
     let learner = LearnerBuilder::new(artifact_dir)
         .metric_train_numeric(LossMetric::new()) // add metric for
@@ -102,11 +134,7 @@ pub fn train<B: AutodiffBackend>(artifact_dir: &str, config: TrainingConfig, dev
         .with_file_checkpointer(CompactRecorder::new())
         .summary()
         .build(
-            config.model.init::<B>(
-                &device,
-                config.sequence_length * ALPHABET.len(),
-                config.dropout,
-            ),
+            model,
             config.optimizer.init(),
             ExponentialLrSchedulerConfig::new(config.learning_rate, config.gamma).init(),
         );
@@ -0,0 +1,609 @@
+//! Module with taxonomic classification pipeline composed of pluggable stages
+//!
+//! The pipeline loads sequences, clusters them, selects queries from clusters, searches them in
+//! the database and aggregates matches into found organisms. With checkpoints, the dissimilarity
+//! matrix, clusters and matches are saved, so the resumed run skips the completed stages.
+
+use crate::checkpoint::{Artifact, Checkpoints};
+use crate::clustering::cluster::Cluster;
+use crate::clustering::consensus::{Consensus, ConsensusBuilder};
+use crate::clustering::dereplication::Dereplicator;
+use crate::clustering::dissimilarity::DissimilarityMatrix;
+use crate::clustering::traits::{Clustering, Dissimilarities};
+use crate::io::sequence::Sequence;
+use crate::io::traits::{Reader, Record};
+use crate::progress::{report, NoObserver, Observer, Stage};
+use crate::result::{ExquisitorError, ExquisitorErrorKind};
+use crate::searching::organism::{ClusterAgreement, MatchFilter, OrganismFound, OrganismMatch};
+use crate::searching::traits::{Aggregation, DatabaseSearch};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::Result as IoResult;
+
+/// Sequences processed by the pipeline
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct PipelineInput {
+    ids: Vec<String>,
+    sequences: Vec<Sequence>,
+    qualities: Option<Vec<Sequence>>,
+}
+
+impl PipelineInput {
+    pub fn new(sequences: Vec<Sequence>) -> Self {
+        Self {
+            ids: vec![],
+            sequences,
+            qualities: None,
+        }
+    }
+
+    /// Reads identifiers and sequences of all records
+    pub fn from_reader<R>(reader: R) -> IoResult<Self>
+    where
+        R: Reader,
+        R::Iterator: Iterator<Item = IoResult<R::Record>>,
+    {
+        let records = reader.iter().collect::<IoResult<Vec<_>>>()?;
+
+        let (ids, sequences) = records
+            .iter()
+            .map(|record| (record.id().to_string(), record.sequence().clone()))
+            .unzip();
+
+        Ok(Self {
+            ids,
+            sequences,
+            qualities: None,
+        })
+    }
+
+    pub fn with_ids(mut self, ids: Vec<String>) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    pub fn with_qualities(mut self, qualities: Option<Vec<Sequence>>) -> Self {
+        self.qualities = qualities;
+        self
+    }
+
+    pub fn ids(&self) -> &Vec<String> {
+        &self.ids
+    }
+
+    pub fn sequences(&self) -> &Vec<Sequence> {
+        &self.sequences
+    }
+
+    pub fn qualities(&self) -> Option<&Vec<Sequence>> {
+        self.qualities.as_ref()
+    }
+}
+
+/// Selection of queries searched in the database for each cluster
+pub enum QuerySelection {
+    /// Representative of each cluster
+    Representatives,
+
+    /// Given number of the most central members of each cluster
+    ///
+    /// Requires clustering by dissimilarity matrix, otherwise representatives are used.
+    Central(usize),
+
+    /// Consensus sequence of each cluster
+    Consensus(ConsensusBuilder),
+}
+
+/// Clustering stage of the pipeline
+enum ClusteringStage<'a> {
+    /// Clustering of the dissimilarity matrix calculated between sequences
+    Matrix {
+        dissimilarities: Box<dyn Dissimilarities + 'a>,
+        clustering: Box<dyn Clustering<DissimilarityMatrix> + 'a>,
+    },
+
+    /// Clustering working directly on sequences
+    Sequences(Box<dyn for<'s> Clustering<&'s [Sequence]> + 'a>),
+}
+
+/// Clusters created by the pipeline
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PipelineClusters {
+    /// Clusters of all sequences
+    pub clusters: Vec<Cluster>,
+
+    /// The most central members of each cluster, if selected as queries
+    pub central: Option<Vec<Vec<usize>>>,
+}
+
+/// Result of the whole pipeline
+#[derive(Clone, Debug)]
+pub struct PipelineResult {
+    /// Clusters of all sequences
+    pub clusters: Vec<Cluster>,
+
+    /// Consensus sequences of clusters, if used as queries
+    pub consensus: Option<Vec<Consensus>>,
+
+    /// Cluster searched by each query
+    pub queries: Vec<usize>,
+
+    /// Matches of queries found in the database
+    pub matches: Vec<OrganismMatch>,
+
+    /// Found organisms with their abundance
+    pub found: Vec<OrganismFound>,
+
+    /// Agreement of queries of each cluster
+    pub agreements: Vec<ClusterAgreement>,
+}
+
+/// Taxonomic classification pipeline
+///
+/// Sequences are clustered either by dissimilarity matrix, optionally after dereplication, or
+/// directly. Queries selected from clusters are searched in the database and the matches are
+/// aggregated into found organisms, by default with `MatchFilter`.
+///
+/// Every stage reports its progress to the observer and stops once the observer cancels the work.
+pub struct Pipeline<'a> {
+    clustering: ClusteringStage<'a>,
+    dereplicator: Option<Dereplicator>,
+    queries: QuerySelection,
+    search: Option<Box<dyn DatabaseSearch + 'a>>,
+    aggregation: Box<dyn Aggregation + 'a>,
+    observer: &'a dyn Observer,
+    checkpoints: Option<Checkpoints>,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Creates pipeline clustering dissimilarity matrix of sequences
+    pub fn new(
+        dissimilarities: Box<dyn Dissimilarities + 'a>,
+        clustering: Box<dyn Clustering<DissimilarityMatrix> + 'a>,
+    ) -> Self {
+        Self::from_stage(ClusteringStage::Matrix {
+            dissimilarities,
+            clustering,
+        })
+    }
+
+    /// Creates pipeline clustering sequences directly, e.g. with greedy clustering
+    pub fn new_sequence_clustering(
+        clustering: Box<dyn for<'s> Clustering<&'s [Sequence]> + 'a>,
+    ) -> Self {
+        Self::from_stage(ClusteringStage::Sequences(clustering))
+    }
+
+    fn from_stage(clustering: ClusteringStage<'a>) -> Self {
+        Self {
+            clustering,
+            dereplicator: None,
+            queries: QuerySelection::Representatives,
+            search: None,
+            aggregation: Box::new(MatchFilter::new()),
+            observer: &NoObserver,
+            checkpoints: None,
+        }
+    }
+
+    /// Dereplicates sequences before calculating dissimilarity matrix
+    pub fn with_dereplicator(mut self, dereplicator: Dereplicator) -> Self {
+        self.dereplicator = Some(dereplicator);
+        self
+    }
+
+    pub fn with_queries(mut self, queries: QuerySelection) -> Self {
+        self.queries = queries;
+        self
+    }
+
+    pub fn with_search(mut self, search: Box<dyn DatabaseSearch + 'a>) -> Self {
+        self.search = Some(search);
+        self
+    }
+
+    pub fn with_aggregation(mut self, aggregation: Box<dyn Aggregation + 'a>) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: &'a dyn Observer) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Saves artifacts of the stages and reuses them when resuming
+    pub fn with_checkpoints(mut self, checkpoints: Checkpoints) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Checks if the most central members of clusters can be selected as queries
+    pub fn uses_central_members(&self) -> bool {
+        matches!(self.queries, QuerySelection::Central(_))
+            && matches!(self.clustering, ClusteringStage::Matrix { .. })
+    }
+
+    /// Runs the whole pipeline
+    pub fn run(&self, input: &PipelineInput) -> IoResult<PipelineResult> {
+        let clusters = self.cluster(input)?;
+        self.search(input, clusters)
+    }
+
+    /// Clusters sequences of the input
+    pub fn cluster(&self, input: &PipelineInput) -> IoResult<PipelineClusters> {
+        if let Some(clusters) = self.restore(Artifact::Clusters)? {
+            return Ok(clusters);
+        }
+
+        let clusters = self.create_clusters(input)?;
+        self.persist(Artifact::Clusters, &clusters)?;
+
+        Ok(clusters)
+    }
+
+    fn create_clusters(&self, input: &PipelineInput) -> IoResult<PipelineClusters> {
+        let sequences = input.sequences();
+
+        let (dissimilarities, clustering) = match &self.clustering {
+            ClusteringStage::Sequences(clustering) => {
+                return Ok(PipelineClusters {
+                    clusters: clustering.cluster_observed(sequences, self.observer)?,
+                    central: None,
+                });
+            }
+            ClusteringStage::Matrix {
+                dissimilarities,
+                clustering,
+            } => (dissimilarities, clustering),
+        };
+
+        let dereplication = self
+            .dereplicator
+            .as_ref()
+            .map(|dereplicator| dereplicator.dereplicate(sequences));
+        let uniques = match dereplication {
+            Some(ref dereplication) => dereplication.uniques(),
+            None => sequences,
+        };
+
+        let restored = match self.checkpoints {
+            Some(ref checkpoints) => checkpoints.load_matrix()?,
+            None => None,
+        };
+        let distance_matrix = match restored {
+            Some(matrix) => matrix,
+            None => {
+                let matrix = dissimilarities.matrix(uniques, self.observer)?;
+                if let Some(ref checkpoints) = self.checkpoints {
+                    checkpoints.save_matrix(&matrix)?;
+                }
+                matrix
+            }
+        };
+        let matrix = self.uses_central_members().then(|| distance_matrix.clone());
+
+        let clusters = clustering.cluster_observed(distance_matrix, self.observer)?;
+
+        let central = matrix.map(|matrix| {
+            let n = match self.queries {
+                QuerySelection::Central(n) => n,
+                _ => 1,
+            };
+
+            clusters
+                .iter()
+                .map(|cluster| {
+                    let members = cluster.central_members(&matrix, n);
+                    match dereplication {
+                        Some(ref dereplication) => members
+                            .into_iter()
+                            .map(|unique| dereplication.members(unique)[0])
+                            .collect(),
+                        None => members,
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let clusters = match dereplication {
+            Some(dereplication) => dereplication.expand(&clusters),
+            None => clusters,
+        };
+
+        Ok(PipelineClusters { clusters, central })
+    }
+
+    /// Searches queries selected from clusters and aggregates their matches
+    pub fn search(
+        &self,
+        input: &PipelineInput,
+        clusters: PipelineClusters,
+    ) -> IoResult<PipelineResult> {
+        let search = self.search.as_ref().ok_or_else(|| {
+            ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                "Pipeline has no database search stage".to_string(),
+            )
+        })?;
+
+        let PipelineClusters { clusters, central } = clusters;
+        let sequences = input.sequences();
+
+        let (consensus, (representatives, queries)): (_, (Vec<Sequence>, Vec<usize>)) =
+            match &self.queries {
+                QuerySelection::Consensus(builder) => {
+                    let consensus = builder.build_all(
+                        &clusters,
+                        sequences,
+                        input.qualities().map(|qualities| qualities.as_slice()),
+                    )?;
+                    let queries = consensus
+                        .iter()
+                        .map(|c| c.sequence.clone())
+                        .zip(0..clusters.len())
+                        .unzip();
+
+                    (Some(consensus), queries)
+                }
+                _ => {
+                    let members = central.unwrap_or_else(|| {
+                        clusters
+                            .iter()
+                            .map(|cluster| vec![cluster.representative()])
+                            .collect()
+                    });
+
+                    let queries = members
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(cluster, members)| {
+                            members
+                                .iter()
+                                .map(move |&member| (sequences[member].clone(), cluster))
+                        })
+                        .unzip();
+
+                    (None, queries)
+                }
+            };
+
+        let matches = match self.restore(Artifact::Matches)? {
+            Some(matches) => matches,
+            None => {
+                let matches = search.search_observed(representatives, self.observer)?;
+                self.persist(Artifact::Matches, &matches)?;
+                matches
+            }
+        };
+
+        report(self.observer, Stage::Aggregation, 0, 1)?;
+        let (found, agreements) =
+            self.aggregation
+                .aggregate(&matches, &clusters, &queries, sequences.len())?;
+        report(self.observer, Stage::Aggregation, 1, 1)?;
+
+        Ok(PipelineResult {
+            clusters,
+            consensus,
+            queries,
+            matches,
+            found,
+            agreements,
+        })
+    }
+
+    /// Loads the artifact saved by the previous run
+    fn restore<T: DeserializeOwned>(&self, artifact: Artifact) -> IoResult<Option<T>> {
+        match self.checkpoints {
+            Some(ref checkpoints) => checkpoints.load(artifact),
+            None => Ok(None),
+        }
+    }
+
+    /// Saves the artifact for the resumed run
+    fn persist<T: Serialize>(&self, artifact: Artifact, value: &T) -> IoResult<()> {
+        match self.checkpoints {
+            Some(ref checkpoints) => checkpoints.save(artifact, value),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clustering::cluster::KMedoidClustering;
+    use crate::clustering::dissimilarity::KMer;
+    use crate::clustering::greedy::GreedyClustering;
+    use crate::io::fasta::reader::FastaReader;
+    use crate::progress::{Progress, RecordingObserver};
+    use float_cmp::assert_approx_eq;
+    use std::io::{Cursor, ErrorKind};
+
+    /// Database matching sequences by their first base
+    struct FirstBaseSearch;
+
+    impl DatabaseSearch for FirstBaseSearch {
+        fn search(&self, sequences: Vec<Sequence>) -> IoResult<Vec<OrganismMatch>> {
+            Ok(sequences
+                .iter()
+                .enumerate()
+                .map(|(idx, sequence)| {
+                    let name = sequence.content()[..1].to_string();
+                    OrganismMatch::new(idx, name, 100f64)
+                })
+                .collect())
+        }
+    }
+
+    /// Database failing every search
+    struct FailingSearch;
+
+    impl DatabaseSearch for FailingSearch {
+        fn search(&self, _sequences: Vec<Sequence>) -> IoResult<Vec<OrganismMatch>> {
+            Err(std::io::Error::other("Search failed"))
+        }
+    }
+
+    fn create_input() -> PipelineInput {
+        PipelineInput::new(
+            ["AAAAAAAA", "AAAAAAAT", "AAAAAAAA", "TTTTTTTT", "TTTTTTTA"]
+                .iter()
+                .map(|content| Sequence::new(content))
+                .collect(),
+        )
+    }
+
+    fn create_pipeline<'a>() -> Pipeline<'a> {
+        Pipeline::new(Box::new(KMer::new(2)), Box::new(KMedoidClustering::new(2)))
+            .with_search(Box::new(FirstBaseSearch))
+    }
+
+    fn abundance(result: &PipelineResult, name: &str) -> f64 {
+        result
+            .found
+            .iter()
+            .find(|found| found.name() == name)
+            .map(|found| found.quality())
+            .unwrap_or(0f64)
+    }
+
+    #[test]
+    fn test_pipeline_input_from_reader() {
+        let data = ">a\nACTG\n>b\nAAAA\n";
+        let reader = FastaReader::new(Cursor::new(data));
+
+        let input = PipelineInput::from_reader(reader).unwrap();
+
+        assert_eq!(input.ids(), &vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(input.sequences()[1].content(), "AAAA");
+        assert!(input.qualities().is_none());
+    }
+
+    #[test]
+    fn test_pipeline_run() {
+        let result = create_pipeline().run(&create_input()).unwrap();
+
+        assert_eq!(result.clusters.len(), 2);
+        assert_eq!(result.queries, vec![0, 1]);
+        assert!(result.consensus.is_none());
+        assert_approx_eq!(f64, abundance(&result, "A"), 0.6);
+        assert_approx_eq!(f64, abundance(&result, "T"), 0.4);
+    }
+
+    #[test]
+    fn test_pipeline_dereplication_and_central_members() {
+        let pipeline = create_pipeline()
+            .with_dereplicator(Dereplicator::new())
+            .with_queries(QuerySelection::Central(2));
+
+        let result = pipeline.run(&create_input()).unwrap();
+
+        let members = result
+            .clusters
+            .iter()
+            .map(|cluster| cluster.members().len())
+            .sum::<usize>();
+        assert_eq!(members, 5);
+        assert_eq!(result.queries.len(), 4);
+        assert_approx_eq!(f64, abundance(&result, "A"), 0.6);
+    }
+
+    #[test]
+    fn test_pipeline_consensus() {
+        let pipeline =
+            create_pipeline().with_queries(QuerySelection::Consensus(ConsensusBuilder::new()));
+
+        let result = pipeline.run(&create_input()).unwrap();
+
+        assert_eq!(result.consensus.as_ref().unwrap().len(), 2);
+        assert_eq!(result.queries, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_pipeline_sequence_clustering() {
+        let pipeline = Pipeline::new_sequence_clustering(Box::new(GreedyClustering::new(0.8)))
+            .with_queries(QuerySelection::Central(2));
+
+        let clusters = pipeline.cluster(&create_input()).unwrap();
+
+        assert!(!pipeline.uses_central_members());
+        assert_eq!(clusters.clusters.len(), 2);
+        assert!(clusters.central.is_none());
+    }
+
+    #[test]
+    fn test_pipeline_without_search() {
+        let pipeline = Pipeline::new(Box::new(KMer::new(2)), Box::new(KMedoidClustering::new(2)));
+
+        assert!(pipeline.run(&create_input()).is_err());
+    }
+
+    #[test]
+    fn test_pipeline_progress() {
+        let observer = RecordingObserver::new();
+        let pipeline = create_pipeline().with_observer(&observer);
+
+        pipeline.run(&create_input()).unwrap();
+
+        let reported = observer.reported();
+        let matrix = reported
+            .iter()
+            .filter(|progress| progress.stage == Stage::Matrix)
+            .count();
+        assert_eq!(matrix, 6);
+        assert_eq!(
+            reported.last(),
+            Some(&Progress::new(Stage::Aggregation, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_pipeline_cancellation() {
+        let observer = RecordingObserver::new().with_cancel_at(Stage::Clustering);
+        let pipeline = create_pipeline().with_observer(&observer);
+
+        let result = pipeline.run(&create_input());
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Interrupted);
+        assert!(observer
+            .reported()
+            .iter()
+            .all(|progress| progress.stage != Stage::Search));
+    }
+
+    #[test]
+    fn test_pipeline_resume() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpoints = Checkpoints::new(directory.path(), "input")
+            .with_parameters(Artifact::Matrix, "kmer=2")
+            .with_parameters(Artifact::Clusters, "k=2");
+
+        let first = create_pipeline()
+            .with_checkpoints(checkpoints.clone())
+            .run(&create_input())
+            .unwrap();
+        assert!(checkpoints.path(Artifact::Matrix).is_file());
+        assert!(checkpoints.path(Artifact::Matches).is_file());
+
+        // Completed stages are skipped, so the failing search is never called
+        let resumed = Pipeline::new(Box::new(KMer::new(2)), Box::new(KMedoidClustering::new(2)))
+            .with_search(Box::new(FailingSearch))
+            .with_checkpoints(checkpoints.clone().with_resume(true))
+            .run(&create_input())
+            .unwrap();
+        assert_eq!(resumed.clusters, first.clusters);
+        assert_eq!(resumed.matches, first.matches);
+
+        // Changed parameters of the search invalidate only the matches
+        let changed = checkpoints
+            .with_parameters(Artifact::Matches, "database=other")
+            .with_resume(true);
+        let pipeline = Pipeline::new(Box::new(KMer::new(2)), Box::new(KMedoidClustering::new(2)))
+            .with_search(Box::new(FailingSearch))
+            .with_checkpoints(changed);
+        assert!(pipeline.cluster(&create_input()).is_ok());
+        assert!(pipeline.run(&create_input()).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Module with progress reporting and cooperative cancellation of long running work
+
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fmt::Formatter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stage of the work reporting progress
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    /// Embedding batches of sequences
+    Embedding,
+
+    /// Calculating rows of dissimilarity matrix
+    Matrix,
+
+    /// Iterations of clustering
+    Clustering,
+
+    /// Searching shards of queries in database
+    Search,
+
+    /// Aggregating matches into found organisms
+    Aggregation,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Stage::Embedding => "Embedding",
+                Stage::Matrix => "Matrix",
+                Stage::Clustering => "Clustering",
+                Stage::Search => "Search",
+                Stage::Aggregation => "Aggregation",
+            }
+        )
+    }
+}
+
+/// Progress of the stage, as number of done units out of total
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Progress {
+    pub stage: Stage,
+    pub done: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn new(stage: Stage, done: usize, total: usize) -> Self {
+        Self { stage, done, total }
+    }
+
+    /// Returns fraction of done units, or one if the stage has no units
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 1f64;
+        }
+
+        self.done as f64 / self.total as f64
+    }
+}
+
+/// Observer of long running work
+///
+/// Work reports its progress after each unit (row of matrix, clustering iteration, shard of
+/// queries, batch of embeddings) and checks for cancellation at the same time, so it stops
+/// promptly once cancellation is requested.
+pub trait Observer {
+    /// Receives progress of the stage
+    fn progress(&self, _progress: Progress) {}
+
+    /// Checks if the work should be stopped
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Observer ignoring progress and never cancelling the work
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
+
+/// Flag requesting cancellation of work, shared between threads
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation of the work
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Observer for CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        CancellationToken::is_cancelled(self)
+    }
+}
+
+/// Reports progress to the observer and returns error if the work was cancelled
+pub fn report(
+    observer: &dyn Observer,
+    stage: Stage,
+    done: usize,
+    total: usize,
+) -> ExquisitorResult<()> {
+    observer.progress(Progress::new(stage, done, total));
+
+    if observer.is_cancelled() {
+        return Err(ExquisitorError::new(
+            ExquisitorErrorKind::Cancelled,
+            format!("{} cancelled after {} of {}", stage, done, total),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Observer recording reported progress, cancelling the work on request of the test
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct RecordingObserver {
+    reported: std::cell::RefCell<Vec<Progress>>,
+    cancel_after: Option<usize>,
+    cancel_at: Option<Stage>,
+}
+
+#[cfg(test)]
+impl RecordingObserver {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the work once more than given number of progresses is reported
+    pub(crate) fn with_cancel_after(mut self, reports: usize) -> Self {
+        self.cancel_after = Some(reports);
+        self
+    }
+
+    /// Cancels the work once the stage reports progress
+    pub(crate) fn with_cancel_at(mut self, stage: Stage) -> Self {
+        self.cancel_at = Some(stage);
+        self
+    }
+
+    pub(crate) fn reported(&self) -> Vec<Progress> {
+        self.reported.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl Observer for RecordingObserver {
+    fn progress(&self, progress: Progress) {
+        self.reported.borrow_mut().push(progress);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        let reported = self.reported.borrow();
+
+        self.cancel_after
+            .is_some_and(|limit| reported.len() > limit)
+            || reported
+                .iter()
+                .any(|progress| Some(progress.stage) == self.cancel_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    #[test]
+    fn test_progress_fraction() {
+        assert_approx_eq!(f64, Progress::new(Stage::Matrix, 1, 4).fraction(), 0.25);
+        assert_approx_eq!(f64, Progress::new(Stage::Search, 0, 0).fraction(), 1f64);
+    }
+
+    #[test]
+    fn test_report() {
+        let observer = RecordingObserver::new().with_cancel_after(2);
+
+        assert!(report(&observer, Stage::Matrix, 1, 3).is_ok());
+        assert!(report(&observer, Stage::Matrix, 2, 3).is_ok());
+
+        let result = report(&observer, Stage::Matrix, 3, 3);
+        assert_eq!(result.unwrap_err().kind(), &ExquisitorErrorKind::Cancelled);
+        assert_eq!(observer.reported().len(), 3);
+    }
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let shared = token.clone();
+
+        assert!(report(&token, Stage::Clustering, 0, 1).is_ok());
+        shared.cancel();
+        assert!(report(&token, Stage::Clustering, 1, 1).is_err());
+    }
+}
@@ -12,6 +12,9 @@ pub type ExquisitorResult<T> = Result<T, ExquisitorError>;
 pub enum ExquisitorErrorKind {
     UnequalSequenceLengths,
     EmptySequence,
+    InvalidParameter,
+    Cancelled,
+    Io(ErrorKind),
 }
 
 impl fmt::Display for ExquisitorErrorKind {
@@ -23,6 +26,15 @@ impl fmt::Display for ExquisitorErrorKind {
             ExquisitorErrorKind::EmptySequence => {
                 write!(f, "EmptySequence")
             }
+            ExquisitorErrorKind::InvalidParameter => {
+                write!(f, "InvalidParameter")
+            }
+            ExquisitorErrorKind::Cancelled => {
+                write!(f, "Cancelled")
+            }
+            ExquisitorErrorKind::Io(kind) => {
+                write!(f, "Io({})", kind)
+            }
         }
     }
 }
@@ -61,7 +73,19 @@ impl Error for ExquisitorError {
 
 impl From<ExquisitorError> for IoError {
     fn from(value: ExquisitorError) -> Self {
-        IoError::new(ErrorKind::Other, value.to_string())
+        let kind = match value.kind {
+            ExquisitorErrorKind::Cancelled => ErrorKind::Interrupted,
+            ExquisitorErrorKind::Io(kind) => kind,
+            _ => ErrorKind::Other,
+        };
+
+        IoError::new(kind, value.to_string())
+    }
+}
+
+impl From<IoError> for ExquisitorError {
+    fn from(value: IoError) -> Self {
+        ExquisitorError::new(ExquisitorErrorKind::Io(value.kind()), value.to_string())
     }
 }
 
@@ -79,4 +103,12 @@ mod tests {
         assert_eq!(error.kind(), &ExquisitorErrorKind::UnequalSequenceLengths);
         assert_eq!(format!("{}", error), "UnequalSequenceLengths: 1 != 2");
     }
+
+    #[test]
+    fn test_io_error_round_trip() {
+        let error = ExquisitorError::from(IoError::new(ErrorKind::NotFound, "missing"));
+
+        assert_eq!(error.kind(), &ExquisitorErrorKind::Io(ErrorKind::NotFound));
+        assert_eq!(IoError::from(error).kind(), ErrorKind::NotFound);
+    }
 }
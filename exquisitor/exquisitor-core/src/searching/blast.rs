@@ -4,6 +4,7 @@ use crate::io::fasta::record::FastaRecord;
 use crate::io::fasta::writer::FastaWriter;
 use crate::io::sequence::Sequence;
 use crate::io::traits::Writer;
+use crate::progress::{report, NoObserver, Observer, Stage};
 use crate::searching::organism::OrganismMatch;
 use crate::searching::traits::DatabaseSearch;
 use std::fs::File;
@@ -11,12 +12,24 @@ use std::io;
 use std::io::{BufRead, ErrorKind, Seek, SeekFrom};
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
 use tempfile::NamedTempFile;
 
+/// Default number of queries searched by a single BLASTn run
+pub const DEFAULT_SHARD_SIZE: usize = 1000;
+
+/// Interval of checking whether the BLASTn run finished or was cancelled
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Paths for BLASTn program and it's database
+///
+/// Queries are searched in shards of `shard_size` sequences, so the progress can be reported and
+/// the search cancelled between (and during) the BLASTn runs.
 pub struct Blast {
     program_path: String,
     database_path: String,
+    shard_size: usize,
 }
 
 impl Blast {
@@ -24,19 +37,25 @@ impl Blast {
         Self {
             program_path: program_path.into(),
             database_path: database_path.into(),
+            shard_size: DEFAULT_SHARD_SIZE,
         }
     }
 
-    /// Saves the sequences to file
+    pub fn with_shard_size(mut self, shard_size: usize) -> Self {
+        self.shard_size = shard_size.max(1);
+        self
+    }
+
+    /// Saves the sequences to file, numbering them from the identifier of the first one
     pub(crate) fn save_sequences_to_file(
         &self,
-        sequences: &mut Vec<Sequence>,
+        sequences: &[Sequence],
+        first_id: usize,
         input_file: &File,
     ) -> std::io::Result<()> {
-        let records = sequences
-            .iter_mut()
-            .enumerate()
-            .map(|(idx, s)| FastaRecord::new(idx.to_string().as_str(), None, s.to_owned()));
+        let records = sequences.iter().enumerate().map(|(idx, s)| {
+            FastaRecord::new((first_id + idx).to_string().as_str(), None, s.to_owned())
+        });
 
         let mut writer = FastaWriter::new(input_file, None);
 
@@ -49,6 +68,16 @@ impl Blast {
 
     /// Runs the BLASTn program
     pub fn run(&self, input_filepath: &Path, output_filepath: &Path) -> std::io::Result<()> {
+        self.run_observed(input_filepath, output_filepath, &NoObserver)
+    }
+
+    /// Runs the BLASTn program, killing it once the observer cancels the work
+    pub fn run_observed(
+        &self,
+        input_filepath: &Path,
+        output_filepath: &Path,
+        observer: &dyn Observer,
+    ) -> std::io::Result<()> {
         let mut child = Command::new(&self.program_path)
             .env("BLASTDB", &self.database_path)
             .arg("-db")
@@ -58,11 +87,23 @@ impl Blast {
             .arg("-out")
             .arg(output_filepath)
             .arg("-outfmt")
-            .arg("6 qseqid sscinames pident")
+            .arg("6 qseqid sscinames pident evalue qcovs")
             .stdout(Stdio::piped())
             .spawn()?;
 
-        child.wait()?;
+        while child.try_wait()?.is_none() {
+            if observer.is_cancelled() {
+                child.kill()?;
+                child.wait()?;
+
+                return Err(io::Error::new(
+                    ErrorKind::Interrupted,
+                    "BLASTn search cancelled",
+                ));
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
 
         Ok(())
     }
@@ -77,10 +118,11 @@ impl Blast {
             let line_content = line?;
             let row = line_content.trim().split('\t').collect::<Vec<&str>>();
 
-            if row.len() != 3 {
+            if row.len() != 3 && row.len() != 5 {
                 return Err(io::Error::new(
                     ErrorKind::InvalidData,
-                    "Result line should consist of cluster id, scientific name and quality index",
+                    "Result line should consist of cluster id, scientific name, quality index \
+                    and optionally e-value and query coverage",
                 ));
             }
 
@@ -92,11 +134,20 @@ impl Blast {
                 .parse::<f64>()
                 .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
 
-            organisms.push(OrganismMatch::new(
-                sequence_id,
-                row[1].into(),
-                confidence_score,
-            ));
+            let mut organism = OrganismMatch::new(sequence_id, row[1].into(), confidence_score);
+
+            if row.len() == 5 {
+                let evalue = row[3]
+                    .parse::<f64>()
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+                let coverage = row[4]
+                    .parse::<f64>()
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+                organism = organism.with_evalue(evalue).with_coverage(coverage);
+            }
+
+            organisms.push(organism);
         }
 
         Ok(organisms)
@@ -116,36 +167,55 @@ impl Blast {
 }
 
 impl DatabaseSearch for Blast {
-    fn search(&self, mut sequences: Vec<Sequence>) -> std::io::Result<Vec<OrganismMatch>> {
-        let mut input_file = NamedTempFile::new()?;
-        let output_file = NamedTempFile::new()?;
+    fn search(&self, sequences: Vec<Sequence>) -> std::io::Result<Vec<OrganismMatch>> {
+        self.search_observed(sequences, &NoObserver)
+    }
 
-        // Save sequences in temporary file
-        self.save_sequences_to_file(&mut sequences, input_file.as_file())?;
+    fn search_observed(
+        &self,
+        sequences: Vec<Sequence>,
+        observer: &dyn Observer,
+    ) -> io::Result<Vec<OrganismMatch>> {
+        let shards = sequences.len().div_ceil(self.shard_size);
+        let mut organisms = vec![];
+
+        report(observer, Stage::Search, 0, shards)?;
+
+        for (shard, queries) in sequences.chunks(self.shard_size).enumerate() {
+            let mut input_file = NamedTempFile::new()?;
+            let output_file = NamedTempFile::new()?;
 
-        // Run Blast
-        input_file.seek(SeekFrom::Start(0))?;
+            // Save shard in temporary file, keeping identifiers of the whole search
+            self.save_sequences_to_file(queries, shard * self.shard_size, input_file.as_file())?;
+
+            // Run Blast
+            input_file.seek(SeekFrom::Start(0))?;
+            self.run_observed(input_file.path(), output_file.path(), observer)?;
+            organisms.extend(self.parse_results_file(output_file.path())?);
+
+            report(observer, Stage::Search, shard + 1, shards)?;
+        }
 
-        self.search_file(input_file.path(), output_file.path())
+        Ok(organisms)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::{Read, Seek, SeekFrom};
+    use std::io::{Read, Seek, SeekFrom, Write};
 
     // region save_sequences_to_file()
 
     #[test]
     fn test_save_sequences_to_file() {
         let mut file = NamedTempFile::new().unwrap();
-        let mut sequences = vec![Sequence::new("AACT"), Sequence::new("TTGC")];
+        let sequences = vec![Sequence::new("AACT"), Sequence::new("TTGC")];
 
         // Write sequences
         let blast = Blast::new("/blast/blastn".into(), "/blast/db".into());
         blast
-            .save_sequences_to_file(&mut sequences, file.as_file())
+            .save_sequences_to_file(&sequences, 0, file.as_file())
             .unwrap();
 
         // Seek file
@@ -158,6 +228,59 @@ mod tests {
         assert_eq!(result, ">0\nAACT\n>1\nTTGC\n");
     }
 
+    #[test]
+    fn test_save_sequences_to_file_shard() {
+        let mut file = NamedTempFile::new().unwrap();
+        let sequences = vec![Sequence::new("AACT"), Sequence::new("TTGC")];
+
+        let blast = Blast::new("/blast/blastn", "/blast/db").with_shard_size(2);
+        blast
+            .save_sequences_to_file(&sequences, 4, file.as_file())
+            .unwrap();
+
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let result = &mut "".to_string();
+        file.read_to_string(result).unwrap();
+
+        assert_eq!(result, ">4\nAACT\n>5\nTTGC\n");
+    }
+
+    // endregion
+
+    // region parse_results_file()
+
+    #[test]
+    fn test_parse_results_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "0\tEscherichia coli\t99.5\t1e-50\t98\n1\tBacillus subtilis\t87.0\n"
+        )
+        .unwrap();
+
+        let blast = Blast::new("/blast/blastn", "/blast/db");
+        let organisms = blast.parse_results_file(file.path()).unwrap();
+
+        assert_eq!(organisms.len(), 2);
+        assert_eq!(organisms[0].name(), "Escherichia coli");
+        assert_eq!(organisms[0].evalue(), Some(1e-50));
+        assert_eq!(organisms[0].coverage(), Some(98f64));
+        assert_eq!(organisms[1].sequence_id(), 1);
+        assert_eq!(organisms[1].evalue(), None);
+    }
+
+    #[test]
+    fn test_parse_results_file_invalid() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "0\tEscherichia coli\t99.5\t1e-50").unwrap();
+
+        let blast = Blast::new("/blast/blastn", "/blast/db");
+        let result = blast.parse_results_file(file.path());
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
     // endregion
 
     // region search
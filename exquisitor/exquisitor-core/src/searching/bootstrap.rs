@@ -0,0 +1,360 @@
+//! Module with bootstrap estimation of organism abundance
+
+use crate::clustering::cluster::Cluster;
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
+use crate::searching::organism::{
+    cluster_sizes, BootstrapEstimate, ClusterAgreement, MatchFilter, MatchSelection, OrganismFound,
+    OrganismMatch,
+};
+use crate::searching::traits::Aggregation;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Binomial, Distribution};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Unit resampled in each bootstrap iteration
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BootstrapUnit {
+    /// Resamples reads, so sizes of clusters follow multinomial distribution
+    Reads,
+
+    /// Resamples whole clusters
+    Clusters,
+}
+
+/// Bootstrap estimation of organism abundance
+///
+/// Matches are selected once and re-aggregated with resampled cluster sizes in each iteration.
+/// Confidence intervals are percentile intervals of the resampled abundances.
+pub struct Bootstrap {
+    iterations: usize,
+    confidence: f64,
+    unit: BootstrapUnit,
+    seed: u64,
+}
+
+impl Bootstrap {
+    pub fn new(iterations: usize) -> Self {
+        Self {
+            iterations,
+            confidence: 0.95,
+            unit: BootstrapUnit::Reads,
+            seed: 0,
+        }
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn with_unit(mut self, unit: BootstrapUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Aggregates matched organisms like `filter_matches` and attaches bootstrap estimates
+    pub fn estimate(
+        &self,
+        matches: &[OrganismMatch],
+        clusters: &[Cluster],
+        queries: &[usize],
+        n_sequences: usize,
+        filter: &MatchFilter,
+    ) -> ExquisitorResult<(Vec<OrganismFound>, Vec<ClusterAgreement>)> {
+        if self.iterations < 1 || !(self.confidence > 0f64 && self.confidence < 1f64) {
+            return Err(ExquisitorError::new(
+                ExquisitorErrorKind::InvalidParameter,
+                format!(
+                    "Bootstrap requires positive number of iterations and confidence in range \
+                    (0, 1), got {} and {}",
+                    self.iterations, self.confidence
+                ),
+            ));
+        }
+
+        let selection = MatchSelection::new(matches, clusters.len(), queries, filter)?;
+        let sizes = cluster_sizes(clusters);
+        let n_sequences = n_sequences as f64;
+
+        let point = selection.score(&to_f64(&sizes), n_sequences);
+
+        let mut samples = HashMap::<String, Vec<f64>>::new();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        for _ in 0..self.iterations {
+            let resampled = self.resample(&sizes, &mut rng);
+            let total = sizes.iter().sum::<usize>();
+
+            // Keeps the scale of the original number of sequences
+            let scale = if total > 0 {
+                resampled.iter().sum::<usize>() as f64 / total as f64
+            } else {
+                1f64
+            };
+
+            let scores = selection.score(&to_f64(&resampled), n_sequences * scale);
+
+            for name in point.keys() {
+                samples
+                    .entry(name.clone())
+                    .or_default()
+                    .push(scores.get(name).copied().unwrap_or(0f64));
+            }
+        }
+
+        let alpha = (1f64 - self.confidence) / 2f64;
+        let found = point
+            .into_iter()
+            .map(|(name, quality)| {
+                let mut values = samples.remove(&name).unwrap_or_default();
+                values.sort_by(f64::total_cmp);
+
+                let estimate = BootstrapEstimate {
+                    mean: values.iter().sum::<f64>() / values.len() as f64,
+                    lower: quantile(&values, alpha),
+                    upper: quantile(&values, 1f64 - alpha),
+                    confidence: self.confidence,
+                };
+
+                OrganismFound::new(name, quality).with_bootstrap(estimate)
+            })
+            .collect();
+
+        Ok((found, selection.into_agreements()))
+    }
+
+    /// Draws new sizes of clusters
+    fn resample(&self, sizes: &[usize], rng: &mut StdRng) -> Vec<usize> {
+        let mut resampled = vec![0usize; sizes.len()];
+        if sizes.is_empty() {
+            return resampled;
+        }
+
+        match self.unit {
+            BootstrapUnit::Reads => {
+                // Multinomial draw as a chain of binomials conditioned on the remaining reads
+                let mut remaining_reads = sizes.iter().sum::<usize>();
+                let mut remaining_size = remaining_reads;
+
+                for (cluster, &size) in sizes.iter().enumerate() {
+                    if remaining_reads == 0 || remaining_size == 0 {
+                        break;
+                    }
+
+                    let probability = f64::min(1f64, size as f64 / remaining_size as f64);
+                    let drawn = Binomial::new(remaining_reads as u64, probability)
+                        .expect("Probability is in range [0, 1]")
+                        .sample(rng) as usize;
+
+                    resampled[cluster] = drawn;
+                    remaining_reads -= drawn;
+                    remaining_size -= size;
+                }
+            }
+            BootstrapUnit::Clusters => {
+                for _ in 0..sizes.len() {
+                    let cluster = rng.gen_range(0..sizes.len());
+                    resampled[cluster] += sizes[cluster];
+                }
+            }
+        }
+
+        resampled
+    }
+}
+
+/// Aggregation of matches with bootstrap estimates of abundance
+pub struct BootstrapAggregation {
+    bootstrap: Bootstrap,
+    filter: MatchFilter,
+}
+
+impl BootstrapAggregation {
+    pub fn new(bootstrap: Bootstrap, filter: MatchFilter) -> Self {
+        Self { bootstrap, filter }
+    }
+}
+
+impl Aggregation for BootstrapAggregation {
+    fn aggregate(
+        &self,
+        matches: &[OrganismMatch],
+        clusters: &[Cluster],
+        queries: &[usize],
+        n_sequences: usize,
+    ) -> ExquisitorResult<(Vec<OrganismFound>, Vec<ClusterAgreement>)> {
+        self.bootstrap
+            .estimate(matches, clusters, queries, n_sequences, &self.filter)
+    }
+}
+
+fn to_f64(sizes: &[usize]) -> Vec<f64> {
+    sizes.iter().map(|&size| size as f64).collect()
+}
+
+/// Calculates quantile of sorted values with linear interpolation
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0f64;
+    }
+
+    let position = q * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::assert_approx_eq;
+
+    fn create_clusters() -> Vec<Cluster> {
+        vec![
+            Cluster::new(0, (0..90).collect()),
+            Cluster::new(90, (90..100).collect()),
+        ]
+    }
+
+    fn create_matches() -> Vec<OrganismMatch> {
+        vec![
+            OrganismMatch::new(0, "A".into(), 100f64),
+            OrganismMatch::new(1, "B".into(), 100f64),
+        ]
+    }
+
+    #[test]
+    fn test_quantile() {
+        let values = [1f64, 2f64, 3f64, 4f64, 5f64];
+
+        assert_approx_eq!(f64, quantile(&values, 0f64), 1f64);
+        assert_approx_eq!(f64, quantile(&values, 0.5f64), 3f64);
+        assert_approx_eq!(f64, quantile(&values, 0.1f64), 1.4f64);
+        assert_approx_eq!(f64, quantile(&values, 1f64), 5f64);
+    }
+
+    #[test]
+    fn test_bootstrap_estimate() {
+        let bootstrap = Bootstrap::new(200).with_seed(7);
+        let (found, _) = bootstrap
+            .estimate(
+                &create_matches(),
+                &create_clusters(),
+                &[0, 1],
+                100,
+                &MatchFilter::new(),
+            )
+            .unwrap();
+
+        for organism in found.iter() {
+            let estimate = organism.bootstrap().unwrap();
+
+            assert!(estimate.lower <= estimate.mean && estimate.mean <= estimate.upper);
+            assert!(estimate.lower <= organism.quality() && organism.quality() <= estimate.upper);
+            assert!(estimate.lower < estimate.upper);
+        }
+
+        let b = found.iter().find(|f| f.name() == "B").unwrap();
+        assert_approx_eq!(f64, b.quality(), 0.1f64);
+        assert_approx_eq!(f64, b.bootstrap().unwrap().mean, 0.1f64, epsilon = 0.01f64);
+    }
+
+    #[test]
+    fn test_bootstrap_deterministic() {
+        for unit in [BootstrapUnit::Reads, BootstrapUnit::Clusters] {
+            let estimate = || {
+                let (mut found, _) = Bootstrap::new(50)
+                    .with_unit(unit)
+                    .with_seed(3)
+                    .estimate(
+                        &create_matches(),
+                        &create_clusters(),
+                        &[0, 1],
+                        100,
+                        &MatchFilter::new(),
+                    )
+                    .unwrap();
+                found.sort_by(|a, b| a.name().cmp(b.name()));
+                found
+            };
+
+            assert_eq!(estimate(), estimate());
+        }
+    }
+
+    #[test]
+    fn test_resample_reads() {
+        let bootstrap = Bootstrap::new(1);
+        let mut rng = StdRng::seed_from_u64(5);
+
+        for _ in 0..20 {
+            let resampled = bootstrap.resample(&[90, 0, 10], &mut rng);
+
+            assert_eq!(resampled.iter().sum::<usize>(), 100);
+            assert_eq!(resampled[1], 0);
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_single_cluster() {
+        let clusters = vec![Cluster::new(0, vec![0, 1, 2])];
+        let matches = vec![OrganismMatch::new(0, "A".into(), 100f64)];
+
+        let (found, _) = Bootstrap::new(20)
+            .estimate(&matches, &clusters, &[0], 3, &MatchFilter::new())
+            .unwrap();
+
+        let estimate = found[0].bootstrap().unwrap();
+        assert_approx_eq!(f64, estimate.mean, 1f64);
+        assert_approx_eq!(f64, estimate.lower, 1f64);
+        assert_approx_eq!(f64, estimate.upper, 1f64);
+    }
+
+    #[test]
+    fn test_bootstrap_singleton_clusters() {
+        // Representatives are not among elements of clusters of naive clustering
+        let clusters = vec![Cluster::new(0, vec![]), Cluster::new(1, vec![])];
+
+        let (found, _) = Bootstrap::new(50)
+            .with_seed(1)
+            .estimate(
+                &create_matches(),
+                &clusters,
+                &[0, 1],
+                2,
+                &MatchFilter::new(),
+            )
+            .unwrap();
+
+        for organism in found.iter() {
+            assert_approx_eq!(f64, organism.quality(), 0.5f64);
+            assert!(organism.bootstrap().unwrap().upper > 0f64);
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_invalid_confidence() {
+        let result = Bootstrap::new(10).with_confidence(1.5).estimate(
+            &create_matches(),
+            &create_clusters(),
+            &[0, 1],
+            100,
+            &MatchFilter::new(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ExquisitorErrorKind::InvalidParameter
+        );
+    }
+}
@@ -1,6 +1,7 @@
 //! Modules for searching
 
 pub mod blast;
+pub mod bootstrap;
 pub mod organism;
 pub mod quality;
 pub mod traits;
@@ -1,6 +1,8 @@
 use crate::clustering::cluster::Cluster;
+use crate::result::{ExquisitorError, ExquisitorErrorKind, ExquisitorResult};
+use crate::searching::traits::Aggregation;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Read, Write};
 
 /// Organism matched to given sequence
@@ -14,6 +16,14 @@ pub struct OrganismMatch {
 
     /// Confidence score returned by BLASTn
     confidence_score: f64,
+
+    /// Expect value of the hit
+    #[serde(default)]
+    evalue: Option<f64>,
+
+    /// Percentage of the query covered by the hit
+    #[serde(default)]
+    coverage: Option<f64>,
 }
 
 impl OrganismMatch {
@@ -22,9 +32,21 @@ impl OrganismMatch {
             sequence_id,
             name,
             confidence_score,
+            evalue: None,
+            coverage: None,
         }
     }
 
+    pub fn with_evalue(mut self, evalue: f64) -> Self {
+        self.evalue = Some(evalue);
+        self
+    }
+
+    pub fn with_coverage(mut self, coverage: f64) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
     pub fn sequence_id(&self) -> usize {
         self.sequence_id
     }
@@ -36,6 +58,14 @@ impl OrganismMatch {
     pub fn confidence_score(&self) -> f64 {
         self.confidence_score
     }
+
+    pub fn evalue(&self) -> Option<f64> {
+        self.evalue
+    }
+
+    pub fn coverage(&self) -> Option<f64> {
+        self.coverage
+    }
 }
 
 /// Organism found
@@ -48,11 +78,40 @@ pub struct OrganismFound {
 
     /// Quality of found
     quality: f64,
+
+    /// Bootstrap estimate of the quality
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bootstrap: Option<BootstrapEstimate>,
+}
+
+/// Mean and confidence interval of the quality estimated by bootstrap resampling
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct BootstrapEstimate {
+    /// Mean quality over all bootstrap iterations
+    pub mean: f64,
+
+    /// Lower bound of the confidence interval
+    pub lower: f64,
+
+    /// Upper bound of the confidence interval
+    pub upper: f64,
+
+    /// Confidence level of the interval
+    pub confidence: f64,
 }
 
 impl OrganismFound {
     pub fn new(name: String, quality: f64) -> Self {
-        Self { name, quality }
+        Self {
+            name,
+            quality,
+            bootstrap: None,
+        }
+    }
+
+    pub fn with_bootstrap(mut self, bootstrap: BootstrapEstimate) -> Self {
+        self.bootstrap = Some(bootstrap);
+        self
     }
 
     pub fn name(&self) -> &String {
@@ -63,6 +122,10 @@ impl OrganismFound {
         self.quality
     }
 
+    pub fn bootstrap(&self) -> Option<&BootstrapEstimate> {
+        self.bootstrap.as_ref()
+    }
+
     pub fn to_tuple(self) -> (String, f64) {
         (self.name, self.quality)
     }
@@ -89,38 +152,340 @@ pub fn save_found_organisms(
 pub fn load_found_organisms(buffer: &mut dyn Read) -> std::io::Result<Vec<OrganismFound>> {
     let mut data = String::new();
     buffer.read_to_string(&mut data)?;
-    let vec: Vec<OrganismFound> = serde_json::from_str(&data).unwrap();
+    let vec: Vec<OrganismFound> = serde_json::from_str(&data)?;
     Ok(vec)
 }
 
-/// Aggregates matched organisms to create list of found organisms
-pub fn filter_matches(
-    matches: &Vec<OrganismMatch>,
-    clusters: &Vec<Cluster>,
-    n_sequences: usize,
-) -> Vec<OrganismFound> {
-    let mut found = HashMap::<String, f64>::new();
+/// Rule combining hits of multiple queries of the same cluster
+///
+/// Each query votes for the organism of its best hit (the highest confidence score).
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum VotingRule {
+    /// Keeps hits to all organisms
+    Any,
 
-    for organism_match in matches {
-        let cluster = clusters.get(organism_match.sequence_id()).unwrap();
+    /// Keeps hits to the organism with the most votes
+    Majority,
 
-        let match_score = organism_match.confidence_score() * (cluster.sequence_ids().len() as f64)
-            / n_sequences as f64;
+    /// Keeps hits only if all queries with hits voted for the same organism
+    Unanimous,
+}
+
+/// Agreement between queries of single cluster
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ClusterAgreement {
+    /// Index of the cluster
+    pub cluster: usize,
+
+    /// Number of queries searched for the cluster
+    pub queries: usize,
+
+    /// Number of votes for each organism
+    pub votes: BTreeMap<String, usize>,
 
-        match found.get_mut(organism_match.name()) {
-            Some(score) => {
-                *score += match_score;
+    /// Organism with the most votes
+    pub winner: Option<String>,
+
+    /// Fraction of queries which voted for the winner
+    pub agreement: f64,
+}
+
+impl ClusterAgreement {
+    /// Checks if queries of the cluster voted for different organisms (e.g. chimeric or mixed
+    /// cluster)
+    pub fn is_mixed(&self) -> bool {
+        self.votes.len() > 1
+    }
+}
+
+/// Filter and aggregation rules of organism matches
+pub struct MatchFilter {
+    voting_rule: VotingRule,
+    best_hit_only: bool,
+    max_evalue: Option<f64>,
+    min_identity: Option<f64>,
+    min_coverage: Option<f64>,
+    normalize: bool,
+}
+
+impl MatchFilter {
+    pub fn new() -> Self {
+        Self {
+            voting_rule: VotingRule::Any,
+            best_hit_only: true,
+            max_evalue: None,
+            min_identity: None,
+            min_coverage: None,
+            normalize: true,
+        }
+    }
+
+    pub fn with_voting_rule(mut self, voting_rule: VotingRule) -> Self {
+        self.voting_rule = voting_rule;
+        self
+    }
+
+    pub fn with_best_hit_only(mut self, best_hit_only: bool) -> Self {
+        self.best_hit_only = best_hit_only;
+        self
+    }
+
+    pub fn with_max_evalue(mut self, max_evalue: Option<f64>) -> Self {
+        self.max_evalue = max_evalue;
+        self
+    }
+
+    pub fn with_min_identity(mut self, min_identity: Option<f64>) -> Self {
+        self.min_identity = min_identity;
+        self
+    }
+
+    pub fn with_min_coverage(mut self, min_coverage: Option<f64>) -> Self {
+        self.min_coverage = min_coverage;
+        self
+    }
+
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Checks if the match passes all thresholds
+    ///
+    /// Matches without e-value or coverage do not pass the respective threshold.
+    fn passes(&self, organism_match: &OrganismMatch) -> bool {
+        let evalue = self
+            .max_evalue
+            .is_none_or(|max| organism_match.evalue().is_some_and(|evalue| evalue <= max));
+        let identity = self
+            .min_identity
+            .is_none_or(|min| organism_match.confidence_score() >= min);
+        let coverage = self.min_coverage.is_none_or(|min| {
+            organism_match
+                .coverage()
+                .is_some_and(|coverage| coverage >= min)
+        });
+
+        evalue && identity && coverage
+    }
+}
+
+impl Default for MatchFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Matches selected for aggregation together with agreement of queries of clusters
+///
+/// Selection depends only on matches and queries, so it can be scored repeatedly with
+/// different cluster sizes.
+pub(crate) struct MatchSelection<'a> {
+    queries: &'a [usize],
+    queries_count: Vec<usize>,
+    scored: Vec<&'a OrganismMatch>,
+    agreements: Vec<ClusterAgreement>,
+    normalize: bool,
+}
+
+impl<'a> MatchSelection<'a> {
+    /// Drops matches below thresholds, selects best hits and votes of queries
+    pub(crate) fn new(
+        matches: &'a [OrganismMatch],
+        n_clusters: usize,
+        queries: &'a [usize],
+        filter: &MatchFilter,
+    ) -> ExquisitorResult<Self> {
+        let mut queries_count = vec![0usize; n_clusters];
+        for &cluster in queries {
+            *queries_count.get_mut(cluster).ok_or_else(|| {
+                ExquisitorError::new(
+                    ExquisitorErrorKind::InvalidParameter,
+                    format!(
+                        "Query refers to cluster {} out of {} clusters",
+                        cluster, n_clusters
+                    ),
+                )
+            })? += 1;
+        }
+
+        let mut accepted = vec![];
+        for organism_match in matches {
+            if organism_match.sequence_id() >= queries.len() {
+                return Err(ExquisitorError::new(
+                    ExquisitorErrorKind::InvalidParameter,
+                    format!(
+                        "Match refers to query {} out of {} queries",
+                        organism_match.sequence_id(),
+                        queries.len()
+                    ),
+                ));
             }
-            None => {
-                found.insert(organism_match.name().into(), match_score);
+
+            if filter.passes(organism_match) {
+                accepted.push(organism_match);
             }
         }
+
+        // Best hit of each query
+        let mut best = BTreeMap::<usize, &OrganismMatch>::new();
+        for &organism_match in accepted.iter() {
+            best.entry(organism_match.sequence_id())
+                .and_modify(|current| {
+                    if organism_match.confidence_score() > current.confidence_score() {
+                        *current = organism_match;
+                    }
+                })
+                .or_insert(organism_match);
+        }
+
+        let mut votes = vec![BTreeMap::<String, usize>::new(); n_clusters];
+        for (&query, organism_match) in best.iter() {
+            *votes[queries[query]]
+                .entry(organism_match.name().clone())
+                .or_default() += 1;
+        }
+
+        let agreements = votes
+            .into_iter()
+            .enumerate()
+            .map(|(cluster, votes)| {
+                let winner = votes
+                    .iter()
+                    .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+                    .map(|(name, _)| name.clone());
+                let agreement = match (&winner, queries_count[cluster]) {
+                    (Some(winner), count) if count > 0 => votes[winner] as f64 / count as f64,
+                    _ => 0f64,
+                };
+
+                ClusterAgreement {
+                    cluster,
+                    queries: queries_count[cluster],
+                    votes,
+                    winner,
+                    agreement,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let candidates = if filter.best_hit_only {
+            best.into_values().collect::<Vec<_>>()
+        } else {
+            accepted
+        };
+
+        let scored = candidates
+            .into_iter()
+            .filter(|organism_match| {
+                let agreement = &agreements[queries[organism_match.sequence_id()]];
+                let name = Some(organism_match.name());
+
+                match filter.voting_rule {
+                    VotingRule::Any => true,
+                    VotingRule::Majority => agreement.winner.as_ref() == name,
+                    VotingRule::Unanimous => {
+                        !agreement.is_mixed() && agreement.winner.as_ref() == name
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            queries,
+            queries_count,
+            scored,
+            agreements,
+            normalize: filter.normalize,
+        })
     }
 
-    found
+    /// Scores selected matches using given sizes of clusters and total number of sequences
+    pub(crate) fn score(&self, sizes: &[f64], n_sequences: f64) -> HashMap<String, f64> {
+        let mut found = HashMap::<String, f64>::new();
+
+        for organism_match in self.scored.iter() {
+            let cluster = self.queries[organism_match.sequence_id()];
+
+            let match_score = organism_match.confidence_score() * sizes[cluster]
+                / n_sequences
+                / self.queries_count[cluster] as f64;
+
+            *found.entry(organism_match.name().clone()).or_default() += match_score;
+        }
+
+        // Relative abundance
+        let total = found.values().sum::<f64>();
+        if self.normalize && total > 0f64 {
+            found.values_mut().for_each(|score| *score /= total);
+        }
+
+        found
+    }
+
+    pub(crate) fn into_agreements(self) -> Vec<ClusterAgreement> {
+        self.agreements
+    }
+}
+
+/// Counts sequences of each cluster, including its representative
+pub(crate) fn cluster_sizes(clusters: &[Cluster]) -> Vec<usize> {
+    clusters
         .iter()
-        .map(|(k, v)| OrganismFound::new(k.into(), *v))
-        .collect::<Vec<_>>()
+        .map(|cluster| cluster.members().len())
+        .collect()
+}
+
+/// Aggregates matched organisms to create list of found organisms
+///
+/// Queries are mapped to clusters by `queries`, where `queries[i]` is the cluster searched by
+/// the query with sequence identifier `i`. Matches below thresholds are dropped, then each query
+/// votes for the organism of its best hit. Hits of cluster (only the best ones if configured)
+/// are averaged over its queries, weighted by cluster size and filtered by voting rule. Returns
+/// found organisms and agreement of queries of each cluster.
+pub fn filter_matches(
+    matches: &[OrganismMatch],
+    clusters: &[Cluster],
+    queries: &[usize],
+    n_sequences: usize,
+    filter: &MatchFilter,
+) -> ExquisitorResult<(Vec<OrganismFound>, Vec<ClusterAgreement>)> {
+    let selection = MatchSelection::new(matches, clusters.len(), queries, filter)?;
+    let sizes = cluster_sizes(clusters)
+        .into_iter()
+        .map(|size| size as f64)
+        .collect::<Vec<_>>();
+
+    let found = selection
+        .score(&sizes, n_sequences as f64)
+        .into_iter()
+        .map(|(name, quality)| OrganismFound::new(name, quality))
+        .collect::<Vec<_>>();
+
+    Ok((found, selection.into_agreements()))
+}
+
+impl Aggregation for MatchFilter {
+    fn aggregate(
+        &self,
+        matches: &[OrganismMatch],
+        clusters: &[Cluster],
+        queries: &[usize],
+        n_sequences: usize,
+    ) -> ExquisitorResult<(Vec<OrganismFound>, Vec<ClusterAgreement>)> {
+        filter_matches(matches, clusters, queries, n_sequences, self)
+    }
+}
+
+/// Saves agreement of queries of clusters to file
+pub fn save_agreements(
+    buffer: &mut dyn Write,
+    agreements: &Vec<ClusterAgreement>,
+) -> std::io::Result<()> {
+    let json = serde_json::to_string(&agreements)?;
+    buffer.write_all(json.as_bytes())?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -159,4 +524,171 @@ mod tests {
         assert_eq!(organisms[1].name(), "B");
         assert_approx_eq!(f64, organisms[1].quality(), 1.45);
     }
+
+    fn create_matches() -> Vec<OrganismMatch> {
+        vec![
+            OrganismMatch::new(0, "A".into(), 90f64),
+            OrganismMatch::new(0, "B".into(), 80f64),
+            OrganismMatch::new(1, "A".into(), 95f64),
+            OrganismMatch::new(2, "B".into(), 99f64),
+            OrganismMatch::new(3, "C".into(), 100f64),
+        ]
+    }
+
+    fn create_clusters() -> Vec<Cluster> {
+        vec![
+            Cluster::new(0, vec![0, 1, 2, 3, 4, 5]),
+            Cluster::new(6, vec![6, 7]),
+        ]
+    }
+
+    #[test]
+    fn test_filter_matches_agreement() {
+        let (_, agreements) = filter_matches(
+            &create_matches(),
+            &create_clusters(),
+            &[0, 0, 0, 1],
+            8,
+            &MatchFilter::new(),
+        )
+        .unwrap();
+
+        assert_eq!(agreements.len(), 2);
+        assert_eq!(agreements[0].queries, 3);
+        assert_eq!(agreements[0].votes["A"], 2);
+        assert_eq!(agreements[0].votes["B"], 1);
+        assert_eq!(agreements[0].winner, Some("A".to_string()));
+        assert_approx_eq!(f64, agreements[0].agreement, 2f64 / 3f64);
+        assert!(agreements[0].is_mixed());
+        assert!(!agreements[1].is_mixed());
+        assert_approx_eq!(f64, agreements[1].agreement, 1f64);
+    }
+
+    #[test]
+    fn test_filter_matches_voting_rules() {
+        let score = |found: &Vec<OrganismFound>, name: &str| {
+            found.iter().find(|f| f.name() == name).map(|f| f.quality())
+        };
+        let matches = create_matches();
+        let clusters = create_clusters();
+        let queries = [0, 0, 0, 1];
+
+        let filter = |rule: VotingRule| {
+            let filter = MatchFilter::new()
+                .with_voting_rule(rule)
+                .with_best_hit_only(false)
+                .with_normalize(false);
+            filter_matches(&matches, &clusters, &queries, 8, &filter)
+                .unwrap()
+                .0
+        };
+
+        let any = filter(VotingRule::Any);
+        let majority = filter(VotingRule::Majority);
+        let unanimous = filter(VotingRule::Unanimous);
+
+        // Hits of the first cluster are averaged over its 3 queries and weighted by 6 / 8
+        assert_approx_eq!(f64, score(&any, "A").unwrap(), 185f64 / 3f64 * 0.75);
+        assert_approx_eq!(f64, score(&any, "B").unwrap(), 179f64 / 3f64 * 0.75);
+        assert_approx_eq!(f64, score(&majority, "A").unwrap(), 185f64 / 3f64 * 0.75);
+        assert_eq!(score(&majority, "B"), None);
+        assert_eq!(score(&unanimous, "A"), None);
+        assert_approx_eq!(f64, score(&unanimous, "C").unwrap(), 25f64);
+    }
+
+    #[test]
+    fn test_filter_matches_best_hit_normalized() {
+        let score = |found: &Vec<OrganismFound>, name: &str| {
+            found.iter().find(|f| f.name() == name).map(|f| f.quality())
+        };
+        let mut matches = create_matches();
+        // Many hits of a single query do not inflate the score
+        matches.extend((0..100).map(|_| OrganismMatch::new(3, "C".into(), 90f64)));
+
+        let (found, _) = filter_matches(
+            &matches,
+            &create_clusters(),
+            &[0, 0, 0, 1],
+            8,
+            &MatchFilter::new(),
+        )
+        .unwrap();
+
+        // Best hits: A = (90 + 95) / 3 * 0.75, B = 99 / 3 * 0.75, C = 100 * 0.25
+        let total = 185f64 / 4f64 + 99f64 / 4f64 + 25f64;
+        assert_approx_eq!(f64, score(&found, "A").unwrap(), 185f64 / 4f64 / total);
+        assert_approx_eq!(f64, score(&found, "C").unwrap(), 25f64 / total);
+        assert_approx_eq!(f64, found.iter().map(|f| f.quality()).sum::<f64>(), 1f64);
+    }
+
+    #[test]
+    fn test_filter_matches_thresholds() {
+        let matches = vec![
+            OrganismMatch::new(0, "A".into(), 99f64)
+                .with_evalue(1e-30)
+                .with_coverage(95f64),
+            OrganismMatch::new(1, "B".into(), 99f64)
+                .with_evalue(1e-2)
+                .with_coverage(95f64),
+            OrganismMatch::new(2, "C".into(), 99f64)
+                .with_evalue(1e-30)
+                .with_coverage(20f64),
+            OrganismMatch::new(3, "D".into(), 80f64)
+                .with_evalue(1e-30)
+                .with_coverage(95f64),
+        ];
+        let clusters = (0..4).map(|i| Cluster::new(i, vec![i])).collect::<Vec<_>>();
+        let filter = MatchFilter::new()
+            .with_max_evalue(Some(1e-10))
+            .with_min_coverage(Some(80f64))
+            .with_min_identity(Some(97f64));
+
+        let (found, _) = filter_matches(&matches, &clusters, &[0, 1, 2, 3], 4, &filter).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name(), "A");
+        assert_approx_eq!(f64, found[0].quality(), 1f64);
+    }
+
+    #[test]
+    fn test_filter_matches_singleton_clusters() {
+        // Representatives are not among elements of clusters of naive clustering
+        let matches = vec![
+            OrganismMatch::new(0, "A".into(), 100f64),
+            OrganismMatch::new(1, "B".into(), 100f64),
+        ];
+        let clusters = vec![Cluster::new(0, vec![]), Cluster::new(1, vec![2, 3])];
+
+        let (found, _) = filter_matches(
+            &matches,
+            &clusters,
+            &[0, 1],
+            4,
+            &MatchFilter::new().with_normalize(false),
+        )
+        .unwrap();
+        let score = |name: &str| found.iter().find(|f| f.name() == name).unwrap().quality();
+
+        assert_eq!(cluster_sizes(&clusters), vec![1, 3]);
+        assert_approx_eq!(f64, score("A"), 25f64);
+        assert_approx_eq!(f64, score("B"), 75f64);
+    }
+
+    #[test]
+    fn test_filter_matches_nonexistent_cluster() {
+        let matches = create_matches();
+        let clusters = create_clusters();
+
+        let unknown_query = filter_matches(&matches, &clusters, &[0, 0], 8, &MatchFilter::new());
+        let unknown_cluster =
+            filter_matches(&matches, &clusters, &[0, 0, 0, 5], 8, &MatchFilter::new());
+
+        for result in [unknown_query, unknown_cluster] {
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err().kind(),
+                &ExquisitorErrorKind::InvalidParameter
+            );
+        }
+    }
 }
@@ -1,47 +1,224 @@
 //! Module for calculating quality of search
 
 use crate::searching::organism::OrganismFound;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Result as IoResult, Write};
+
+/// Lineages of organisms, ordered from the most general rank to the organism itself
+pub type Taxonomy = HashMap<String, Vec<String>>;
+
+/// Quality metrics of the search against the reference
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SearchQuality {
+    /// Mean fraction of the reference abundance recovered by the probe
+    pub positive: f64,
+
+    /// Mean ratio of lower to higher abundance over all organisms
+    pub negative: f64,
+
+    /// Fraction of organisms found by the probe that are present in the reference
+    pub precision: f64,
+
+    /// Fraction of organisms in the reference that are found by the probe
+    pub recall: f64,
+
+    /// Harmonic mean of the precision and recall
+    pub f1: f64,
+
+    /// L1 distance between relative abundances, in range [0, 2]
+    pub l1: f64,
+
+    /// Bray–Curtis dissimilarity between relative abundances, in range [0, 1]
+    pub bray_curtis: f64,
+
+    /// Taxonomy-weighted (UniFrac-like) distance between relative abundances, in range [0, 1]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taxonomy_weighted: Option<f64>,
+}
+
+impl SearchQuality {
+    pub fn with_taxonomy_weighted(mut self, distance: f64) -> Self {
+        self.taxonomy_weighted = Some(distance);
+        self
+    }
+}
 
 /// Calculates the quality of search
+///
+/// Organisms are present when their quality is positive. Empty reference or probe is treated as
+/// having no organisms, so comparing two empty results gives perfect scores.
 pub fn calculate_search_quality(
     reference: Vec<OrganismFound>,
     probe: Vec<OrganismFound>,
-) -> (f64, f64) {
-    let reference_map: HashMap<String, f64> = reference.into_iter().map(|s| s.to_tuple()).collect();
+) -> SearchQuality {
+    let reference_map = to_map(reference);
+    let probe_map = to_map(probe);
 
-    let probe_map: HashMap<String, f64> = probe.into_iter().map(|s| s.to_tuple()).collect();
+    let unique: BTreeSet<&String> = reference_map.keys().chain(probe_map.keys()).collect();
 
     // Positive
-    let mut positive = 0f64;
-
-    for (name, quality) in &reference_map {
+    let positive = mean(reference_map.iter().map(|(name, quality)| {
         let probe_quality = probe_map.get(name).unwrap_or(&0.0);
-        positive += f64::min(*quality, *probe_quality) / quality;
-    }
-
-    positive /= reference_map.len() as f64;
+        f64::min(*quality, *probe_quality) / quality
+    }));
 
     // Negative
-    let unique: HashSet<String> = reference_map
-        .keys()
-        .chain(probe_map.keys())
-        .cloned()
-        .collect();
-    let mut negative = 0f64;
-
-    for name in &unique {
+    let negative = mean(unique.iter().map(|&name| {
         let reference_quality = *reference_map.get(name).unwrap_or(&0.0);
         let probe_quality = *probe_map.get(name).unwrap_or(&0.0);
 
-        let common = f64::min(reference_quality, probe_quality);
-        let sum = f64::max(reference_quality, probe_quality);
-        negative += common / sum;
+        f64::min(reference_quality, probe_quality) / f64::max(reference_quality, probe_quality)
+    }));
+
+    // Presence
+    let true_positives = reference_map
+        .keys()
+        .filter(|name| probe_map.contains_key(*name))
+        .count();
+    let precision = ratio(true_positives, probe_map.len());
+    let recall = ratio(true_positives, reference_map.len());
+    let f1 = if precision + recall > 0f64 {
+        2f64 * precision * recall / (precision + recall)
+    } else {
+        0f64
+    };
+
+    // Abundance
+    let reference_relative = relative(&reference_map);
+    let probe_relative = relative(&probe_map);
+
+    let l1 = unique
+        .iter()
+        .map(|&name| {
+            let reference_abundance = reference_relative.get(name).unwrap_or(&0.0);
+            let probe_abundance = probe_relative.get(name).unwrap_or(&0.0);
+            (reference_abundance - probe_abundance).abs()
+        })
+        .sum::<f64>();
+
+    let total = reference_relative.values().sum::<f64>() + probe_relative.values().sum::<f64>();
+    let bray_curtis = if total > 0f64 { l1 / total } else { 0f64 };
+
+    SearchQuality {
+        positive,
+        negative,
+        precision,
+        recall,
+        f1,
+        l1,
+        bray_curtis,
+        taxonomy_weighted: None,
+    }
+}
+
+/// Calculates taxonomy-weighted distance between relative abundances of found organisms
+///
+/// Each rank of the lineage is a branch of unit length in the taxonomy tree. The distance is the
+/// sum of absolute differences of abundance below every node, normalized by the depths of the
+/// organisms as in normalized weighted UniFrac. Organisms without lineage in the taxonomy are
+/// placed under their genus, taken as the first word of the scientific name.
+pub fn calculate_taxonomy_distance(
+    reference: &[OrganismFound],
+    probe: &[OrganismFound],
+    taxonomy: &Taxonomy,
+) -> f64 {
+    let reference_relative = relative(&to_map(reference.to_vec()));
+    let probe_relative = relative(&to_map(probe.to_vec()));
+
+    let mut nodes = HashMap::<Vec<String>, f64>::new();
+    let mut normalizer = 0f64;
+
+    for (abundances, sign) in [(&reference_relative, 1f64), (&probe_relative, -1f64)] {
+        for (name, abundance) in abundances {
+            let lineage = lineage(name, taxonomy);
+            normalizer += lineage.len() as f64 * abundance;
+
+            for rank in 1..=lineage.len() {
+                *nodes.entry(lineage[..rank].to_vec()).or_default() += sign * abundance;
+            }
+        }
+    }
+
+    if normalizer > 0f64 {
+        nodes
+            .values()
+            .map(|difference| difference.abs())
+            .sum::<f64>()
+            / normalizer
+    } else {
+        0f64
+    }
+}
+
+/// Saves quality of search to file
+pub fn save_search_quality(buffer: &mut dyn Write, quality: &SearchQuality) -> IoResult<()> {
+    let json = serde_json::to_string(&quality)?;
+    buffer.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+/// Loads taxonomy mapping names of organisms to their lineages from file
+pub fn load_taxonomy(buffer: &mut dyn Read) -> IoResult<Taxonomy> {
+    let mut data = String::new();
+    buffer.read_to_string(&mut data)?;
+    let taxonomy: Taxonomy = serde_json::from_str(&data)?;
+    Ok(taxonomy)
+}
+
+/// Collects organisms with positive quality, summing qualities of repeated names
+fn to_map(organisms: Vec<OrganismFound>) -> HashMap<String, f64> {
+    let mut map = HashMap::new();
+
+    for (name, quality) in organisms.into_iter().map(|s| s.to_tuple()) {
+        if quality > 0f64 {
+            *map.entry(name).or_default() += quality;
+        }
     }
 
-    negative /= unique.len() as f64;
+    map
+}
+
+fn relative(map: &HashMap<String, f64>) -> HashMap<String, f64> {
+    let total = map.values().sum::<f64>();
 
-    (positive, negative)
+    map.iter()
+        .map(|(name, quality)| (name.clone(), quality / total))
+        .collect()
+}
+
+/// Returns lineage of the organism ending with its name
+fn lineage(name: &String, taxonomy: &Taxonomy) -> Vec<String> {
+    match taxonomy.get(name) {
+        Some(lineage) if lineage.last() == Some(name) => lineage.clone(),
+        Some(lineage) => lineage.iter().chain([name]).cloned().collect(),
+        None => match name.split_whitespace().next() {
+            Some(genus) if genus != name => vec![genus.to_string(), name.clone()],
+            _ => vec![name.clone()],
+        },
+    }
+}
+
+/// Calculates mean of values, or one if there are no values
+fn mean(values: impl Iterator<Item = f64>) -> f64 {
+    let (sum, count) = values.fold((0f64, 0usize), |(sum, count), value| {
+        (sum + value, count + 1)
+    });
+
+    if count > 0 {
+        sum / count as f64
+    } else {
+        1f64
+    }
+}
+
+/// Calculates ratio of counts, or one if the whole is empty
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole > 0 {
+        part as f64 / whole as f64
+    } else {
+        1f64
+    }
 }
 
 #[cfg(test)]
@@ -60,10 +237,10 @@ mod tests {
             OrganismFound::new("B".into(), 2f64),
         ];
 
-        let (positive, negative) = calculate_search_quality(reference, probe);
+        let quality = calculate_search_quality(reference, probe);
 
-        assert_approx_eq!(f64, positive, 0.75);
-        assert_approx_eq!(f64, negative, 0.75);
+        assert_approx_eq!(f64, quality.positive, 0.75);
+        assert_approx_eq!(f64, quality.negative, 0.75);
     }
 
     #[test]
@@ -74,10 +251,10 @@ mod tests {
         ];
         let probe = vec![OrganismFound::new("B".into(), 1f64)];
 
-        let (positive, negative) = calculate_search_quality(reference, probe);
+        let quality = calculate_search_quality(reference, probe);
 
-        assert_approx_eq!(f64, positive, 0.25);
-        assert_approx_eq!(f64, negative, 0.25);
+        assert_approx_eq!(f64, quality.positive, 0.25);
+        assert_approx_eq!(f64, quality.negative, 0.25);
     }
 
     #[test]
@@ -88,9 +265,119 @@ mod tests {
             OrganismFound::new("B".into(), 2f64),
         ];
 
-        let (positive, negative) = calculate_search_quality(reference, probe);
+        let quality = calculate_search_quality(reference, probe);
+
+        assert_approx_eq!(f64, quality.positive, 0.5);
+        assert_approx_eq!(f64, quality.negative, 0.25);
+    }
+
+    #[test]
+    fn test_calculate_search_quality_presence() {
+        let reference = vec![
+            OrganismFound::new("A".into(), 1f64),
+            OrganismFound::new("B".into(), 1f64),
+            OrganismFound::new("C".into(), 2f64),
+        ];
+        let probe = vec![
+            OrganismFound::new("A".into(), 1f64),
+            OrganismFound::new("D".into(), 1f64),
+        ];
+
+        let quality = calculate_search_quality(reference, probe);
+
+        assert_approx_eq!(f64, quality.precision, 0.5);
+        assert_approx_eq!(f64, quality.recall, 1f64 / 3f64);
+        assert_approx_eq!(f64, quality.f1, 0.4);
+    }
+
+    #[test]
+    fn test_calculate_search_quality_abundance() {
+        // Relative abundances: reference (0.5, 0.5, 0), probe (0.25, 0.5, 0.25)
+        let reference = vec![
+            OrganismFound::new("A".into(), 2f64),
+            OrganismFound::new("B".into(), 2f64),
+        ];
+        let probe = vec![
+            OrganismFound::new("A".into(), 1f64),
+            OrganismFound::new("B".into(), 2f64),
+            OrganismFound::new("C".into(), 1f64),
+        ];
+
+        let quality = calculate_search_quality(reference, probe);
+
+        assert_approx_eq!(f64, quality.l1, 0.5);
+        assert_approx_eq!(f64, quality.bray_curtis, 0.25);
+        assert_eq!(quality.taxonomy_weighted, None);
+    }
+
+    #[test]
+    fn test_calculate_search_quality_empty() {
+        let both = calculate_search_quality(vec![], vec![]);
+        assert_approx_eq!(f64, both.positive, 1f64);
+        assert_approx_eq!(f64, both.f1, 1f64);
+        assert_approx_eq!(f64, both.bray_curtis, 0f64);
+
+        let reference =
+            calculate_search_quality(vec![], vec![OrganismFound::new("A".into(), 1f64)]);
+        assert_approx_eq!(f64, reference.positive, 1f64);
+        assert_approx_eq!(f64, reference.negative, 0f64);
+        assert_approx_eq!(f64, reference.precision, 0f64);
+        assert_approx_eq!(f64, reference.recall, 1f64);
+        assert_approx_eq!(f64, reference.f1, 0f64);
+        assert_approx_eq!(f64, reference.l1, 1f64);
+        assert_approx_eq!(f64, reference.bray_curtis, 1f64);
+
+        let probe = calculate_search_quality(vec![OrganismFound::new("A".into(), 1f64)], vec![]);
+        assert_approx_eq!(f64, probe.positive, 0f64);
+        assert_approx_eq!(f64, probe.precision, 1f64);
+        assert_approx_eq!(f64, probe.recall, 0f64);
+        assert_approx_eq!(f64, probe.bray_curtis, 1f64);
+    }
+
+    #[test]
+    fn test_calculate_taxonomy_distance() {
+        let reference = vec![OrganismFound::new("Bacillus subtilis".into(), 1f64)];
+        let sibling = vec![OrganismFound::new("Bacillus cereus".into(), 1f64)];
+        let distant = vec![OrganismFound::new("Escherichia coli".into(), 1f64)];
+        let taxonomy = Taxonomy::new();
+
+        assert_approx_eq!(
+            f64,
+            calculate_taxonomy_distance(&reference, &reference, &taxonomy),
+            0f64
+        );
+        assert_approx_eq!(
+            f64,
+            calculate_taxonomy_distance(&reference, &sibling, &taxonomy),
+            0.5
+        );
+        assert_approx_eq!(
+            f64,
+            calculate_taxonomy_distance(&reference, &distant, &taxonomy),
+            1f64
+        );
+    }
+
+    #[test]
+    fn test_calculate_taxonomy_distance_lineage() {
+        let taxonomy = Taxonomy::from([
+            (
+                "Bacillus subtilis".to_string(),
+                vec!["Bacillota".to_string(), "Bacillus".to_string()],
+            ),
+            (
+                "Listeria monocytogenes".to_string(),
+                vec!["Bacillota".to_string(), "Listeria".to_string()],
+            ),
+        ]);
+        let reference = vec![OrganismFound::new("Bacillus subtilis".into(), 1f64)];
+        let probe = vec![OrganismFound::new("Listeria monocytogenes".into(), 1f64)];
 
-        assert_approx_eq!(f64, positive, 0.5);
-        assert_approx_eq!(f64, negative, 0.25);
+        // Organisms share the phylum, so only two of three ranks differ
+        assert_approx_eq!(
+            f64,
+            calculate_taxonomy_distance(&reference, &probe, &taxonomy),
+            2f64 / 3f64
+        );
     }
 }
@@ -1,8 +1,39 @@
+use crate::clustering::cluster::Cluster;
 use crate::io::sequence::Sequence;
-use crate::searching::organism::OrganismMatch;
+use crate::progress::{report, Observer, Stage};
+use crate::result::ExquisitorResult;
+use crate::searching::organism::{ClusterAgreement, OrganismFound, OrganismMatch};
 use std::io;
 
 pub trait DatabaseSearch {
     /// Searches given sequences in database
     fn search(&self, sequences: Vec<Sequence>) -> io::Result<Vec<OrganismMatch>>;
+
+    /// Searches given sequences in database, reporting searched shards to the observer
+    ///
+    /// Backends without shards report only the start and the end of the search.
+    fn search_observed(
+        &self,
+        sequences: Vec<Sequence>,
+        observer: &dyn Observer,
+    ) -> io::Result<Vec<OrganismMatch>> {
+        report(observer, Stage::Search, 0, 1)?;
+        let matches = self.search(sequences)?;
+        report(observer, Stage::Search, 1, 1)?;
+
+        Ok(matches)
+    }
+}
+
+pub trait Aggregation {
+    /// Aggregates matches of queries selected from clusters into found organisms
+    ///
+    /// `queries[i]` is the cluster searched by the query with sequence identifier `i`.
+    fn aggregate(
+        &self,
+        matches: &[OrganismMatch],
+        clusters: &[Cluster],
+        queries: &[usize],
+        n_sequences: usize,
+    ) -> ExquisitorResult<(Vec<OrganismFound>, Vec<ClusterAgreement>)>;
 }
@@ -1,30 +1,89 @@
 //! Module responsible for training artificial neural network (ANN) model
+//!
+//! Device running the training is selected with `--device <name>` option, GPU by default.
+//! Architecture of the model is read from JSON file given by `--model-config <path>` option,
+//! missing fields taking default values. Loss function is read from JSON file given by
+//! `--loss-config <path>` option, e.g. `{"type": "info-nce", "temperature": 0.1}`.
 
-use burn::backend::wgpu::WgpuDevice;
-use burn::backend::{Autodiff, Wgpu};
+use burn::backend::Autodiff;
+use burn::config::Config;
 use burn::optim::AdamWConfig;
+#[cfg(feature = "ndarray")]
+use exquisitor_core::neural::backend::CpuBackend;
+use exquisitor_core::neural::backend::Device;
+#[cfg(feature = "wgpu")]
+use exquisitor_core::neural::backend::GpuBackend;
+use exquisitor_core::neural::loss::LossConfig;
 use exquisitor_core::neural::model::ModelConfig;
 use exquisitor_core::neural::training::{train, TrainingConfig};
+use std::process::exit;
 
-type LearnBackend = Wgpu<f32, i32>;
+/// Reads value of `--<name> <value>` or `--<name>=<value>` option
+fn get_option(name: &str) -> Option<String> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
 
-fn get_device() -> WgpuDevice {
-    WgpuDevice::default()
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if *arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+/// Reads device from `--device` option
+fn get_device() -> Device {
+    match get_option("device").map(|name| name.parse::<Device>()) {
+        None => Device::default(),
+        Some(Ok(device)) => device,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            exit(2);
+        }
+    }
+}
+
+/// Reads configuration of the model from file given by `--model-config` option
+fn get_model_config() -> ModelConfig {
+    match get_option("model-config").map(|path| ModelConfig::load(&path)) {
+        None => ModelConfig::new(),
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            eprintln!("Cannot load model config: {}", e);
+            exit(2);
+        }
+    }
+}
+
+/// Reads configuration of the loss function from file given by `--loss-config` option
+fn get_loss_config() -> Option<LossConfig> {
+    match get_option("loss-config").map(|path| LossConfig::load(&path)) {
+        None => None,
+        Some(Ok(config)) => Some(config),
+        Some(Err(e)) => {
+            eprintln!("Cannot load loss config: {}", e);
+            exit(2);
+        }
+    }
 }
 
 fn main() {
-    type LearnAutodiffBackend = Autodiff<LearnBackend>;
-
-    let device = get_device();
-    train::<LearnAutodiffBackend>(
-        format!("models/model_final").as_str(),
-        TrainingConfig::new(ModelConfig::new(), AdamWConfig::new(), 44)
-            .with_sequence_length(150)
-            .with_num_workers(1)
-            .with_batch_size(256)
-            .with_learning_rate(1e-6)
-            .with_dropout(0.4)
-            .with_num_epochs(3),
-        device.clone(),
-    );
+    let config = TrainingConfig::new(get_model_config(), AdamWConfig::new(), 44)
+        .with_sequence_length(150)
+        .with_num_workers(1)
+        .with_batch_size(256)
+        .with_learning_rate(1e-6)
+        .with_dropout(0.4)
+        .with_num_epochs(3)
+        .with_loss(get_loss_config());
+    let artifact_dir = "models/model_final";
+
+    match get_device() {
+        #[cfg(feature = "wgpu")]
+        Device::Gpu => train::<Autodiff<GpuBackend>>(artifact_dir, config, Default::default()),
+        #[cfg(feature = "ndarray")]
+        Device::Cpu => train::<Autodiff<CpuBackend>>(artifact_dir, config, Default::default()),
+    }
 }